@@ -0,0 +1,223 @@
+//! Announcements: races, attributes, card names checked against an opcode filter, numbers,
+//! card types and coin sides.
+
+use crate::core::card::set_code_matches;
+use crate::core::database::CardData;
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::messages::{MsgAnnounceRace, MsgRetry, MsgSelectOption, MsgType};
+use crate::core::prompt::{ask, ready, set_prompting, unexpected};
+use crate::core::response::{Prompt, Response};
+use crate::core::scripting::duel_data;
+use mlua::{Lua, MultiValue, Variadic};
+
+/// Cards that may be declared although they are aliases or tokens
+const CARD_MARINE_DOLPHIN: u32 = 78734254;
+const CARD_TWINKLE_MOSS: u32 = 13857930;
+
+/// Run an AnnounceCard filter program against a card (field::is_declarable). Opcodes pop their operands
+/// off a stack, every other value is pushed; the card passes when exactly one non-zero value is left.
+/// Alias cards and tokens can never be declared.
+pub fn is_declarable(cd: &CardData, opcodes: &[u32]) -> bool {
+    let mut stack: Vec<i32> = Vec::new();
+    for &op in opcodes {
+        match op {
+            OPCODE_ADD | OPCODE_SUB | OPCODE_MUL | OPCODE_DIV | OPCODE_AND | OPCODE_OR => {
+                if stack.len() < 2 {
+                    continue;
+                }
+                let rhs = stack.pop().unwrap_or(0);
+                let lhs = stack.pop().unwrap_or(0);
+                stack.push(match op {
+                    OPCODE_ADD => lhs.wrapping_add(rhs),
+                    OPCODE_SUB => lhs.wrapping_sub(rhs),
+                    OPCODE_MUL => lhs.wrapping_mul(rhs),
+                    OPCODE_DIV => lhs.checked_div(rhs).unwrap_or(0),
+                    OPCODE_AND => (lhs != 0 && rhs != 0) as i32,
+                    _ => (lhs != 0 || rhs != 0) as i32,
+                });
+            }
+            OPCODE_NEG | OPCODE_NOT | OPCODE_ISCODE | OPCODE_ISSETCARD | OPCODE_ISTYPE | OPCODE_ISRACE | OPCODE_ISATTRIBUTE => {
+                let Some(value) = stack.pop() else { continue };
+                stack.push(match op {
+                    OPCODE_NEG => value.wrapping_neg(),
+                    OPCODE_NOT => (value == 0) as i32,
+                    OPCODE_ISCODE => (cd.code == value as u32) as i32,
+                    OPCODE_ISSETCARD => set_code_matches(cd.setcode, value as u32) as i32,
+                    OPCODE_ISTYPE => (cd.type_ & value as u32) as i32,
+                    OPCODE_ISRACE => (cd.race & value as u32) as i32,
+                    _ => (cd.attribute & value as u32) as i32,
+                });
+            }
+            _ => stack.push(op as i32),
+        }
+    }
+    if stack.len() != 1 || stack[0] == 0 {
+        return false;
+    }
+    let token = (CardType::MONSTER | CardType::TOKEN).bits();
+    cd.code == CARD_MARINE_DOLPHIN || cd.code == CARD_TWINKLE_MOSS || (cd.alias == 0 && cd.type_ & token != token)
+}
+
+impl DuelData {
+    /// Whether `code` is a card in the database that passes the AnnounceCard `opcodes` filter.
+    pub fn is_declarable_code(&self, code: u32, opcodes: &[u32]) -> bool {
+        let data = self.database.lock().ok().and_then(|mut db| db.query_card(code).ok().flatten());
+        data.is_some_and(|cd| is_declarable(&cd, opcodes))
+    }
+}
+
+/// Let `player` pick one of `options` (string ids) with MSG_SELECT_OPTION; `then` gets the index.
+pub fn select_option<'lua, F>(lua: &'lua Lua, player: u8, options: Vec<u32>, then: F) -> mlua::Result<MultiValue<'lua>>
+where
+    F: for<'a> FnOnce(&'a Lua, usize) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+{
+    let prompt = Prompt::SelectOption { count: options.len() };
+    ask(lua, prompt, &MsgSelectOption { player, options }.encode(), move |lua, answer| match answer {
+        Response::Option(index) => then(lua, index),
+        other => Err(unexpected(other)),
+    })
+}
+
+/// `player` announces `count` of the `available` races or attributes (MSG_ANNOUNCE_RACE / MSG_ANNOUNCE_ATTRIB).
+fn announce_bits(lua: &Lua, prompt: Prompt, msg_type: MsgType, player: u8, count: u8, available: u32) -> mlua::Result<MultiValue<'_>> {
+    let msg = MsgAnnounceRace { player, count, available };
+    ask(lua, prompt, &msg.encode_as(msg_type), |lua, answer| match answer {
+        Response::Value(value) => ready(lua, value),
+        other => Err(unexpected(other)),
+    })
+}
+
+/// `player` declares a card name passing the `opcodes` filter (MSG_ANNOUNCE_CARD). A code that is not in the
+/// database or fails the filter is answered with MSG_RETRY and asked for again.
+fn announce_card(lua: &Lua, player: u8, opcodes: Vec<u32>) -> mlua::Result<MultiValue<'_>> {
+    let msg = MsgSelectOption { player, options: opcodes.clone() };
+    ask(lua, Prompt::AnnounceCard, &msg.encode_as(MsgType::AnnounceCard), move |lua, answer| {
+        let Response::Value(code) = answer else {
+            return Err(unexpected(answer));
+        };
+        {
+            let data = duel_data(lua);
+            let mut data_guard = data.lock().unwrap();
+            if data_guard.is_declarable_code(code, &opcodes) {
+                return ready(lua, code);
+            }
+            data_guard.write_message(&MsgRetry.encode());
+        }
+        announce_card(lua, player, opcodes)
+    })
+}
+
+/// Register the announce functions on the global `Duel` table.
+pub fn register_announce_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.AnnounceRace(player, count, available) -> races
+    set_prompting(lua, &duel_table, "AnnounceRace", |lua, (player, count, available): (u8, u8, u32)| {
+        announce_bits(lua, Prompt::AnnounceRace { count: count as usize, available }, MsgType::AnnounceRace, player, count, available)
+    })?;
+
+    // Duel.AnnounceAttribute(player, count, available) -> attributes
+    set_prompting(lua, &duel_table, "AnnounceAttribute", |lua, (player, count, available): (u8, u8, u32)| {
+        announce_bits(lua, Prompt::AnnounceAttrib { count: count as usize, available }, MsgType::AnnounceAttrib, player, count, available)
+    })?;
+
+    // Duel.AnnounceCard(player[, type | opcodes...]) -> code; a single argument is a card type to match
+    set_prompting(lua, &duel_table, "AnnounceCard", |lua, (player, args): (u8, Variadic<i64>)| {
+        let opcodes: Vec<u32> = match args.len() {
+            0 => vec![(CardType::MONSTER | CardType::SPELL | CardType::TRAP).bits(), OPCODE_ISTYPE],
+            1 => vec![args[0] as u32, OPCODE_ISTYPE],
+            _ => args.iter().map(|&op| op as u32).collect(),
+        };
+        announce_card(lua, player, opcodes)
+    })?;
+
+    // Duel.AnnounceNumber(player, ...) -> number, index
+    set_prompting(lua, &duel_table, "AnnounceNumber", |lua, (player, numbers): (u8, Variadic<u32>)| {
+        if numbers.is_empty() {
+            return Err(mlua::Error::RuntimeError("AnnounceNumber: no numbers to announce".to_string()));
+        }
+        let numbers = numbers.to_vec();
+        let msg = MsgSelectOption { player, options: numbers.clone() };
+        ask(lua, Prompt::AnnounceNumber { count: numbers.len() }, &msg.encode_as(MsgType::AnnounceNumber), move |lua, answer| match answer {
+            Response::Number(index) => ready(lua, (numbers[index], index)),
+            other => Err(unexpected(other)),
+        })
+    })?;
+
+    // Duel.AnnounceType(player) -> 0 monster, 1 spell, 2 trap
+    set_prompting(lua, &duel_table, "AnnounceType", |lua, player: u8| {
+        select_option(lua, player, vec![70, 71, 72], |lua, index| ready(lua, index))
+    })?;
+
+    // Duel.AnnounceCoin(player) -> 1 for heads, 0 for tails
+    set_prompting(lua, &duel_table, "AnnounceCoin", |lua, player: u8| {
+        select_option(lua, player, vec![60, 61], |lua, index| ready(lua, 1 - index.min(1)))
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_declarable;
+    use crate::core::database::CardData;
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::messages::MsgRetry;
+    use crate::core::processor::ProcessResult;
+
+    fn card_data(code: u32, alias: u32, setcode: u64, type_: u32, race: u32) -> CardData {
+        CardData { code, alias, setcode, type_, level: 4, attribute: 0x20, race, attack: 1800, defense: 1000, lscale: 0, rscale: 0, link_marker: 0 }
+    }
+
+    #[test]
+    fn opcode_filters_run_against_card_data() {
+        let dragon = card_data(89631139, 0, 0xdd, 0x11, 0x2000);
+        let alias = card_data(89631140, 89631139, 0xdd, 0x11, 0x2000);
+        let token = card_data(9001, 0, 0, 0x4011, 0x2000);
+        let dragon_or_spell = [0x2000, OPCODE_ISRACE, 0x2, OPCODE_ISTYPE, OPCODE_OR];
+        assert!(is_declarable(&dragon, &dragon_or_spell));
+        assert!(!is_declarable(&alias, &dragon_or_spell), "alternate artworks cannot be declared");
+        assert!(!is_declarable(&token, &dragon_or_spell), "tokens cannot be declared");
+        assert!(!is_declarable(&dragon, &[0xdd, OPCODE_ISSETCARD, 89631139, OPCODE_ISCODE, OPCODE_NOT, OPCODE_AND]), "archetype members but this one");
+        assert!(is_declarable(&dragon, &[3, 4, OPCODE_MUL, 12, OPCODE_SUB, OPCODE_NOT]));
+        assert!(!is_declarable(&dragon, &[1, 1]), "more than one value left");
+    }
+
+    #[test]
+    fn announcements_wait_for_the_player() {
+        let mut duel = Duel::new(0);
+        {
+            let data = duel.data.lock().unwrap();
+            let mut db = data.database.lock().unwrap();
+            db.cache.insert(89631139, card_data(89631139, 0, 0xdd, 0x11, 0x2000));
+            db.cache.insert(5318639, card_data(5318639, 0, 0, 0x2, 0));
+        }
+        duel.execute_operation(duel.lua.load(r#"
+            race = Duel.AnnounceRace(0, 1, RACE_DRAGON + RACE_FIEND)
+            number, index = Duel.AnnounceNumber(1, 2, 4, 6)
+            monster = Duel.AnnounceCard(0, TYPE_MONSTER)
+            kind, coin = Duel.AnnounceType(0), Duel.AnnounceCoin(1)
+        "#).into_function().unwrap()).unwrap();
+        fn answer(duel: &mut Duel, value: i32, expected: ProcessResult) -> Vec<u8> {
+            duel.set_responseb(&value.to_le_bytes());
+            assert_eq!(duel.process(), expected);
+            duel.get_message()
+        }
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.get_message();
+        assert_eq!(answer(&mut duel, 0x20, ProcessResult::Waiting), MsgRetry.encode(), "a race that is not available is asked again");
+        answer(&mut duel, CardRace::DRAGON.bits() as i32, ProcessResult::Waiting);
+        answer(&mut duel, 1, ProcessResult::Waiting);
+        // A spell is not a monster, so the name is asked again
+        assert!(answer(&mut duel, 5318639, ProcessResult::Waiting).starts_with(&MsgRetry.encode()));
+        answer(&mut duel, 89631139, ProcessResult::Waiting);
+        answer(&mut duel, 1, ProcessResult::Waiting);
+        answer(&mut duel, 0, ProcessResult::Continue);
+        let (race, number, index, monster): (u32, u32, usize, u32) = duel.lua.load("return race, number, index, monster").eval().unwrap();
+        assert_eq!((race, number, index, monster), (CardRace::DRAGON.bits(), 4, 1, 89631139));
+        let (kind, coin): (u32, u32) = duel.lua.load("return kind, coin").eval().unwrap();
+        assert_eq!((kind, coin), (1, 1), "spell, and heads for the first option");
+    }
+}
@@ -1,6 +1,7 @@
 use crate::core::enums::*;
 use mlua::{UserData, UserDataMethods};
 use crate::core::types::{CardId, EffectId};
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 
 /// StatBlock stores a card's original/current mutable attributes
@@ -11,6 +12,7 @@ pub struct StatBlock {
     pub level: u32,
     pub rank: u32,
     pub link: u32,
+    pub link_marker: u32,
     pub code2: u32,
     pub lscale: u32,
     pub rscale: u32,
@@ -24,7 +26,7 @@ pub struct StatBlock {
 
 // Default derived above; custom default ensures bitflags are set to empty
 impl Default for StatBlock {
-    fn default() -> Self { StatBlock { type_: CardType::empty(), level: 0, rank: 0, link: 0, code2: 0, lscale: 0, rscale: 0, attribute: CardAttribute::empty(), race: CardRace::empty(), attack: 0, defense: 0, base_attack: 0, base_defense: 0 } }
+    fn default() -> Self { StatBlock { type_: CardType::empty(), level: 0, rank: 0, link: 0, link_marker: 0, code2: 0, lscale: 0, rscale: 0, attribute: CardAttribute::empty(), race: CardRace::empty(), attack: 0, defense: 0, base_attack: 0, base_defense: 0 } }
 }
 
 /// Card structure for osiris core. Does not hold references to other cards or duel.
@@ -34,6 +36,8 @@ pub struct Card {
     // Identity
     pub code: u32,
     pub alias: u32,
+    /// Up to four 16-bit archetype codes
+    pub setcode: u64,
 
     // Stats
     pub original_stats: StatBlock,
@@ -46,12 +50,42 @@ pub struct Card {
     pub owner: u8,
     pub controller: u8,
     pub reason: u32,
+    /// Player responsible for the last move (or the one under way)
+    pub reason_player: u8,
+    /// Where the card is being sent, set before replacement and redirect effects look at the move
+    pub destination: Location,
+    /// Zone picked for the card before it is moved into a monster or spell & trap zone
+    pub to_field_sequence: Option<u8>,
+    /// Position the card takes at its destination when the move sets one (Duel.Remove face-down)
+    pub to_position: Option<CardPosition>,
+    /// Players a hidden card was confirmed to, one bit each; forgotten once it moves or is shuffled
+    pub confirmed_to: u8,
+    /// Summon type in the high bits with the summon location in bits 16..24 (summon_info in C++)
+    pub summon_info: u32,
 
     // Flags
     // Placeholder for now; we can implement CardStatus as bitflags later.
     pub status: CardStatus,
     // Associated effects
     pub effects: Vec<EffectId>,
+
+    // Relations with other cards
+    pub equip_target: Option<CardId>,
+    pub equip_cards: Vec<CardId>,
+    pub effect_target_cards: Vec<CardId>,
+    pub effect_target_owners: Vec<CardId>,
+    /// Cards related by CreateRelation, dropped once this card moves
+    pub card_relations: Vec<CardId>,
+    /// Effects related by CreateEffectRelation or by being activated or targeted, dropped once this card moves
+    pub effect_relations: Vec<EffectId>,
+    /// Materials used to summon this card (set by Card.SetMaterial)
+    pub materials: Vec<CardId>,
+    /// Overlay units attached to this card, bottom first
+    pub xyz_materials: Vec<CardId>,
+    /// Card this one is attached to while it is an overlay unit
+    pub overlay_target: Option<CardId>,
+    // Counter type -> count
+    pub counters: BTreeMap<u16, u16>,
 }
 
 impl Card {
@@ -60,6 +94,7 @@ impl Card {
         Card {
             code,
             alias: 0,
+            setcode: 0,
             original_stats: StatBlock::default(),
             current_stats: StatBlock::default(),
             location: Location::empty(),
@@ -68,11 +103,42 @@ impl Card {
             owner: 0,
             controller: 0,
             reason: 0,
+            reason_player: 0,
+            destination: Location::empty(),
+            to_field_sequence: None,
+            to_position: None,
+            confirmed_to: 0,
+            summon_info: 0,
             status: CardStatus::empty(),
             effects: vec![],
+            equip_target: None,
+            equip_cards: vec![],
+            effect_target_cards: vec![],
+            effect_target_owners: vec![],
+            card_relations: vec![],
+            effect_relations: vec![],
+            materials: vec![],
+            xyz_materials: vec![],
+            overlay_target: None,
+            counters: BTreeMap::new(),
         }
     }
 
+    /// Summon type recorded for the card (summon_info without the location byte).
+    pub fn summon_type(&self) -> u32 {
+        self.summon_info & 0xff00ffff
+    }
+
+    /// Location the card was summoned from.
+    pub fn summon_location(&self) -> u32 {
+        (self.summon_info >> 16) & 0xff
+    }
+
+    /// Whether one of the card's archetype codes belongs to `set_code`.
+    pub fn is_set_card(&self, set_code: u32) -> bool {
+        set_code_matches(self.setcode, set_code)
+    }
+
     /// Mark the given status bits on the card.
     pub fn set_status(&mut self, status: CardStatus) {
         self.status |= status;
@@ -89,6 +155,14 @@ impl Card {
     }
 }
 
+/// Whether one of the packed 16-bit archetype codes in `setcode` belongs to `set_code`.
+/// The low 12 bits name the archetype and the high 4 bits a sub-archetype.
+pub fn set_code_matches(setcode: u64, set_code: u32) -> bool {
+    let set_code = set_code as u64 & 0xffff;
+    (0..4).map(|i| (setcode >> (i * 16)) & 0xffff)
+        .any(|sc| sc != 0 && sc & 0xfff == set_code & 0xfff && sc & set_code == set_code)
+}
+
 impl UserData for CardId {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         // Method: c:RegisterEffect(e) - stub for now
@@ -96,47 +170,9 @@ impl UserData for CardId {
             // Register the effect in the DuelData arena and attach to this card
             let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
                 .expect("DuelData not found in Lua app data");
-            // Copy effect data from userdata and move into arena by making a fresh Effect instance.
-            // Extract fields from userdata, then build a new Effect with cloned fields and newly created registry keys.
+            // Copy the effect into the arena with its own registry keys for the Lua functions.
             if let Ok(e) = effect_ud.borrow::<crate::core::effect::Effect>() {
-                // Recreate registry keys by extracting function and creating a new registry entry for it.
-                let mut cond_key = None;
-                if let Some(k) = &e.condition {
-                    if let Ok(func) = lua.registry_value::<mlua::Function>(k) {
-                        if let Ok(newk) = lua.create_registry_value(func) { cond_key = Some(newk); }
-                    }
-                }
-                let mut cost_key = None;
-                if let Some(k) = &e.cost {
-                    if let Ok(func) = lua.registry_value::<mlua::Function>(k) {
-                        if let Ok(newk) = lua.create_registry_value(func) { cost_key = Some(newk); }
-                    }
-                }
-                let mut target_key = None;
-                if let Some(k) = &e.target {
-                    if let Ok(func) = lua.registry_value::<mlua::Function>(k) {
-                        if let Ok(newk) = lua.create_registry_value(func) { target_key = Some(newk); }
-                    }
-                }
-                let mut op_key = None;
-                if let Some(k) = &e.operation {
-                    if let Ok(func) = lua.registry_value::<mlua::Function>(k) {
-                        if let Ok(newk) = lua.create_registry_value(func) { op_key = Some(newk); }
-                    }
-                }
-                let new_effect = crate::core::effect::Effect {
-                    id: 0,
-                    owner: *self_,
-                    description: e.description,
-                    code: e.code,
-                    type_: e.type_,
-                    range: e.range,
-                    flag: e.flag,
-                    condition: cond_key,
-                    cost: cost_key,
-                    target: target_key,
-                    operation: op_key,
-                };
+                let new_effect = e.duplicate(lua, *self_);
                 let mut data_guard = data.lock().unwrap();
                 let _eid = data_guard.register_effect(new_effect, Some(*self_));
             }
@@ -171,6 +207,50 @@ impl UserData for CardId {
             }
         });
         
+        // Method: c:IsControler(player)
+        methods.add_method("IsControler", |lua, self_, player: u8| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.controller == player))
+        });
+
+        // Method: c:GetOwner()
+        methods.add_method("GetOwner", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.owner as u32).unwrap_or(0))
+        });
+
+        // Method: c:IsAbleToChangeControler() - not locked by EFFECT_CANNOT_CHANGE_CONTROL
+        methods.add_method("IsAbleToChangeControler", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.is_able_to_change_controller(*self_))
+        });
+
+        // Method: c:IsControlerCanBeChanged([ignore_mzone]) - the opponent could also place it in a free zone
+        methods.add_method("IsControlerCanBeChanged", |lua, self_, ignore_mzone: Option<bool>| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            let Some(controller) = data_guard.get_card(*self_).map(|c| c.controller) else {
+                return Ok(false);
+            };
+            let room = ignore_mzone.unwrap_or(false) || data_guard.location_count(1 - controller, Location::MZONE, 0x1f) > 0;
+            Ok(room && data_guard.is_able_to_change_controller(*self_))
+        });
+
+        // Method: c:IsPublic() - both players may see the card
+        methods.add_method("IsPublic", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.is_public(*self_))
+        });
+
         // Method: c:GetLocation() - returns location
         methods.add_method("GetLocation", |lua, self_, ()| {
             // Get the actual location from the duel data
@@ -184,6 +264,622 @@ impl UserData for CardId {
                 Ok(0) // Return 0 if card not found
             }
         });
+
+        // Method: c:IsLocation(location) - a monster still being summoned is not in the Monster Zone yet
+        methods.add_method("IsLocation", |lua, self_, location: u32| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| {
+                c.location.bits() & location != 0
+                    && !(c.location == Location::MZONE && c.status.intersects(CardStatus::SUMMONING | CardStatus::SPSUMMON_STEP))
+            }))
+        });
+
+        // Method: c:GetReason() - reason of the last move, or of the one being decided
+        methods.add_method("GetReason", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.reason).unwrap_or(0))
+        });
+
+        // Method: c:IsReason(reason) - any of the reason bits
+        methods.add_method("IsReason", |lua, self_, reason: u32| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.reason & reason != 0))
+        });
+
+        // Method: c:GetReasonPlayer()
+        methods.add_method("GetReasonPlayer", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.reason_player as u32).unwrap_or(PLAYER_NONE))
+        });
+
+        // Method: c:GetDestination() - where the card is being sent
+        methods.add_method("GetDestination", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.destination.bits()).unwrap_or(0))
+        });
+
+        // Method: c:IsCanBeSpecialSummoned(e, sumtype, sumplayer, nocheck, nolimit[, pos, target_player])
+        methods.add_method("IsCanBeSpecialSummoned", |lua, self_, (effect, sumtype, sumplayer, nocheck, nolimit, pos, target_player): (Option<mlua::AnyUserData>, u32, u8, bool, bool, Option<u32>, Option<u8>)| {
+            let effect = effect.and_then(|ud| ud.borrow::<EffectId>().ok().map(|e| *e));
+            let pos = pos.unwrap_or(CardPosition::FACEUP.bits());
+            crate::core::summon::is_can_be_special_summoned(lua, *self_, effect, sumtype, sumplayer, nocheck, nolimit, pos, target_player.unwrap_or(sumplayer))
+        });
+
+        // Method: c:IsSpecialSummonable() - whether one of the card's summon procedures can be used now
+        methods.add_method("IsSpecialSummonable", |lua, self_, ()| {
+            let player = {
+                let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                    .expect("DuelData not found in Lua app data");
+                let data_guard = data.lock().unwrap();
+                data_guard.get_card(*self_).map(|c| c.controller).unwrap_or(0)
+            };
+            Ok(crate::core::summon::special_summon_procedure(lua, *self_, player)?.is_some())
+        });
+
+        // Method: c:EnableReviveLimit() - the card must be properly summoned before it can be revived
+        methods.add_method("EnableReviveLimit", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let flag = EFFECT_FLAG_CANNOT_DISABLE | EFFECT_FLAG_UNCOPYABLE;
+            let effect = crate::core::effect::Effect::new(0, *self_, 0, EFFECT_REVIVE_LIMIT, EFFECT_TYPE_SINGLE, 0, flag);
+            data.lock().unwrap().register_effect(effect, Some(*self_));
+            Ok(())
+        });
+
+        // Method: c:CompleteProcedure() - mark the card as properly summoned
+        methods.add_method("CompleteProcedure", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let mut data_guard = data.lock().unwrap();
+            if let Some(card) = data_guard.cards.get_mut(self_.0 as usize) {
+                card.set_status(CardStatus::PROC_COMPLETE);
+            }
+            Ok(())
+        });
+
+        // Method: c:IsStatus(status)
+        methods.add_method("IsStatus", |lua, self_, status: u32| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.has_status(CardStatus::from_bits_truncate(status))))
+        });
+
+        // Method: c:GetSummonType()
+        methods.add_method("GetSummonType", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.summon_type()).unwrap_or(0))
+        });
+
+        // Method: c:IsSummonType(sumtype) - sumtype bits must all be present
+        methods.add_method("IsSummonType", |lua, self_, sumtype: u32| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.summon_type() & sumtype == sumtype))
+        });
+
+        // Method: c:IsSetCard(setname)
+        methods.add_method("IsSetCard", |lua, self_, set_code: u32| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.is_set_card(set_code)))
+        });
+
+        // Method: c:IsFusionCode(code, ...) - any of the codes the card counts as for fusion
+        methods.add_method("IsFusionCode", |lua, self_, codes: mlua::Variadic<u32>| {
+            let fusion_codes = crate::core::fusion::fusion_codes(lua, *self_)?;
+            Ok(codes.iter().any(|code| fusion_codes.contains(code)))
+        });
+
+        // Method: c:IsFusionSetCard(setname)
+        methods.add_method("IsFusionSetCard", |lua, self_, set_code: u32| {
+            crate::core::fusion::is_fusion_set_card(lua, *self_, set_code)
+        });
+
+        // Method: c:IsCanBeFusionMaterial([fc])
+        methods.add_method("IsCanBeFusionMaterial", |lua, self_, fusion: Option<CardId>| {
+            crate::core::fusion::is_can_be_fusion_material(lua, *self_, fusion)
+        });
+
+        // Method: c:CheckFusionSubstitute(fc)
+        methods.add_method("CheckFusionSubstitute", |lua, self_, fusion: CardId| {
+            crate::core::fusion::check_fusion_substitute(lua, *self_, fusion)
+        });
+
+        // Method: c:CheckFusionMaterial([mg, gc, chkf]) - mg defaults to the controller's material pool
+        methods.add_method("CheckFusionMaterial", |lua, self_, (materials, gc, chkf): (Option<mlua::AnyUserData>, Option<CardId>, Option<u32>)| {
+            let materials = match materials {
+                Some(ud) => ud.borrow::<crate::core::group::Group>()?.clone(),
+                None => {
+                    let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                        .expect("DuelData not found in Lua app data");
+                    let data_guard = data.lock().unwrap();
+                    let player = data_guard.get_card(*self_).map(|c| c.controller).unwrap_or(0);
+                    crate::core::group::Group(data_guard.fusion_material_pool(player).into_iter().collect())
+                }
+            };
+            crate::core::fusion::check_fusion_material(lua, *self_, materials, gc, chkf.unwrap_or(PLAYER_NONE))
+        });
+
+        // Method: c:SetMaterial(g) - record the materials used to summon the card (nil clears them)
+        methods.add_method("SetMaterial", |lua, self_, materials: Option<mlua::AnyUserData>| {
+            let materials: Vec<CardId> = match materials {
+                Some(ud) => ud.borrow::<crate::core::group::Group>()?.0.iter().copied().collect(),
+                None => Vec::new(),
+            };
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            if let Some(card) = data.lock().unwrap().cards.get_mut(self_.0 as usize) {
+                card.materials = materials;
+            }
+            Ok(())
+        });
+
+        // Method: c:GetMaterial() -> Group
+        methods.add_method("GetMaterial", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            let materials = data_guard.get_card(*self_).map(|c| c.materials).unwrap_or_default();
+            Ok(crate::core::group::Group(materials.into_iter().collect()))
+        });
+
+        // Method: c:GetMaterialCount()
+        methods.add_method("GetMaterialCount", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.materials.len()).unwrap_or(0) as u32)
+        });
+
+        // Method: c:GetLevel() - 0 for Xyz and Link monsters
+        methods.add_method("GetLevel", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_)
+                .filter(|c| !c.current_stats.type_.intersects(CardType::XYZ | CardType::LINK))
+                .map(|c| c.current_stats.level)
+                .unwrap_or(0))
+        });
+
+        // Method: c:IsType(type) - any of the type bits
+        methods.add_method("IsType", |lua, self_, type_: u32| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.current_stats.type_.bits() & type_ != 0))
+        });
+
+        // Method: c:IsFaceup()
+        methods.add_method("IsFaceup", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.position.intersects(CardPosition::FACEUP)))
+        });
+
+        // Method: c:IsTuner([sc]) - usable as the tuner of a synchro summon
+        methods.add_method("IsTuner", |lua, self_, _synchro: Option<CardId>| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.is_tuner(*self_))
+        });
+
+        // Method: c:IsNotTuner() - usable as a non-tuner synchro material
+        methods.add_method("IsNotTuner", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.is_not_tuner(*self_))
+        });
+
+        // Method: c:GetSynchroLevel(sc) - packed as level | alternate << 16
+        methods.add_method("GetSynchroLevel", |lua, self_, synchro: CardId| {
+            crate::core::synchro::synchro_level(lua, *self_, synchro)
+        });
+
+        // Method: c:IsCanBeSynchroMaterial([sc, tuner])
+        methods.add_method("IsCanBeSynchroMaterial", |lua, self_, (synchro, tuner): (Option<CardId>, Option<CardId>)| {
+            crate::core::synchro::is_can_be_synchro_material(lua, *self_, synchro, tuner)
+        });
+
+        // Method: c:GetRitualLevel(rc) - packed as level | alternate << 16
+        methods.add_method("GetRitualLevel", |lua, self_, ritual: CardId| {
+            crate::core::ritual::ritual_level(lua, *self_, ritual)
+        });
+
+        // Method: c:IsCanBeRitualMaterial([rc])
+        methods.add_method("IsCanBeRitualMaterial", |lua, self_, ritual: Option<CardId>| {
+            crate::core::ritual::is_can_be_ritual_material(lua, *self_, ritual)
+        });
+
+        // Method: c:IsReleasableByEffect() - tributable by its controller outside a tribute summon
+        methods.add_method("IsReleasableByEffect", |lua, self_, ()| {
+            let controller = {
+                let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                    .expect("DuelData not found in Lua app data");
+                let data_guard = data.lock().unwrap();
+                data_guard.get_card(*self_).map(|c| c.controller).unwrap_or(0)
+            };
+            crate::core::ritual::is_releasable_by_effect(lua, *self_, controller)
+        });
+
+        // Method: c:GetRank() - 0 for non-Xyz monsters
+        methods.add_method("GetRank", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_)
+                .filter(|c| c.current_stats.type_.contains(CardType::XYZ))
+                .map(|c| c.current_stats.rank)
+                .unwrap_or(0))
+        });
+
+        // Method: c:IsXyzLevel(xyzc, lv)
+        methods.add_method("IsXyzLevel", |lua, self_, (xyz, level): (CardId, u32)| {
+            crate::core::xyz::is_xyz_level(lua, *self_, xyz, level)
+        });
+
+        // Method: c:IsCanBeXyzMaterial([xyzc])
+        methods.add_method("IsCanBeXyzMaterial", |lua, self_, xyz: Option<CardId>| {
+            crate::core::xyz::is_can_be_xyz_material(lua, *self_, xyz)
+        });
+
+        // Method: c:GetLink() - link rating, 0 for non-Link monsters
+        methods.add_method("GetLink", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_)
+                .filter(|c| c.current_stats.type_.contains(CardType::LINK))
+                .map(|c| c.current_stats.link)
+                .unwrap_or(0))
+        });
+
+        // Method: c:GetLinkMarker()
+        methods.add_method("GetLinkMarker", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_)
+                .filter(|c| c.current_stats.type_.contains(CardType::LINK))
+                .map(|c| c.current_stats.link_marker)
+                .unwrap_or(0))
+        });
+
+        // Method: c:IsLinkMarker(marker) - all of the given arrows
+        methods.add_method("IsLinkMarker", |lua, self_, marker: u32| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| {
+                c.current_stats.type_.contains(CardType::LINK) && c.current_stats.link_marker & marker == marker
+            }))
+        });
+
+        // Method: c:GetLinkedZone([player]) - zones seen from `player` (defaults to the controller)
+        methods.add_method("GetLinkedZone", |lua, self_, player: Option<u8>| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            let zones = data_guard.card_linked_zone(*self_);
+            let controller = data_guard.get_card(*self_).map(|c| c.controller).unwrap_or(0);
+            Ok(if player.is_some_and(|p| p != controller) { zones.rotate_left(16) } else { zones })
+        });
+
+        // Method: c:GetLinkedGroup() -> Group of the monsters the card points to
+        methods.add_method("GetLinkedGroup", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(crate::core::group::Group(data_guard.linked_cards(*self_).into_iter().collect()))
+        });
+
+        // Method: c:GetLinkedGroupCount()
+        methods.add_method("GetLinkedGroupCount", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.linked_cards(*self_).len() as u32)
+        });
+
+        // Method: c:IsCanBeLinkMaterial([lc])
+        methods.add_method("IsCanBeLinkMaterial", |lua, self_, link: Option<CardId>| {
+            crate::core::link::is_can_be_link_material(lua, *self_, link)
+        });
+
+        // Method: c:GetLeftScale()
+        methods.add_method("GetLeftScale", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.current_stats.lscale).unwrap_or(0))
+        });
+
+        // Method: c:GetRightScale()
+        methods.add_method("GetRightScale", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.current_stats.rscale).unwrap_or(0))
+        });
+
+        // Method: c:GetCurrentScale() - the scale facing the other pendulum zone
+        methods.add_method("GetCurrentScale", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.current_scale(*self_))
+        });
+
+        // Method: c:GetOverlayGroup() -> Group of the card's overlay units
+        methods.add_method("GetOverlayGroup", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            let overlays = data_guard.get_card(*self_).map(|c| c.xyz_materials).unwrap_or_default();
+            Ok(crate::core::group::Group(overlays.into_iter().collect()))
+        });
+
+        // Method: c:GetOverlayCount()
+        methods.add_method("GetOverlayCount", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.xyz_materials.len()).unwrap_or(0) as u32)
+        });
+
+        // Method: c:GetOverlayTarget() - the Xyz monster this overlay unit is attached to
+        methods.add_method("GetOverlayTarget", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).and_then(|c| c.overlay_target))
+        });
+
+        // Method: c:CheckRemoveOverlayCard(player, count, reason)
+        methods.add_method("CheckRemoveOverlayCard", |lua, self_, (_player, count, _reason): (u8, usize, u32)| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.xyz_materials.len() >= count))
+        });
+
+        // Method: c:GetEquipTarget() - the monster this card is equipped to, or nil
+        methods.add_method("GetEquipTarget", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).and_then(|c| c.equip_target))
+        });
+
+        // Method: c:GetEquipGroup() -> Group of the cards equipped to this one
+        methods.add_method("GetEquipGroup", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            let equips = data_guard.get_card(*self_).map(|c| c.equip_cards).unwrap_or_default();
+            Ok(crate::core::group::Group(equips.into_iter().collect()))
+        });
+
+        // Method: c:GetEquipCount()
+        methods.add_method("GetEquipCount", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.equip_cards.len()).unwrap_or(0) as u32)
+        });
+
+        // Method: c:SetCardTarget(tc) - keep targeting tc while both stay on the field
+        methods.add_method("SetCardTarget", |lua, self_, target: CardId| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            data.lock().unwrap().set_card_target(*self_, target);
+            Ok(())
+        });
+
+        // Method: c:CancelCardTarget(tc)
+        methods.add_method("CancelCardTarget", |lua, self_, target: CardId| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            data.lock().unwrap().cancel_card_target(*self_, target);
+            Ok(())
+        });
+
+        // Method: c:GetCardTarget() -> Group of the cards this one keeps targeting
+        methods.add_method("GetCardTarget", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            let targets = data_guard.get_card(*self_).map(|c| c.effect_target_cards).unwrap_or_default();
+            Ok(crate::core::group::Group(targets.into_iter().collect()))
+        });
+
+        // Method: c:GetFirstCardTarget() - the first card this one targeted, or nil
+        methods.add_method("GetFirstCardTarget", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).and_then(|c| c.effect_target_cards.first().copied()))
+        });
+
+        // Method: c:GetCardTargetCount()
+        methods.add_method("GetCardTargetCount", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).map(|c| c.effect_target_cards.len()).unwrap_or(0) as u32)
+        });
+
+        // Method: c:IsHasCardTarget(tc)
+        methods.add_method("IsHasCardTarget", |lua, self_, target: CardId| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.effect_target_cards.contains(&target)))
+        });
+
+        // Method: c:GetOwnerTarget() -> Group of the cards that keep targeting this one
+        methods.add_method("GetOwnerTarget", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            let owners = data_guard.get_card(*self_).map(|c| c.effect_target_owners).unwrap_or_default();
+            Ok(crate::core::group::Group(owners.into_iter().collect()))
+        });
+
+        // Method: c:CreateRelation(tc, reset) - the relation lasts until this card moves
+        methods.add_method("CreateRelation", |lua, self_, (other, _reset): (CardId, Option<u32>)| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let mut data_guard = data.lock().unwrap();
+            if let Some(card) = data_guard.cards.get_mut(self_.0 as usize) {
+                if !card.card_relations.contains(&other) {
+                    card.card_relations.push(other);
+                }
+            }
+            Ok(())
+        });
+
+        // Method: c:ReleaseRelation(tc)
+        methods.add_method("ReleaseRelation", |lua, self_, other: CardId| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let mut data_guard = data.lock().unwrap();
+            if let Some(card) = data_guard.cards.get_mut(self_.0 as usize) {
+                card.card_relations.retain(|&id| id != other);
+            }
+            Ok(())
+        });
+
+        // Method: c:IsRelateToCard(tc)
+        methods.add_method("IsRelateToCard", |lua, self_, other: CardId| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.card_relations.contains(&other)))
+        });
+
+        // Method: c:CreateEffectRelation(e) - the relation lasts until this card moves
+        methods.add_method("CreateEffectRelation", |lua, self_, effect: mlua::AnyUserData| {
+            let effect = *effect.borrow::<EffectId>()?;
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            data.lock().unwrap().relate_to_effect(*self_, effect);
+            Ok(())
+        });
+
+        // Method: c:ReleaseEffectRelation(e)
+        methods.add_method("ReleaseEffectRelation", |lua, self_, effect: mlua::AnyUserData| {
+            let effect = *effect.borrow::<EffectId>()?;
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let mut data_guard = data.lock().unwrap();
+            if let Some(card) = data_guard.cards.get_mut(self_.0 as usize) {
+                card.effect_relations.retain(|&e| e != effect);
+            }
+            Ok(())
+        });
+
+        // Method: c:IsRelateToEffect(e) - false once the card moved after being activated, targeted or related
+        methods.add_method("IsRelateToEffect", |lua, self_, effect: mlua::AnyUserData| {
+            let effect = *effect.borrow::<EffectId>()?;
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.effect_relations.contains(&effect)))
+        });
+
+        // Method: c:IsRelateToChain([chain_count]) - IsRelateToEffect with the effect of a chain link, 0 for the current one
+        methods.add_method("IsRelateToChain", |lua, self_, index: Option<usize>| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            let Some(effect) = data_guard.chain_effect(index.unwrap_or(0)) else {
+                return Ok(false);
+            };
+            Ok(data_guard.get_card(*self_).is_some_and(|c| c.effect_relations.contains(&effect)))
+        });
+
+        // Method: c:EnableCounterPermit(counter_type[, location]) - allow the counter in a location,
+        // by default the monster zones for monsters and the spell & trap zones otherwise
+        methods.add_method("EnableCounterPermit", |lua, self_, (counter_type, location): (u16, Option<u32>)| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let mut data_guard = data.lock().unwrap();
+            let is_monster = data_guard.get_card(*self_).is_some_and(|c| c.original_stats.type_.contains(CardType::MONSTER));
+            let range = location.unwrap_or(if is_monster { Location::MZONE.bits() } else { Location::SZONE.bits() });
+            let code = EFFECT_COUNTER_PERMIT + counter_type as u32;
+            let effect = crate::core::effect::Effect::new(0, *self_, 0, code, EFFECT_TYPE_SINGLE, range, EFFECT_FLAG_CANNOT_DISABLE);
+            data_guard.register_effect(effect, Some(*self_));
+            Ok(())
+        });
+
+        // Method: c:SetCounterLimit(counter_type, count) - the card holds at most `count` of the counter
+        methods.add_method("SetCounterLimit", |lua, self_, (counter_type, count): (u16, u32)| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let mut effect = crate::core::effect::Effect::new(0, *self_, 0, EFFECT_COUNTER_LIMIT + counter_type as u32, EFFECT_TYPE_SINGLE, 0, EFFECT_FLAG_CANNOT_DISABLE);
+            effect.value = count;
+            data.lock().unwrap().register_effect(effect, Some(*self_));
+            Ok(())
+        });
+
+        // Method: c:AddCounter(counter_type, count[, singly]) -> bool
+        methods.add_method("AddCounter", |lua, self_, (counter_type, count, singly): (u16, u16, Option<bool>)| {
+            crate::core::counter::add_counter(lua, *self_, counter_type, count, singly.unwrap_or(false))
+        });
+
+        // Method: c:IsCanAddCounter(counter_type, count[, singly, location])
+        methods.add_method("IsCanAddCounter", |lua, self_, (counter_type, count, singly, location): (u16, u16, Option<bool>, Option<u32>)| {
+            let location = location.map(Location::from_bits_truncate);
+            crate::core::counter::is_can_add_counter(lua, *self_, counter_type, count, singly.unwrap_or(false), location)
+        });
+
+        // Method: c:RemoveCounter(player, counter_type, count, reason) -> bool
+        methods.add_method("RemoveCounter", |lua, self_, (_player, counter_type, count): (u8, u16, u16)| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let removed = data.lock().unwrap().remove_counter(*self_, counter_type, count);
+            Ok(removed)
+        });
+
+        // Method: c:IsCanRemoveCounter(player, counter_type, count, reason)
+        methods.add_method("IsCanRemoveCounter", |lua, self_, (_player, counter_type, count): (u8, u16, u16)| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.counter(*self_, counter_type) >= count)
+        });
+
+        // Method: c:GetCounter(counter_type)
+        methods.add_method("GetCounter", |lua, self_, counter_type: u16| {
+            let data = lua.app_data_ref::<Arc<Mutex<crate::core::duel::DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.counter(*self_, counter_type))
+        });
+
+        // Methods that may wait on the host are Lua functions of the global Card table (see prompt.rs)
+        methods.add_meta_function(mlua::MetaMethod::Index, |lua, (_, name): (mlua::Value, mlua::Value)| {
+            lua.globals().get::<_, mlua::Table>("Card")?.raw_get::<_, mlua::Value>(name)
+        });
     }
 }
 
@@ -199,6 +895,18 @@ mod tests {
         assert_eq!(c.current_stats.attack, 0);
     }
 
+    #[test]
+    fn set_card_matches_archetype_and_sub_archetype() {
+        let mut c = Card::new(1);
+        // Two archetypes: 0x1045 (a sub-archetype of 0x45) and 0x3b
+        c.setcode = 0x003b_1045;
+        assert!(c.is_set_card(0x45));
+        assert!(c.is_set_card(0x1045));
+        assert!(c.is_set_card(0x3b));
+        assert!(!c.is_set_card(0x2045));
+        assert!(!c.is_set_card(0x46));
+    }
+
     #[test]
     fn status_helpers_work() {
         let mut c = Card::new(1);
@@ -0,0 +1,189 @@
+//! Coin tosses, dice rolls and rock-paper-scissors. Tosses and rolls draw from the duel RNG in
+//! ocgcore's order so replays come out the same.
+
+use crate::core::duel::{Duel, DuelData};
+use crate::core::enums::*;
+use crate::core::messages::{MsgHandRes, MsgRockPaperScissors, MsgTossCoin, MsgType};
+use crate::core::prompt::{ask, ready, set_prompting, unexpected};
+use crate::core::response::{Prompt, Response};
+use crate::core::scripting::duel_data;
+use mlua::{Lua, MultiValue, Variadic};
+
+/// Most coins or dice a single effect may toss or roll
+const MAX_TOSS: u8 = 5;
+
+impl DuelData {
+    /// Toss `count` coins for `player` and announce them with MSG_TOSS_COIN. 1 is heads.
+    pub fn toss_coin(&mut self, player: u8, count: u8) -> Vec<u8> {
+        let results: Vec<u8> = (0..count).map(|_| self.random.get_next_integer(0, 1) as u8).collect();
+        self.write_message(&MsgTossCoin { player, results: results.clone() }.encode());
+        self.coin_results = results.clone();
+        results
+    }
+
+    /// Roll `count1` dice for `player`, then `count2` for the opponent, each batch with its own MSG_TOSS_DICE.
+    pub fn toss_dice(&mut self, player: u8, count1: u8, count2: u8) -> Vec<u8> {
+        let mut all = Vec::new();
+        for (roller, count) in [(player, count1), (1 - player, count2)] {
+            if count == 0 {
+                continue;
+            }
+            let results: Vec<u8> = (0..count).map(|_| self.random.get_next_integer(1, 6) as u8).collect();
+            self.write_message(&MsgTossCoin { player: roller, results: results.clone() }.encode_as(MsgType::TossDice));
+            all.extend(results);
+        }
+        self.dice_results = all.clone();
+        all
+    }
+}
+
+/// Ask `player` for the hand they throw (1 scissors, 2 rock, 3 paper) with MSG_ROCK_PAPER_SCISSORS.
+fn throw_hand<'lua, F>(lua: &'lua Lua, player: u8, then: F) -> mlua::Result<MultiValue<'lua>>
+where
+    F: for<'a> FnOnce(&'a Lua, u8) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+{
+    ask(lua, Prompt::RockPaperScissors, &MsgRockPaperScissors { player }.encode(), move |lua, answer| match answer {
+        Response::Hand(hand) => then(lua, hand),
+        other => Err(unexpected(other)),
+    })
+}
+
+/// Play rock-paper-scissors and give the winner, or PLAYER_NONE for a draw. With `repeat` ties are played again.
+fn rock_paper_scissors(lua: &Lua, repeat: bool) -> mlua::Result<MultiValue<'_>> {
+    throw_hand(lua, 0, move |lua, first| throw_hand(lua, 1, move |lua, second| {
+        let hands = [first, second];
+        duel_data(lua).lock().unwrap().write_message(&MsgHandRes { hands }.encode());
+        match hands {
+            [a, b] if a == b && repeat => rock_paper_scissors(lua, repeat),
+            [a, b] if a == b => ready(lua, PLAYER_NONE as u8),
+            [1, 2] | [2, 3] | [3, 1] => ready(lua, 1),
+            _ => ready(lua, 0),
+        }
+    }))
+}
+
+/// Register the coin, dice and rock-paper-scissors functions on the global `Duel` table.
+pub fn register_coin_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.TossCoin(player, count) -> one result per coin
+    duel_table.set("TossCoin", lua.create_function(|lua, (player, count): (u8, u8)| {
+        if count == 0 || count > MAX_TOSS {
+            return Err(mlua::Error::RuntimeError("TossCoin: count should be 1-5".to_string()));
+        }
+        let data = duel_data(lua);
+        let (reason_player, effect) = {
+            let mut data_guard = data.lock().unwrap();
+            data_guard.toss_coin(player, count);
+            data_guard.reason_context()
+        };
+        Duel::raise_event_static(lua, data.clone(), EVENT_TOSS_COIN, None, reason_player, effect);
+        // Handlers of the toss may have changed the results with Duel.SetCoinResult
+        let results = data.lock().unwrap().coin_results.clone();
+        Ok(Variadic::from_iter(results))
+    })?)?;
+
+    // Duel.TossDice(player, count1[, count2]) -> the player's results, then the opponent's
+    duel_table.set("TossDice", lua.create_function(|lua, (player, count1, count2): (u8, u8, Option<u8>)| {
+        let count2 = count2.unwrap_or(0);
+        if count1 == 0 || count1 > MAX_TOSS || count2 > MAX_TOSS {
+            return Err(mlua::Error::RuntimeError("TossDice: count1 should be 1-5 and count2 0-5".to_string()));
+        }
+        let data = duel_data(lua);
+        let (reason_player, effect) = {
+            let mut data_guard = data.lock().unwrap();
+            data_guard.toss_dice(player, count1, count2);
+            data_guard.reason_context()
+        };
+        Duel::raise_event_static(lua, data.clone(), EVENT_TOSS_DICE, None, reason_player, effect);
+        let results = data.lock().unwrap().dice_results.clone();
+        Ok(Variadic::from_iter(results))
+    })?)?;
+
+    // Duel.GetCoinResult() -> the last toss
+    duel_table.set("GetCoinResult", lua.create_function(|lua, ()| {
+        Ok(Variadic::from_iter(duel_data(lua).lock().unwrap().coin_results.clone()))
+    })?)?;
+
+    // Duel.GetDiceResult() -> the last roll
+    duel_table.set("GetDiceResult", lua.create_function(|lua, ()| {
+        Ok(Variadic::from_iter(duel_data(lua).lock().unwrap().dice_results.clone()))
+    })?)?;
+
+    // Duel.SetCoinResult(...) - anything but heads (1) reads as tails
+    duel_table.set("SetCoinResult", lua.create_function(|lua, results: Variadic<i64>| {
+        let results = results.iter().take(MAX_TOSS as usize).map(|&r| if r == 1 { 1 } else { 0 }).collect();
+        duel_data(lua).lock().unwrap().coin_results = results;
+        Ok(())
+    })?)?;
+
+    // Duel.SetDiceResult(...) - faces outside 1-6 read as 1
+    duel_table.set("SetDiceResult", lua.create_function(|lua, results: Variadic<i64>| {
+        let results = results.iter().take(MAX_TOSS as usize).map(|&r| if (1..=6).contains(&r) { r as u8 } else { 1 }).collect();
+        duel_data(lua).lock().unwrap().dice_results = results;
+        Ok(())
+    })?)?;
+
+    // Duel.RockPaperScissors([repeat]) -> winner, PLAYER_NONE on a draw; ties are replayed by default
+    set_prompting(lua, &duel_table, "RockPaperScissors", |lua, repeat: Option<bool>| {
+        rock_paper_scissors(lua, repeat.unwrap_or(true))
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::messages::{MsgHandRes, MsgTossCoin, MsgType};
+    use crate::core::mtrandom::Mt19937;
+    use crate::core::processor::ProcessResult;
+
+    #[test]
+    fn coins_and_dice_follow_the_duel_rng() {
+        let duel = Duel::new(7);
+        let mut rng = Mt19937::new(7);
+        let coins: Vec<u8> = (0..3).map(|_| rng.get_next_integer(0, 1) as u8).collect();
+        let dice: Vec<u8> = (0..3).map(|_| rng.get_next_integer(1, 6) as u8).collect();
+        duel.data.lock().unwrap().message_buffer.clear();
+        let (tossed, rolled, changed): (Vec<u8>, Vec<u8>, Vec<u8>) = duel.lua.load(r#"
+            local coins = { Duel.TossCoin(0, 3) }
+            local dice = { Duel.TossDice(1, 2, 1) }
+            Duel.SetCoinResult(1, 7)
+            return coins, dice, { Duel.GetCoinResult() }
+        "#).eval().unwrap();
+        assert_eq!(tossed, coins);
+        assert_eq!(rolled, dice);
+        assert_eq!(changed, vec![1, 0]);
+        let data = duel.data.lock().unwrap();
+        let mut expected = MsgTossCoin { player: 0, results: coins }.encode();
+        expected.extend(MsgTossCoin { player: 1, results: dice[..2].to_vec() }.encode_as(MsgType::TossDice));
+        expected.extend(MsgTossCoin { player: 0, results: dice[2..].to_vec() }.encode_as(MsgType::TossDice));
+        assert_eq!(data.message_buffer, expected);
+        assert!(duel.lua.load("return Duel.TossCoin(0, 6)").exec().is_err());
+    }
+
+    #[test]
+    fn rock_paper_scissors_asks_both_players() {
+        let mut duel = Duel::new(0);
+        duel.execute_operation(duel.lua.load(r#"
+            winner = Duel.RockPaperScissors()
+            draw = Duel.RockPaperScissors(false)
+        "#).into_function().unwrap()).unwrap();
+        // A tie is played again: rock and rock, then paper against rock
+        for hand in [2i32, 2, 3, 2] {
+            assert_eq!(duel.process(), ProcessResult::Waiting);
+            duel.set_responseb(&hand.to_le_bytes());
+        }
+        // Without repeating, a tie is a draw
+        for _ in 0..2 {
+            assert_eq!(duel.process(), ProcessResult::Waiting);
+            duel.set_responseb(&1i32.to_le_bytes());
+        }
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let (winner, draw): (u8, u8) = duel.lua.load("return winner, draw").eval().unwrap();
+        assert_eq!((winner, draw), (0, 2));
+        let data = duel.data.lock().unwrap();
+        assert!(data.message_buffer.ends_with(&MsgHandRes { hands: [1, 1] }.encode()));
+    }
+}
@@ -0,0 +1,263 @@
+//! Control of monsters: taking and swapping control, control that returns in the End Phase,
+//! and sending cards that leave the field back to their owner's side.
+
+use crate::core::duel::{Duel, DuelData};
+use crate::core::enums::*;
+use crate::core::group::{cards_of, Group};
+use crate::core::messages::MsgSwap;
+use crate::core::prompt::{ready, set_prompting, wrap};
+use crate::core::replace::send_to;
+use crate::core::scripting::duel_data;
+use crate::core::types::CardId;
+use crate::core::zone::place_each;
+use mlua::{Lua, MultiValue};
+
+impl DuelData {
+    /// Player whose `location` a card sent there by `player` ends up in: the GY, banishment and the
+    /// Extra Deck are always the owner's, as are the hand and Deck when no player is given.
+    pub fn destination_player(&self, card_id: CardId, player: u8, location: Location) -> u8 {
+        let owner = self.cards.get(card_id.0 as usize).map(|c| c.owner).unwrap_or(player);
+        if player > 1 || location.intersects(Location::GRAVE | Location::REMOVED | Location::EXTRA) {
+            owner
+        } else {
+            player
+        }
+    }
+
+    /// Whether a monster's control may change at all (EFFECT_CANNOT_CHANGE_CONTROL).
+    pub fn is_able_to_change_controller(&self, card_id: CardId) -> bool {
+        self.cards.get(card_id.0 as usize).is_some_and(|c| c.location == Location::MZONE)
+            && self.card_effects(card_id, EFFECT_CANNOT_CHANGE_CONTROL).is_empty()
+    }
+
+    /// Whether `player` could take control of a monster they do not control yet.
+    pub fn can_take_control(&self, card_id: CardId, player: u8) -> bool {
+        player <= 1
+            && self.cards.get(card_id.0 as usize).is_some_and(|c| c.controller != player)
+            && self.is_able_to_change_controller(card_id)
+    }
+
+    /// Move a monster into main monster zone `seq` of `player`, making them its controller. No legality checks.
+    pub fn move_control(&mut self, card_id: CardId, player: u8, seq: u8) -> bool {
+        if !self.is_location_useable(player, Location::MZONE, seq) {
+            return false;
+        }
+        let card = &self.cards[card_id.0 as usize];
+        let (old, old_seq) = (card.controller, card.sequence);
+        let from = self.info_location(card_id);
+        self.field.remove_card(old, Location::MZONE, old_seq);
+        self.field.add_card(player, Location::MZONE, card_id, seq);
+        let card = &mut self.cards[card_id.0 as usize];
+        card.controller = player;
+        card.sequence = seq;
+        self.write_move_message(card_id, from, REASON_EFFECT);
+        true
+    }
+
+    /// `player` takes control of a monster, putting it into their monster zone `seq`. With `reset_count`,
+    /// control goes back to the current controller in that many End Phases. Returns false if control did not change.
+    pub fn get_control(&mut self, card_id: CardId, player: u8, reset_count: Option<u32>, seq: u8) -> bool {
+        let Some(old) = self.cards.get(card_id.0 as usize).map(|c| c.controller) else {
+            return false;
+        };
+        if !self.can_take_control(card_id, player) || !self.move_control(card_id, player, seq) {
+            return false;
+        }
+        self.schedule_control_return(card_id, old, reset_count);
+        true
+    }
+
+    /// The two monsters trade places and controllers, announced with MSG_SWAP.
+    pub fn swap_control(&mut self, first: CardId, second: CardId, reset_count: Option<u32>) -> bool {
+        let (Some(a), Some(b)) = (self.cards.get(first.0 as usize), self.cards.get(second.0 as usize)) else {
+            return false;
+        };
+        let ((pa, sa), (pb, sb)) = ((a.controller, a.sequence), (b.controller, b.sequence));
+        if pa == pb || !self.is_able_to_change_controller(first) || !self.is_able_to_change_controller(second) {
+            return false;
+        }
+        self.field.mzone[pa as usize][sa as usize] = Some(second);
+        self.field.mzone[pb as usize][sb as usize] = Some(first);
+        for (card_id, player, seq) in [(first, pb, sb), (second, pa, sa)] {
+            let card = &mut self.cards[card_id.0 as usize];
+            card.controller = player;
+            card.sequence = seq;
+        }
+        self.schedule_control_return(first, pa, reset_count);
+        self.schedule_control_return(second, pb, reset_count);
+        let msg = MsgSwap {
+            code1: self.cards[first.0 as usize].code,
+            location1: self.info_location(first),
+            code2: self.cards[second.0 as usize].code,
+            location2: self.info_location(second),
+        };
+        self.write_message(&msg.encode());
+        true
+    }
+
+    /// Remember (or forget, for permanent control) who a monster goes back to.
+    /// A monster already under temporary control keeps going back to its earlier controller.
+    fn schedule_control_return(&mut self, card_id: CardId, previous: u8, reset_count: Option<u32>) {
+        let pending = self.control_returns.iter().position(|&(id, _, _)| id == card_id);
+        let back_to = pending.map(|i| self.control_returns.remove(i).1).unwrap_or(previous);
+        if let Some(count) = reset_count {
+            self.control_returns.push((card_id, back_to, count.max(1)));
+        }
+    }
+
+    /// End Phase: the monsters whose temporary control ran out and that are still under it.
+    pub fn due_control_returns(&mut self) -> Vec<CardId> {
+        let mut due = Vec::new();
+        self.control_returns.retain_mut(|(card_id, player, count)| {
+            *count -= 1;
+            if *count == 0 {
+                due.push((*card_id, *player));
+            }
+            *count > 0
+        });
+        due.into_iter()
+            .filter(|&(card_id, player)| self.cards.get(card_id.0 as usize).is_some_and(|c| c.location == Location::MZONE && c.controller != player))
+            .map(|(card_id, _)| card_id)
+            .collect()
+    }
+}
+
+/// Registry name of the function giving back the monsters whose temporary control ran out.
+pub(crate) const CONTROL_RETURN: &str = "osiris.control.return";
+
+/// End Phase of `turn_player`: each monster due goes back into a zone its controller-to-be picks.
+/// The ones that cannot find a free zone there are sent to the GY instead.
+fn return_control(lua: &Lua, turn_player: u8) -> mlua::Result<MultiValue<'_>> {
+    let due = duel_data(lua).lock().unwrap().due_control_returns();
+    // With two players, a monster due goes back to the one not controlling it
+    let zones = |lua: &Lua, card_id: CardId| {
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        let player = 1 - data_guard.cards[card_id.0 as usize].controller;
+        Ok(Some((player, data_guard.useable_zones(player, Location::MZONE, 0x1f))))
+    };
+    let place = |lua: &Lua, card_id: CardId, seq: Option<u8>| {
+        let data = duel_data(lua);
+        let mut data_guard = data.lock().unwrap();
+        let player = 1 - data_guard.cards[card_id.0 as usize].controller;
+        Ok(seq.is_some_and(|seq| data_guard.move_control(card_id, player, seq)))
+    };
+    place_each(lua, Location::MZONE, due.clone(), Vec::new(), zones, place, move |lua, returned| {
+        let homeless: Vec<CardId> = due.into_iter().filter(|id| !returned.contains(id)).collect();
+        send_to(lua, &homeless, None, Location::GRAVE, None, REASON_RULE, turn_player, None)?;
+        ready(lua, ())
+    })
+}
+
+/// Register the control functions on the global `Duel` table.
+pub fn register_control_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+    lua.set_named_registry_value(CONTROL_RETURN, wrap(lua, lua.create_function(return_control)?)?)?;
+
+    // Duel.GetControl(targets, player[, reset_phase, reset_count]) -> number of monsters taken,
+    // given back in the End Phase when reset_phase is PHASE_END
+    set_prompting(lua, &duel_table, "GetControl", |lua, (targets, player, reset_phase, reset_count): (mlua::Value, u8, Option<u32>, Option<u32>)| {
+        let reset = reset_phase.filter(|&phase| phase & Phase::END.bits() != 0).map(|_| reset_count.unwrap_or(1));
+        let zones = move |lua: &Lua, card_id: CardId| {
+            let data = duel_data(lua);
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.can_take_control(card_id, player).then(|| (player, data_guard.useable_zones(player, Location::MZONE, 0x1f))))
+        };
+        let place = move |lua: &Lua, card_id: CardId, seq: Option<u8>| {
+            Ok(seq.is_some_and(|seq| duel_data(lua).lock().unwrap().get_control(card_id, player, reset, seq)))
+        };
+        place_each(lua, Location::MZONE, cards_of(&targets), Vec::new(), zones, place, move |lua, taken| {
+            let count = taken.len() as u32;
+            if count > 0 {
+                Duel::raise_event_static(lua, duel_data(lua), EVENT_CONTROL_CHANGED, Some(Group(taken.into_iter().collect())), player, None);
+            }
+            ready(lua, count)
+        })
+    })?;
+
+    // Duel.SwapControl(c1, c2[, reset_phase, reset_count]) -> bool
+    duel_table.set("SwapControl", lua.create_function(|lua, (first, second, reset_phase, reset_count): (CardId, CardId, Option<u32>, Option<u32>)| {
+        let reset = reset_phase.filter(|&phase| phase & Phase::END.bits() != 0).map(|_| reset_count.unwrap_or(1));
+        let data = duel_data(lua);
+        let (swapped, player) = {
+            let mut data_guard = data.lock().unwrap();
+            (data_guard.swap_control(first, second, reset), data_guard.turn_player)
+        };
+        if swapped {
+            Duel::raise_event_static(lua, data, EVENT_CONTROL_CHANGED, Some(Group([first, second].into_iter().collect())), player, None);
+        }
+        Ok(swapped)
+    })?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::messages::MsgSwap;
+    use crate::core::processor::{ProcessResult, ProcessorUnit};
+    use crate::core::types::CardId;
+
+    #[test]
+    fn borrowed_monsters_return_in_the_end_phase_and_leave_to_their_owner() {
+        let mut duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9971, 1, 1, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)
+            Debug.AddCard(9972, 1, 1, LOCATION_MZONE, 3, POS_FACEUP_ATTACK)
+            Debug.AddCard(9973, 1, 1, LOCATION_MZONE, 4, POS_FACEUP_ATTACK)
+            local e = Effect.CreateEffect(Card(2))
+            e:SetType(EFFECT_TYPE_SINGLE)
+            e:SetCode(EFFECT_CANNOT_CHANGE_CONTROL)
+            Card(2):RegisterEffect(e)
+        "#).exec().expect("setup");
+        duel.execute_operation(duel.lua.load(r#"
+            borrowed, kept, locked = Duel.GetControl(Card(0), 0, PHASE_END, 1), Duel.GetControl(Card(1), 0), Duel.GetControl(Card(2), 0)
+        "#).into_function().unwrap()).unwrap();
+        // The taker picks a zone for each monster they get
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 0]);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 1]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let (borrowed, kept, locked): (u32, u32, u32) = duel.lua.load("return borrowed, kept, locked").eval().unwrap();
+        assert_eq!((borrowed, kept, locked), (1, 1, 0));
+        {
+            let mut data = duel.data.lock().unwrap();
+            assert_eq!(data.get_card(CardId::new(0)).map(|c| (c.controller, c.owner, c.sequence)), Some((0, 1, 0)));
+            assert_eq!(data.field.mzone[1][2], None);
+            // The permanently taken monster is sent to its owner's GY
+            data.send_card_to(CardId::new(1), 0, Location::GRAVE, REASON_DESTROY);
+            assert_eq!(data.field.grave[1], vec![CardId::new(1)]);
+            assert_eq!(data.get_card(CardId::new(1)).unwrap().controller, 1);
+            data.processor_units.clear();
+            data.processor_units.push_back(ProcessorUnit::phase_event(0, Phase::END.bits()));
+        }
+        // The owner picks the zone it comes back to
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[1, Location::MZONE.bits() as u8, 2]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.phase.bits(), Phase::END.bits());
+        assert_eq!(data.get_card(CardId::new(0)).map(|c| (c.controller, c.location, c.sequence)), Some((1, Location::MZONE, 2)));
+        assert!(data.control_returns.is_empty());
+    }
+
+    #[test]
+    fn swap_control_trades_zones() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9981, 0, 0, LOCATION_MZONE, 1, POS_FACEUP_ATTACK)
+            Debug.AddCard(9982, 1, 1, LOCATION_MZONE, 3, POS_FACEUP_DEFENSE)
+        "#).exec().expect("setup");
+        let swapped: bool = duel.lua.load("return Duel.SwapControl(Card(0), Card(1))").eval().unwrap();
+        assert!(swapped);
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.get_card(CardId::new(0)).map(|c| (c.controller, c.sequence)), Some((1, 3)));
+        assert_eq!(data.get_card(CardId::new(1)).map(|c| (c.controller, c.sequence)), Some((0, 1)));
+        assert_eq!((data.field.mzone[0][1], data.field.mzone[1][3]), (Some(CardId::new(1)), Some(CardId::new(0))));
+        let msg = MsgSwap { code1: 9981, location1: data.info_location(CardId::new(0)), code2: 9982, location2: data.info_location(CardId::new(1)) };
+        assert!(data.message_buffer.ends_with(&msg.encode()));
+    }
+}
@@ -0,0 +1,295 @@
+//! Counters on cards: per-type storage, counter permits and limits, and removing counters from several cards (MSG_SELECT_COUNTER).
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::messages::{CounterCandidate, MsgAddCounter, MsgSelectCounter, MsgType};
+use crate::core::prompt::{ask, ready, set_prompting, unexpected};
+use crate::core::response::{Prompt, Response};
+use crate::core::scripting::{duel_data, effect_value};
+use crate::core::types::{CardId, EffectId};
+use mlua::{Lua, MultiValue};
+
+impl DuelData {
+    /// Counters of a type on a card.
+    pub fn counter(&self, card_id: CardId, counter_type: u16) -> u16 {
+        self.cards.get(card_id.0 as usize).and_then(|c| c.counters.get(&counter_type).copied()).unwrap_or(0)
+    }
+
+    /// Whether a counter permit of the card covers `location` (its current one if `None`).
+    /// Counter types with COUNTER_WITHOUT_PERMIT need none.
+    pub fn counter_permitted(&self, card_id: CardId, counter_type: u16, location: Option<Location>) -> bool {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return false;
+        };
+        if counter_type & COUNTER_WITHOUT_PERMIT != 0 {
+            return true;
+        }
+        let location = location.unwrap_or(card.location);
+        self.card_effects(card_id, EFFECT_COUNTER_PERMIT + counter_type as u32).iter()
+            .any(|eid| self.effects[eid.0 as usize].range & location.bits() != 0)
+    }
+
+    /// Put counters on a card and announce them with MSG_ADD_COUNTER.
+    pub fn add_counter(&mut self, card_id: CardId, counter_type: u16, count: u16) {
+        let Some(card) = self.cards.get_mut(card_id.0 as usize) else {
+            return;
+        };
+        let entry = card.counters.entry(counter_type).or_insert(0);
+        *entry = entry.saturating_add(count);
+        let place = self.info_location(card_id);
+        let msg = MsgAddCounter { counter_type, controller: place.controller, location: place.location, sequence: place.sequence, count };
+        self.write_message(&msg.encode());
+    }
+
+    /// Take counters off a card, announced with MSG_REMOVE_COUNTER. Fails if it has fewer than `count`.
+    pub fn remove_counter(&mut self, card_id: CardId, counter_type: u16, count: u16) -> bool {
+        let Some(card) = self.cards.get_mut(card_id.0 as usize) else {
+            return false;
+        };
+        let Some(current) = card.counters.get_mut(&counter_type).filter(|c| **c >= count) else {
+            return false;
+        };
+        *current -= count;
+        if *current == 0 {
+            card.counters.remove(&counter_type);
+        }
+        let place = self.info_location(card_id);
+        let msg = MsgAddCounter { counter_type, controller: place.controller, location: place.location, sequence: place.sequence, count };
+        self.write_message(&msg.encode_as(MsgType::RemoveCounter));
+        true
+    }
+
+    /// Cards on the field of `player` (`s`) and their opponent (`o`) holding counters of a type.
+    pub fn counter_holders(&self, player: u8, s: bool, o: bool, counter_type: u16) -> Vec<CardId> {
+        let mut sides = Vec::new();
+        if s {
+            sides.push(player as usize);
+        }
+        if o {
+            sides.push(1 - player as usize);
+        }
+        sides.into_iter()
+            .flat_map(|p| self.field.mzone[p].iter().chain(self.field.szone[p].iter()).flatten().copied())
+            .filter(|&id| self.counter(id, counter_type) > 0)
+            .collect()
+    }
+}
+
+/// Whether counters can be put on a card: the player may place counters, the card is face-up on the field
+/// (or `location` is given), it is not disabled for a COUNTER_NEED_ENABLE type, a counter permit covers it
+/// and no counter limit would be exceeded.
+/// With `singly` only the first counter has to fit.
+pub fn is_can_add_counter(lua: &Lua, card_id: CardId, counter_type: u16, count: u16, singly: bool, location: Option<Location>) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let limits = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        if !data_guard.player_effects(card.controller, EFFECT_CANNOT_PLACE_COUNTER).is_empty() {
+            return Ok(false);
+        }
+        if location.is_none() && (!card.location.intersects(Location::ONFIELD) || !card.position.intersects(CardPosition::FACEUP)) {
+            return Ok(false);
+        }
+        if counter_type & COUNTER_NEED_ENABLE != 0 && card.has_status(CardStatus::DISABLED) {
+            return Ok(false);
+        }
+        if !data_guard.counter_permitted(card_id, counter_type, location) {
+            return Ok(false);
+        }
+        data_guard.card_effects(card_id, EFFECT_COUNTER_LIMIT + counter_type as u32)
+    };
+    let Some(limit) = counter_limit(lua, &limits)? else {
+        return Ok(true);
+    };
+    let current = data.lock().unwrap().counter(card_id, counter_type) as u32;
+    let needed = if singly { 1 } else { count as u32 };
+    Ok(current + needed <= limit)
+}
+
+/// Lowest EFFECT_COUNTER_LIMIT value among the effects, if any.
+fn counter_limit(lua: &Lua, limits: &[EffectId]) -> mlua::Result<Option<u32>> {
+    let data = duel_data(lua);
+    let mut lowest = None;
+    for &eid in limits {
+        let value = effect_value(lua, &data, eid, ())?;
+        lowest = Some(lowest.map_or(value, |l: u32| l.min(value)));
+    }
+    Ok(lowest)
+}
+
+/// Card:AddCounter: put counters on a card if it can take them. With `singly` they are added
+/// one at a time, so as many as the counter limit leaves room for are placed.
+pub fn add_counter(lua: &Lua, card_id: CardId, counter_type: u16, count: u16, singly: bool) -> mlua::Result<bool> {
+    if count == 0 || !is_can_add_counter(lua, card_id, counter_type, count, singly, None)? {
+        return Ok(false);
+    }
+    let data = duel_data(lua);
+    let limits = data.lock().unwrap().card_effects(card_id, EFFECT_COUNTER_LIMIT + counter_type as u32);
+    let room = counter_limit(lua, &limits)?;
+    let mut data_guard = data.lock().unwrap();
+    let count = match room {
+        Some(limit) => count.min((limit as u16).saturating_sub(data_guard.counter(card_id, counter_type))),
+        None => count,
+    };
+    data_guard.add_counter(card_id, counter_type, count);
+    Ok(true)
+}
+
+/// Duel.RemoveCounter: remove `count` counters from the cards on the field, letting `player` choose
+/// how many come off each card (MSG_SELECT_COUNTER); a single card is not asked for. Fails without enough counters.
+pub fn remove_field_counters(lua: &Lua, player: u8, s: bool, o: bool, counter_type: u16, count: u16) -> mlua::Result<MultiValue<'_>> {
+    let (holders, msg, available) = {
+        let data = duel_data(lua);
+        let mut data_guard = data.lock().unwrap();
+        let holders = data_guard.counter_holders(player, s, o, counter_type);
+        let available: Vec<u16> = holders.iter().map(|&id| data_guard.counter(id, counter_type)).collect();
+        if count == 0 || available.iter().map(|&held| held as u32).sum::<u32>() < count as u32 {
+            return ready(lua, false);
+        }
+        if let [card_id] = holders[..] {
+            data_guard.remove_counter(card_id, counter_type, count);
+            return ready(lua, true);
+        }
+        let cards = holders.iter().zip(available.iter()).map(|(&id, &held)| {
+            let place = data_guard.info_location(id);
+            let code = data_guard.cards[id.0 as usize].code;
+            CounterCandidate { code, controller: place.controller, location: place.location, sequence: place.sequence, count: held }
+        }).collect();
+        (holders, MsgSelectCounter { player, counter_type, count, cards }, available)
+    };
+    ask(lua, Prompt::SelectCounter { count, available }, &msg.encode(), move |lua, answer| {
+        let Response::Counters(counts) = answer else {
+            return Err(unexpected(answer));
+        };
+        let data = duel_data(lua);
+        let mut data_guard = data.lock().unwrap();
+        for (card_id, taken) in holders.into_iter().zip(counts) {
+            if taken > 0 {
+                data_guard.remove_counter(card_id, counter_type, taken);
+            }
+        }
+        ready(lua, true)
+    })
+}
+
+/// Register the counter functions on the global `Duel` table.
+pub fn register_counter_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.GetCounter(player, s, o, counter_type) -> counters on the cards of both fields
+    duel_table.set("GetCounter", lua.create_function(|lua, (player, s, o, counter_type): (u8, u32, u32, u16)| {
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        let holders = data_guard.counter_holders(player, s != 0, o != 0, counter_type);
+        Ok(holders.iter().map(|&id| data_guard.counter(id, counter_type) as u32).sum::<u32>())
+    })?)?;
+
+    // Duel.IsCanRemoveCounter(player, s, o, counter_type, count, reason)
+    duel_table.set("IsCanRemoveCounter", lua.create_function(|lua, (player, s, o, counter_type, count): (u8, u32, u32, u16, u16)| {
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        let holders = data_guard.counter_holders(player, s != 0, o != 0, counter_type);
+        Ok(holders.iter().map(|&id| data_guard.counter(id, counter_type) as u32).sum::<u32>() >= count as u32)
+    })?)?;
+
+    // Duel.RemoveCounter(player, s, o, counter_type, count, reason) -> bool
+    set_prompting(lua, &duel_table, "RemoveCounter", |lua, (player, s, o, counter_type, count): (u8, u32, u32, u16, u16)| {
+        remove_field_counters(lua, player, s != 0, o != 0, counter_type, count)
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::CardStatus;
+    use crate::core::messages::{MsgAddCounter, MsgRetry, MsgSelectCounter, CounterCandidate, MsgType};
+    use crate::core::processor::ProcessResult;
+    use crate::core::types::CardId;
+
+    #[test]
+    fn counters_need_a_permit_and_respect_the_limit() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            local c = Debug.AddCard(9901, 0, 0, LOCATION_SZONE, 1, POS_FACEUP)
+            Debug.AddCard(9902, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            c:EnableCounterPermit(0x1)
+            c:SetCounterLimit(0x1, 3)
+        "#).exec().expect("setup");
+        duel.get_message();
+        let (added, no_permit, over, singly, count): (bool, bool, bool, bool, u16) = duel.lua.load(r#"
+            local added = Card(0):AddCounter(0x1, 2)
+            local no_permit = Card(1):AddCounter(0x1, 1)
+            local over = Card(0):AddCounter(0x1, 2)
+            local singly = Card(0):AddCounter(0x1, 2, true)
+            return added, no_permit, over, singly, Card(0):GetCounter(0x1)
+        "#).eval().unwrap();
+        assert!(added);
+        assert!(!no_permit);
+        assert!(!over, "2 + 2 is over the limit of 3");
+        assert!(singly, "one at a time fills up to the limit");
+        assert_eq!(count, 3);
+        let data = duel.data.lock().unwrap();
+        let first = MsgAddCounter { counter_type: 0x1, controller: 0, location: 0x08, sequence: 1, count: 2 };
+        let capped = MsgAddCounter { count: 1, ..first.clone() };
+        assert_eq!(data.message_buffer, [first.encode(), capped.encode()].concat());
+    }
+
+    #[test]
+    fn counter_flags_skip_the_permit_or_need_the_card_enabled() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9905, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            local c = Debug.AddCard(9906, 0, 0, LOCATION_SZONE, 0, POS_FACEUP)
+            c:EnableCounterPermit(0x2001)
+        "#).exec().expect("setup");
+        duel.data.lock().unwrap().cards[1].set_status(CardStatus::DISABLED);
+        let (a_counter, disabled, count): (bool, bool, u16) = duel.lua.load(r#"
+            return Card(0):AddCounter(0x100e, 1), Card(1):AddCounter(0x2001, 1), Card(0):GetCounter(0x100e)
+        "#).eval().unwrap();
+        assert!(a_counter, "an A-Counter needs no permit");
+        assert!(!disabled, "a disabled card cannot get a counter that needs it enabled");
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn removing_counters_from_the_field_asks_how_many_per_card() {
+        let mut duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9911, 0, 0, LOCATION_SZONE, 0, POS_FACEUP)
+            Debug.AddCard(9912, 0, 0, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)
+            Debug.PreAddCounter(Card(0), 0x1, 2)
+            Debug.PreAddCounter(Card(1), 0x1, 3)
+        "#).exec().expect("setup");
+        let (total, too_many): (u32, bool) = duel.lua.load("return Duel.GetCounter(0, 1, 0, 0x1), Duel.IsCanRemoveCounter(0, 1, 1, 0x1, 6, 0)").eval().unwrap();
+        assert_eq!(total, 5);
+        assert!(!too_many);
+        duel.get_message();
+        duel.execute_operation(duel.lua.load("removed = Duel.RemoveCounter(0, 1, 0, 0x1, 3, 0)").into_function().unwrap()).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        let monster = CounterCandidate { code: 9912, controller: 0, location: 0x04, sequence: 2, count: 3 };
+        let spell = CounterCandidate { code: 9911, controller: 0, location: 0x08, sequence: 0, count: 2 };
+        assert_eq!(duel.get_message(), MsgSelectCounter { player: 0, counter_type: 0x1, count: 3, cards: vec![monster, spell] }.encode());
+        // The spell only holds two
+        duel.set_responseb(&[0, 0, 3, 0]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "a split the cards cannot give is asked again");
+        assert_eq!(duel.get_message(), MsgRetry.encode());
+        // The monster comes first: take one counter from it and two from the spell
+        duel.set_responseb(&[1, 0, 2, 0]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let removed: bool = duel.lua.globals().get("removed").unwrap();
+        assert!(removed);
+        let data = duel.data.lock().unwrap();
+        assert_eq!((data.counter(CardId::new(0), 0x1), data.counter(CardId::new(1), 0x1)), (0, 2));
+        assert!(data.get_card(CardId::new(0)).unwrap().counters.is_empty());
+        let mut expected = Vec::new();
+        for (c, count) in [(monster, 1), (spell, 2)] {
+            let msg = MsgAddCounter { counter_type: 0x1, controller: c.controller, location: c.location, sequence: c.sequence, count };
+            expected.extend(msg.encode_as(MsgType::RemoveCounter));
+        }
+        assert_eq!(data.message_buffer, expected);
+    }
+}
@@ -17,6 +17,7 @@ pub struct CardData {
     pub defense: i32,
     pub lscale: u32,
     pub rscale: u32,
+    pub link_marker: u32,
 }
 
 pub struct Database {
@@ -50,15 +51,21 @@ impl Database {
             let race: u32 = r.get(6)?;
             let atk: i32 = r.get(7)?;
             let def: i32 = r.get(8)?;
+            // Link monsters store their arrows in the def column
+            let is_link = type_ & 0x4000000 != 0;
             Ok(CardData {
                 code: id,
                 alias,
                 setcode: setcode as u64,
-                type_, level, attribute, race,
+                type_,
+                // Pendulum scales are packed into the upper bytes of the level column
+                level: level & 0xff,
+                attribute, race,
                 attack: atk,
-                defense: def,
-                lscale: 0,
-                rscale: 0,
+                defense: if is_link { 0 } else { def },
+                lscale: (level >> 24) & 0xff,
+                rscale: (level >> 16) & 0xff,
+                link_marker: if is_link { def as u32 } else { 0 },
             })
         }).optional()?;
         if let Some(card) = &row_opt {
@@ -90,4 +97,22 @@ mod tests {
         assert_eq!(c.attack, 1500);
         assert_eq!(c.defense, 1200);
     }
+
+    #[test]
+    fn level_column_unpacks_scales_and_link_markers() {
+        let mut db = Database::open_in_memory().expect("open in memory");
+        db.conn.execute(
+            "CREATE TABLE datas (id INTEGER, alias INTEGER, setcode INTEGER, type INTEGER, level INTEGER, attribute INTEGER, race INTEGER, atk INTEGER, def INTEGER);",
+            params![]
+        ).unwrap();
+        // Level 4 pendulum with scales 2/2, and a link-2 monster with arrows bottom-left/bottom-right
+        db.conn.execute(
+            "INSERT INTO datas VALUES (1, 0, 0, 16777249, 33685508, 1, 1, 1800, 1000), (2, 0, 0, 67108897, 2, 1, 1, 1600, 5)",
+            params![]
+        ).unwrap();
+        let pend = db.query_card(1).unwrap().unwrap();
+        assert_eq!((pend.level, pend.lscale, pend.rscale), (4, 2, 2));
+        let link = db.query_card(2).unwrap().unwrap();
+        assert_eq!((link.level, link.defense, link.link_marker), (2, 0, 5));
+    }
 }
@@ -0,0 +1,249 @@
+//! Debug.* Lua API used by single-mode (puzzle) scripts to build the field directly instead of loading decks.
+
+use crate::core::duel::{Duel, DuelData};
+use crate::core::enums::{CardPosition, CardStatus, CardType, Location};
+use crate::core::messages::{MsgAiName, MsgShowHint};
+use crate::core::options::DuelOptions;
+use crate::core::types::CardId;
+use mlua::{AnyUserData, Lua};
+use std::sync::{Arc, Mutex};
+
+/// Register the global `Debug` table.
+pub fn register_debug_table(lua: &Lua) -> mlua::Result<()> {
+    let debug_table = lua.create_table()?;
+
+    // Debug.SetPlayerInfo(player, lp, start_count, draw_count)
+    debug_table.set("SetPlayerInfo", lua.create_function(|lua, (player, lp, start_count, draw_count): (u8, u32, u32, u32)| {
+        if player > 1 {
+            return Ok(());
+        }
+        let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+            .expect("DuelData not found in Lua app data");
+        let mut data_guard = data.lock().unwrap();
+        let p = player as usize;
+        data_guard.lp[p] = lp;
+        data_guard.start_count[p] = start_count;
+        data_guard.draw_count[p] = draw_count;
+        Ok(())
+    })?)?;
+
+    // Debug.AddCard(code, owner, player, location, sequence, position[, proc]) -> Card or nil
+    debug_table.set("AddCard", lua.create_function(|lua, (code, owner, player, location, sequence, position, proc_): (u32, u8, u8, u32, u8, u32, Option<bool>)| {
+        if owner > 1 || player > 1 {
+            return Ok(None);
+        }
+        let location = Location::from_bits_truncate(location);
+        let card_id = {
+            let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let mut data_guard = data.lock().unwrap();
+            if !data_guard.is_location_useable(player, location, sequence) {
+                return Ok(None);
+            }
+            let card_id = data_guard.new_card(code, owner);
+            let is_pendulum = data_guard.cards[card_id.0 as usize].original_stats.type_.contains(CardType::PENDULUM);
+            let mut position = CardPosition::from_bits_truncate(position);
+            if location == Location::EXTRA && (position.is_empty() || !is_pendulum) {
+                position = CardPosition::FACEDOWN_DEFENSE;
+            }
+            data_guard.place_card(card_id, player, location, sequence, position);
+            if proc_.unwrap_or(false) {
+                data_guard.cards[card_id.0 as usize].set_status(CardStatus::PROC_COMPLETE);
+            }
+            card_id
+        };
+        // The card script must run without the DuelData lock held
+        Duel::load_card_script_static(lua, card_id, code)?;
+        Ok(Some(card_id))
+    })?)?;
+
+    // Debug.PreSummon(c, summon_type[, summon_location])
+    debug_table.set("PreSummon", lua.create_function(|lua, (card, summon_type, summon_location): (AnyUserData, u32, Option<u32>)| {
+        let card_id = *card.borrow::<CardId>()?;
+        let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+            .expect("DuelData not found in Lua app data");
+        let mut data_guard = data.lock().unwrap();
+        if let Some(c) = data_guard.cards.get_mut(card_id.0 as usize) {
+            c.summon_info = summon_type | ((summon_location.unwrap_or(0) & 0xff) << 16);
+        }
+        Ok(())
+    })?)?;
+
+    // Debug.PreEquip(equip_card, target) -> bool
+    debug_table.set("PreEquip", lua.create_function(|lua, (equip, target): (AnyUserData, AnyUserData)| {
+        let equip_id = *equip.borrow::<CardId>()?;
+        let target_id = *target.borrow::<CardId>()?;
+        let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+            .expect("DuelData not found in Lua app data");
+        let mut data_guard = data.lock().unwrap();
+        let (Some(equip_card), Some(target_card)) = (data_guard.get_card(equip_id), data_guard.get_card(target_id)) else {
+            return Ok(false);
+        };
+        if equip_card.location != Location::SZONE
+            || target_card.location != Location::MZONE
+            || target_card.position.intersects(CardPosition::FACEDOWN) {
+            return Ok(false);
+        }
+        data_guard.cards[equip_id.0 as usize].equip_target = Some(target_id);
+        data_guard.cards[target_id.0 as usize].equip_cards.push(equip_id);
+        Ok(true)
+    })?)?;
+
+    // Debug.PreSetTarget(c, target)
+    debug_table.set("PreSetTarget", lua.create_function(|lua, (card, target): (AnyUserData, AnyUserData)| {
+        let card_id = *card.borrow::<CardId>()?;
+        let target_id = *target.borrow::<CardId>()?;
+        let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+            .expect("DuelData not found in Lua app data");
+        let mut data_guard = data.lock().unwrap();
+        if data_guard.get_card(card_id).is_none() || data_guard.get_card(target_id).is_none() {
+            return Ok(());
+        }
+        if !data_guard.cards[card_id.0 as usize].effect_target_cards.contains(&target_id) {
+            data_guard.cards[card_id.0 as usize].effect_target_cards.push(target_id);
+            data_guard.cards[target_id.0 as usize].effect_target_owners.push(card_id);
+        }
+        Ok(())
+    })?)?;
+
+    // Debug.PreAddCounter(c, counter_type, count)
+    debug_table.set("PreAddCounter", lua.create_function(|lua, (card, counter_type, count): (AnyUserData, u16, u16)| {
+        let card_id = *card.borrow::<CardId>()?;
+        let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+            .expect("DuelData not found in Lua app data");
+        let mut data_guard = data.lock().unwrap();
+        if let Some(c) = data_guard.cards.get_mut(card_id.0 as usize) {
+            let entry = c.counters.entry(counter_type).or_insert(0);
+            *entry = entry.saturating_add(count);
+        }
+        Ok(())
+    })?)?;
+
+    // Debug.ReloadFieldBegin(flag[, rule]) clears the duel and applies the duel options
+    debug_table.set("ReloadFieldBegin", lua.create_function(|lua, (flag, rule): (u32, Option<u8>)| {
+        let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+            .expect("DuelData not found in Lua app data");
+        let mut data_guard = data.lock().unwrap();
+        data_guard.clear();
+        let options = DuelOptions::from_duel_flag(flag & 0xffff | (rule.unwrap_or(0) as u32) << 16);
+        data_guard.duel_options |= options.flags;
+        data_guard.duel_rule = options.duel_rule;
+        Ok(())
+    })?)?;
+
+    // Debug.ReloadFieldEnd() publishes the rebuilt field to the clients
+    debug_table.set("ReloadFieldEnd", lua.create_function(|lua, ()| {
+        let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+            .expect("DuelData not found in Lua app data");
+        let mut data_guard = data.lock().unwrap();
+        let msg = data_guard.reload_field_message().encode();
+        data_guard.write_message(&msg);
+        Ok(())
+    })?)?;
+
+    // Debug.SetAIName(name)
+    debug_table.set("SetAIName", lua.create_function(|lua, name: String| {
+        let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+            .expect("DuelData not found in Lua app data");
+        data.lock().unwrap().write_message(&MsgAiName { name }.encode());
+        Ok(())
+    })?)?;
+
+    // Debug.ShowHint(message)
+    debug_table.set("ShowHint", lua.create_function(|lua, message: String| {
+        let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+            .expect("DuelData not found in Lua app data");
+        data.lock().unwrap().write_message(&MsgShowHint { message }.encode());
+        Ok(())
+    })?)?;
+
+    lua.globals().set("Debug", debug_table)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::{CardPosition, CardStatus, Location};
+    use crate::core::types::CardId;
+
+    #[test]
+    fn add_card_places_into_zones() {
+        let duel = Duel::new(0);
+        let result: mlua::Result<(Option<u32>, Option<u32>, Option<u32>)> = duel.lua.load(r#"
+            local a = Debug.AddCard(1001, 0, 0, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)
+            local b = Debug.AddCard(1002, 0, 0, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)
+            local c = Debug.AddCard(1003, 1, 1, LOCATION_EXTRA, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(1004, 0, 0, LOCATION_MZONE, 3, POS_FACEUP_ATTACK, true)
+            return a and 1 or nil, b and 1 or nil, c and 1 or nil
+        "#).eval();
+        let (a, b, c) = result.expect("Debug.AddCard should run");
+        assert!(a.is_some(), "first card should be placed");
+        assert!(b.is_none(), "occupied zone should be rejected");
+        assert!(c.is_some(), "extra deck card should be placed");
+
+        let data = duel.data.lock().unwrap();
+        let placed = data.field.mzone[0][2].expect("mzone 2 occupied");
+        let card = data.get_card(placed).unwrap();
+        assert_eq!(card.code, 1001);
+        assert_eq!(card.location, Location::MZONE);
+        assert_eq!(card.sequence, 2);
+        assert!(card.position.contains(CardPosition::FACEUP_ATTACK));
+        assert!(!card.has_status(CardStatus::PROC_COMPLETE), "only `proc` marks the summon procedure complete");
+        let completed = data.get_card(data.field.mzone[0][3].expect("mzone 3 occupied")).unwrap();
+        assert!(completed.has_status(CardStatus::PROC_COMPLETE));
+        let extra = data.get_card(data.field.extra[1][0]).unwrap();
+        assert_eq!(extra.position.bits(), CardPosition::FACEDOWN_DEFENSE.bits(), "non-pendulum extra cards are face-down");
+    }
+
+    #[test]
+    fn pre_functions_set_relations() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.ReloadFieldBegin(DUEL_ATTACK_FIRST_TURN + DUEL_SIMPLE_AI, 4)
+            Debug.SetPlayerInfo(0, 100, 0, 0)
+            Debug.SetPlayerInfo(1, 8000, 5, 1)
+            local m = Debug.AddCard(2001, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            local e = Debug.AddCard(2002, 0, 0, LOCATION_SZONE, 1, POS_FACEUP)
+            Debug.PreSummon(m, SUMMON_TYPE_SPECIAL, LOCATION_GRAVE)
+            assert(Debug.PreEquip(e, m))
+            Debug.PreSetTarget(e, m)
+            Debug.PreAddCounter(m, 0x1, 3)
+            Debug.ReloadFieldEnd()
+        "#).exec().expect("puzzle setup should run");
+
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.duel_rule, 4);
+        assert_eq!(data.duel_options.bits(), 0x02 | 0x40);
+        assert_eq!(data.lp, [100, 8000]);
+        assert_eq!(data.start_count, [0, 5]);
+        let monster = data.get_card(CardId::new(0)).unwrap();
+        let equip = data.get_card(CardId::new(1)).unwrap();
+        assert_eq!(monster.summon_type(), 0x40000000);
+        assert_eq!(monster.summon_location(), Location::GRAVE.bits());
+        assert_eq!(equip.equip_target, Some(CardId::new(0)));
+        assert_eq!(monster.equip_cards, vec![CardId::new(1)]);
+        assert_eq!(equip.effect_target_cards, vec![CardId::new(0)]);
+        assert_eq!(monster.counters.get(&0x1), Some(&3));
+    }
+
+    #[test]
+    fn start_single_boots_from_script() {
+        let mut duel = Duel::new(0);
+        duel.create_card(5000, 0);
+        duel.start_single(r#"
+            Debug.SetAIName("Puzzle")
+            Debug.ReloadFieldBegin(DUEL_ATTACK_FIRST_TURN, 3)
+            Debug.SetPlayerInfo(0, 4000, 0, 0)
+            Debug.AddCard(3001, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(3002, 0, 0, LOCATION_PZONE, 1, POS_FACEUP)
+            Debug.ReloadFieldEnd()
+        "#).expect("single script should boot");
+
+        let data = duel.data.lock().unwrap();
+        assert!(data.field.deck[0].is_empty(), "decks are not loaded in single mode");
+        assert_eq!(data.field.hand[0].len(), 1);
+        assert!(data.field.szone[0][7].is_some(), "right pendulum zone is szone 7 under master rule 3");
+        assert_eq!(data.lp[0], 4000);
+    }
+}
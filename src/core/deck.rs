@@ -0,0 +1,270 @@
+//! Deck manipulation: sorting and revealing the top of the Deck, shuffles owed after cards were
+//! taken from the middle of it, moving cards between zones, swapping the Deck and GY and reversing Decks.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::group::Group;
+use crate::core::messages::{ConfirmedCard, MsgConfirmCards, MsgDeckTop, MsgReverseDeck, MsgSwapGraveDeck, MsgType};
+use crate::core::prompt::{ask, ready, set_prompting, unexpected};
+use crate::core::response::{Prompt, Response};
+use crate::core::scripting::duel_data;
+use crate::core::types::CardId;
+use mlua::Lua;
+
+impl DuelData {
+    /// Number the cards of `player`'s Deck from the bottom, as ocgcore's reset_sequence does.
+    pub fn reset_deck_sequence(&mut self, player: u8) {
+        for (sequence, card_id) in self.field.deck[player as usize].clone().into_iter().enumerate() {
+            self.cards[card_id.0 as usize].sequence = sequence as u8;
+        }
+    }
+
+    /// Owe `player` a Deck shuffle, unless Duel.DisableShuffleCheck is in force.
+    pub fn check_deck_shuffle(&mut self, player: u8) {
+        if !self.shuffle_check_disabled {
+            self.shuffle_deck_check[player as usize] = true;
+        }
+    }
+
+    /// Shuffle the Decks owed a shuffle and lift Duel.DisableShuffleCheck, once a chain link has resolved.
+    pub fn shuffle_checked_decks(&mut self) {
+        for player in 0..2 {
+            if std::mem::take(&mut self.shuffle_deck_check[player as usize]) && self.field.deck[player as usize].len() > 1 {
+                self.shuffle_deck(player);
+            }
+        }
+        self.shuffle_check_disabled = false;
+    }
+
+    /// Announce the top card of `player`'s Deck with MSG_DECK_TOP if it is face-up.
+    pub fn write_deck_top(&mut self, player: u8) {
+        let Some(&top) = self.field.deck[player as usize].last() else { return };
+        let card = &self.cards[top.0 as usize];
+        if card.position.intersects(CardPosition::FACEUP) {
+            let msg = MsgDeckTop { player, sequence: 0, code: card.code | 0x80000000 };
+            self.write_message(&msg.encode());
+        }
+    }
+
+    /// The top `count` cards of `player`'s Deck, top first.
+    pub fn deck_top(&self, player: u8, count: usize) -> Vec<CardId> {
+        self.field.deck[player as usize].iter().rev().take(count).copied().collect()
+    }
+
+    /// Show the top `count` cards of `target_player`'s Deck to `sort_player` for sorting and return them
+    /// with the MSG_SORT_CARD message, or None when the Deck is empty.
+    pub fn sort_decktop_message(&mut self, sort_player: u8, target_player: u8, count: usize) -> Option<(Vec<CardId>, Vec<u8>)> {
+        let top = self.deck_top(target_player, count);
+        if top.is_empty() {
+            return None;
+        }
+        let deck_size = self.field.deck[target_player as usize].len();
+        let mut cards = Vec::new();
+        for (i, &card_id) in top.iter().enumerate() {
+            let card = &mut self.cards[card_id.0 as usize];
+            card.confirmed_to |= 1 << sort_player;
+            cards.push(ConfirmedCard { code: card.code, controller: target_player, location: Location::DECK.bits() as u8, sequence: (deck_size - 1 - i) as u8 });
+        }
+        let msg = MsgConfirmCards { player: sort_player, cards };
+        Some((top, msg.encode_as(MsgType::SortCard)))
+    }
+
+    /// Put the sorted `top` cards back on `target_player`'s Deck. `order[i]` is the new place of the i-th
+    /// card, 0 being the top; without an order they go back as they were.
+    pub fn sort_decktop(&mut self, target_player: u8, top: &[CardId], order: Option<&[u8]>) {
+        if let Some(order) = order {
+            let mut sorted = top.to_vec();
+            for (&card_id, &place) in top.iter().zip(order.iter()) {
+                sorted[place as usize] = card_id;
+            }
+            let deck = &mut self.field.deck[target_player as usize];
+            deck.truncate(deck.len() - top.len());
+            deck.extend(sorted.into_iter().rev());
+            self.reset_deck_sequence(target_player);
+        }
+        self.write_deck_top(target_player);
+    }
+
+    /// Move a card to another free zone of the same kind on its controller's side (Duel.MoveSequence).
+    pub fn move_sequence(&mut self, card_id: CardId, sequence: u8) -> bool {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return false;
+        };
+        let (player, location, old, reason) = (card.controller, card.location, card.sequence, card.reason);
+        if !(location == Location::MZONE || location == Location::SZONE) || !self.is_location_useable(player, location, sequence) {
+            return false;
+        }
+        let from = self.info_location(card_id);
+        self.field.remove_card(player, location, old);
+        self.field.add_card(player, location, card_id, sequence);
+        self.cards[card_id.0 as usize].sequence = sequence;
+        self.write_move_message(card_id, from, reason);
+        true
+    }
+
+    /// `player`'s Deck becomes their GY face-up and their GY becomes their Deck, with Extra Deck
+    /// monsters going back to the Extra Deck instead. The new Deck is shuffled.
+    pub fn swap_deck_and_grave(&mut self, player: u8) {
+        let p = player as usize;
+        let deck = std::mem::take(&mut self.field.deck[p]);
+        let grave = std::mem::take(&mut self.field.grave[p]);
+        let extra_types = (CardType::FUSION | CardType::SYNCHRO | CardType::XYZ | CardType::LINK).bits();
+        for card_id in grave {
+            let to_extra = self.cards[card_id.0 as usize].original_stats.type_.bits() & extra_types != 0;
+            let (location, stack) = if to_extra { (Location::EXTRA, &mut self.field.extra[p]) } else { (Location::DECK, &mut self.field.deck[p]) };
+            stack.push(card_id);
+            let sequence = (stack.len() - 1) as u8;
+            let card = &mut self.cards[card_id.0 as usize];
+            card.location = location;
+            card.sequence = sequence;
+            card.position = CardPosition::FACEDOWN_DEFENSE;
+            self.drop_relations(card_id);
+        }
+        for card_id in deck {
+            self.field.grave[p].push(card_id);
+            let card = &mut self.cards[card_id.0 as usize];
+            card.location = Location::GRAVE;
+            card.sequence = (self.field.grave[p].len() - 1) as u8;
+            card.position = CardPosition::FACEUP_ATTACK;
+            self.drop_relations(card_id);
+        }
+        self.write_message(&MsgSwapGraveDeck { player }.encode());
+        self.shuffle_deck(player);
+    }
+
+    /// Turn both Decks upside down: their order is reversed and every card flips face-up or face-down.
+    pub fn reverse_deck(&mut self) {
+        for player in 0..2 {
+            self.field.deck[player as usize].reverse();
+            for card_id in self.field.deck[player as usize].clone() {
+                let card = &mut self.cards[card_id.0 as usize];
+                card.position = if card.position.intersects(CardPosition::FACEUP) { CardPosition::FACEDOWN_DEFENSE } else { CardPosition::FACEUP_DEFENSE };
+            }
+            self.reset_deck_sequence(player);
+        }
+        self.write_message(&MsgReverseDeck.encode());
+        for player in 0..2 {
+            self.write_deck_top(player);
+        }
+    }
+}
+
+/// Register the Deck functions on the global `Duel` table.
+pub fn register_deck_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.GetDecktopGroup(player, count) -> the top cards of player's Deck
+    duel_table.set("GetDecktopGroup", lua.create_function(|lua, (player, count): (u8, usize)| {
+        Ok(Group(duel_data(lua).lock().unwrap().deck_top(player, count).into_iter().collect()))
+    })?)?;
+
+    // Duel.SortDecktop(sort_player, target_player, count)
+    set_prompting(lua, &duel_table, "SortDecktop", |lua, (sort_player, target_player, count): (u8, u8, usize)| {
+        let Some((top, msg)) = duel_data(lua).lock().unwrap().sort_decktop_message(sort_player, target_player, count) else {
+            return ready(lua, ());
+        };
+        ask(lua, Prompt::SortCard { count: top.len() }, &msg, move |lua, answer| {
+            let Response::Order(order) = answer else {
+                return Err(unexpected(answer));
+            };
+            duel_data(lua).lock().unwrap().sort_decktop(target_player, &top, order.as_deref());
+            ready(lua, ())
+        })
+    })?;
+
+    // Duel.MoveSequence(c, seq) - into another zone of the same kind
+    duel_table.set("MoveSequence", lua.create_function(|lua, (card, sequence): (CardId, u8)| {
+        duel_data(lua).lock().unwrap().move_sequence(card, sequence);
+        Ok(())
+    })?)?;
+
+    // Duel.DisableShuffleCheck([disabled]) - lasts until the current chain link has resolved
+    duel_table.set("DisableShuffleCheck", lua.create_function(|lua, disabled: Option<bool>| {
+        duel_data(lua).lock().unwrap().shuffle_check_disabled = disabled.unwrap_or(true);
+        Ok(())
+    })?)?;
+
+    // Duel.SwapDeckAndGrave(player)
+    duel_table.set("SwapDeckAndGrave", lua.create_function(|lua, player: u8| {
+        duel_data(lua).lock().unwrap().swap_deck_and_grave(player);
+        Ok(())
+    })?)?;
+
+    // Duel.ReverseDeck() - both players' Decks
+    duel_table.set("ReverseDeck", lua.create_function(|lua, ()| {
+        duel_data(lua).lock().unwrap().reverse_deck();
+        Ok(())
+    })?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::messages::{MsgDeckTop, MsgReverseDeck};
+    use crate::core::processor::ProcessResult;
+    use crate::core::types::CardId;
+
+    fn deck_codes(duel: &Duel, player: usize) -> Vec<u32> {
+        let data = duel.data.lock().unwrap();
+        data.field.deck[player].iter().map(|c| data.cards[c.0 as usize].code).collect()
+    }
+
+    #[test]
+    fn sorted_and_reversed_decks_keep_their_order() {
+        let mut duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9001, 0, 0, LOCATION_DECK, 0, POS_FACEDOWN)
+            Debug.AddCard(9002, 0, 0, LOCATION_DECK, 0, POS_FACEDOWN)
+            Debug.AddCard(9003, 0, 0, LOCATION_DECK, 0, POS_FACEDOWN)
+            Debug.AddCard(9000, 0, 0, LOCATION_DECK, 1, POS_FACEDOWN)
+        "#).exec().expect("setup");
+        assert_eq!(deck_codes(&duel, 0), vec![9000, 9001, 9002, 9003], "9003 went on top last, 9000 to the bottom");
+        // The top card (9003) goes third, 9002 to the top and 9001 second
+        duel.execute_operation(duel.lua.load("Duel.SortDecktop(1, 0, 3)").into_function().unwrap()).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        assert_eq!(deck_codes(&duel, 0), vec![9000, 9001, 9002, 9003], "the Deck is untouched until the order comes");
+        duel.set_responseb(&[2, 0, 1]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        assert_eq!(deck_codes(&duel, 0), vec![9000, 9003, 9001, 9002]);
+        let top: CardId = duel.lua.load("return Duel.GetDecktopGroup(0, 1):GetFirst()").eval().unwrap();
+        assert_eq!(duel.data.lock().unwrap().cards[top.0 as usize].code, 9002);
+        duel.get_message();
+        duel.lua.load("Duel.ReverseDeck()").exec().unwrap();
+        assert_eq!(deck_codes(&duel, 0), vec![9002, 9001, 9003, 9000]);
+        let data = duel.data.lock().unwrap();
+        let bottom = data.field.deck[0][0];
+        assert_eq!(data.cards[bottom.0 as usize].sequence, 0);
+        let expected = [MsgReverseDeck.encode(), MsgDeckTop { player: 0, sequence: 0, code: 9000 | 0x80000000 }.encode()].concat();
+        assert_eq!(data.message_buffer, expected);
+    }
+
+    #[test]
+    fn deck_and_grave_swap_and_searched_decks_get_shuffled() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9101, 0, 0, LOCATION_DECK, 0, POS_FACEDOWN)
+            Debug.AddCard(9102, 0, 0, LOCATION_DECK, 0, POS_FACEDOWN)
+            Debug.AddCard(9103, 0, 0, LOCATION_GRAVE, 0, POS_FACEUP)
+            Debug.AddCard(9104, 1, 1, LOCATION_MZONE, 0, POS_FACEDOWN_DEFENSE)
+        "#).exec().expect("setup");
+        duel.lua.load("Duel.MoveSequence(Card(3), 4) Duel.SwapDeckAndGrave(0)").exec().unwrap();
+        let mut data = duel.data.lock().unwrap();
+        assert_eq!(data.field.mzone[1][4], Some(CardId::new(3)));
+        assert_eq!(data.field.deck[0], vec![CardId::new(2)]);
+        assert_eq!(data.field.grave[0], vec![CardId::new(0), CardId::new(1)]);
+        assert!(data.get_card(CardId::new(0)).unwrap().position.intersects(CardPosition::FACEUP));
+        // A card taken from under the top owes a shuffle, unless the check is disabled
+        data.send_card_to(CardId::new(0), 0, Location::DECKBOT, REASON_EFFECT);
+        data.send_card_to(CardId::new(0), 0, Location::HAND, REASON_EFFECT);
+        assert_eq!(data.shuffle_deck_check, [true, false]);
+        data.shuffle_checked_decks();
+        data.shuffle_check_disabled = true;
+        data.send_card_to(CardId::new(1), 0, Location::DECKBOT, REASON_EFFECT);
+        data.send_card_to(CardId::new(1), 0, Location::GRAVE, REASON_EFFECT);
+        assert_eq!(data.shuffle_deck_check, [false, false]);
+        assert_eq!(data.get_card(CardId::new(1)).unwrap().location, Location::GRAVE);
+    }
+}
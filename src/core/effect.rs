@@ -1,5 +1,5 @@
 use crate::core::types::{CardId, EffectId};
-use mlua::{UserData, UserDataMethods, RegistryKey, Function};
+use mlua::{Lua, UserData, UserDataMethods, RegistryKey, Function};
 use std::sync::{Arc, Mutex};
 use crate::core::duel::DuelData;
 
@@ -17,18 +17,58 @@ pub struct Effect {
     pub cost: Option<RegistryKey>,
     pub target: Option<RegistryKey>,
     pub operation: Option<RegistryKey>,
+    /// Constant value set by SetValue
+    pub value: u32,
+    /// Value function set by SetValue, takes precedence over `value`
+    pub value_fn: Option<RegistryKey>,
+    /// (self, opponent) target range set by SetTargetRange
+    pub target_range: (u32, u32),
+    /// Player the effect was registered to with Duel.RegisterEffect
+    pub player: Option<u8>,
+    /// Integer label set by SetLabel
+    pub label: u32,
+    /// Lua object set by SetLabelObject
+    pub label_object: Option<RegistryKey>,
 }
 
 impl Effect {
     pub fn new(id: u32, owner: CardId, description: u32, code: u32, type_: u32, range: u32, flag: u32) -> Self {
-        Effect { id, owner, description, code, type_, range, flag, condition: None, cost: None, target: None, operation: None }
+        Effect { id, owner, description, code, type_, range, flag, condition: None, cost: None, target: None, operation: None, value: 0, value_fn: None, target_range: (0, 0), player: None, label: 0, label_object: None }
+    }
+
+    /// Copy the effect for registration, giving the copy its own registry entries for the Lua values.
+    pub fn duplicate(&self, lua: &Lua, owner: CardId) -> Effect {
+        let copy_key = |key: &Option<RegistryKey>| {
+            key.as_ref()
+                .and_then(|k| lua.registry_value::<mlua::Value>(k).ok())
+                .and_then(|value| lua.create_registry_value(value).ok())
+        };
+        Effect {
+            id: 0,
+            owner,
+            description: self.description,
+            code: self.code,
+            type_: self.type_,
+            range: self.range,
+            flag: self.flag,
+            condition: copy_key(&self.condition),
+            cost: copy_key(&self.cost),
+            target: copy_key(&self.target),
+            operation: copy_key(&self.operation),
+            value: self.value,
+            value_fn: copy_key(&self.value_fn),
+            target_range: self.target_range,
+            player: self.player,
+            label: self.label,
+            label_object: copy_key(&self.label_object),
+        }
     }
 
     /// Create a new effect (static constructor for Lua)
-    pub fn create_effect(_card: Option<CardId>) -> Self {
+    pub fn create_effect(card: Option<CardId>) -> Self {
         Effect {
             id: 0,
-            owner: CardId::new(0),
+            owner: card.unwrap_or(CardId::new(0)),
             description: 0,
             code: 0,
             type_: 0,
@@ -38,6 +78,12 @@ impl Effect {
             cost: None,
             target: None,
             operation: None,
+            value: 0,
+            value_fn: None,
+            target_range: (0, 0),
+            player: None,
+            label: 0,
+            label_object: None,
         }
     }
 }
@@ -65,8 +111,13 @@ impl UserData for Effect {
             Ok(())
         });
         
-        methods.add_method_mut("SetProperty", |_, _self, _property: u32| {
-            // Stub - store property somewhere if needed
+        methods.add_method_mut("SetProperty", |_, self_, property: u32| {
+            self_.flag = property;
+            Ok(())
+        });
+
+        methods.add_method_mut("SetTargetRange", |_, self_, (s, o): (u32, u32)| {
+            self_.target_range = (s, o);
             Ok(())
         });
         
@@ -98,8 +149,14 @@ impl UserData for Effect {
             }
         });
         
-        methods.add_method_mut("SetValue", |_, _self, _value: mlua::Value| {
-            // Stub - store value if needed
+        methods.add_method_mut("SetValue", |lua, self_, value: mlua::Value| {
+            match value {
+                mlua::Value::Function(f) => self_.value_fn = Some(lua.create_registry_value(f)?),
+                mlua::Value::Integer(i) => self_.value = i as u32,
+                mlua::Value::Number(n) => self_.value = n as u32,
+                mlua::Value::Boolean(b) => self_.value = b as u32,
+                _ => {}
+            }
             Ok(())
         });
     }
@@ -146,6 +203,45 @@ impl UserData for EffectId {
                 Err(mlua::Error::RuntimeError("Effect not found".to_string()))
             }
         });
+
+        methods.add_method("SetLabel", |lua, self_, label: u32| {
+            let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            if let Some(effect) = data.lock().unwrap().effects.get_mut(self_.0 as usize) {
+                effect.label = label;
+            }
+            Ok(())
+        });
+
+        methods.add_method("GetLabel", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            Ok(data_guard.effects.get(self_.0 as usize).map(|e| e.label).unwrap_or(0))
+        });
+
+        methods.add_method("SetLabelObject", |lua, self_, object: mlua::Value| {
+            let key = match object {
+                mlua::Value::Nil => None,
+                object => Some(lua.create_registry_value(object)?),
+            };
+            let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            if let Some(effect) = data.lock().unwrap().effects.get_mut(self_.0 as usize) {
+                effect.label_object = key;
+            }
+            Ok(())
+        });
+
+        methods.add_method("GetLabelObject", |lua, self_, ()| {
+            let data = lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+                .expect("DuelData not found in Lua app data");
+            let data_guard = data.lock().unwrap();
+            match data_guard.effects.get(self_.0 as usize).and_then(|e| e.label_object.as_ref()) {
+                Some(key) => lua.registry_value::<mlua::Value>(key),
+                None => Ok(mlua::Value::Nil),
+            }
+        });
     }
 }
 
@@ -95,6 +95,21 @@ bitflags! {
     }
 }
 
+// Duel option flags (DUEL_* in C++)
+bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct DuelFlag: u32 {
+        const TEST_MODE = 0x01; // DUEL_TEST_MODE
+        const ATTACK_FIRST_TURN = 0x02; // DUEL_ATTACK_FIRST_TURN
+        const OLD_REPLAY = 0x04; // DUEL_OLD_REPLAY
+        const OBSOLETE_RULING = 0x08; // DUEL_OBSOLETE_RULING
+        const PSEUDO_SHUFFLE = 0x10; // DUEL_PSEUDO_SHUFFLE
+        const TAG_MODE = 0x20; // DUEL_TAG_MODE
+        const SIMPLE_AI = 0x40; // DUEL_SIMPLE_AI
+        const RETURN_DECK_TOP = 0x80; // DUEL_RETURN_DECK_TOP
+    }
+}
+
 // Location flags (LOCATION_* in C++)
 bitflags! {
     #[derive(Clone, Copy, Debug, PartialEq)]
@@ -151,6 +166,37 @@ bitflags! {
         const LEAVE_CONFIRMED = 0x2000; // STATUS_LEAVE_CONFIRMED
         const BATTLE_DESTROYED = 0x4000; // STATUS_BATTLE_DESTROYED
         const ATTACK_CANCELED = 0x200000; // STATUS_ATTACK_CANCELED
+        const FORBIDDEN = 0x4000000; // STATUS_FORBIDDEN
+    }
+}
+
+// Query flags (QUERY_* in C++), selecting the fields returned by query_card
+bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct QueryFlag: u32 {
+        const CODE = 0x1; // QUERY_CODE
+        const POSITION = 0x2; // QUERY_POSITION
+        const ALIAS = 0x4; // QUERY_ALIAS
+        const TYPE = 0x8; // QUERY_TYPE
+        const LEVEL = 0x10; // QUERY_LEVEL
+        const RANK = 0x20; // QUERY_RANK
+        const ATTRIBUTE = 0x40; // QUERY_ATTRIBUTE
+        const RACE = 0x80; // QUERY_RACE
+        const ATTACK = 0x100; // QUERY_ATTACK
+        const DEFENSE = 0x200; // QUERY_DEFENSE
+        const BASE_ATTACK = 0x400; // QUERY_BASE_ATTACK
+        const BASE_DEFENSE = 0x800; // QUERY_BASE_DEFENSE
+        const REASON = 0x1000; // QUERY_REASON
+        const REASON_CARD = 0x2000; // QUERY_REASON_CARD
+        const EQUIP_CARD = 0x4000; // QUERY_EQUIP_CARD
+        const TARGET_CARD = 0x8000; // QUERY_TARGET_CARD
+        const OVERLAY_CARD = 0x10000; // QUERY_OVERLAY_CARD
+        const COUNTERS = 0x20000; // QUERY_COUNTERS
+        const OWNER = 0x40000; // QUERY_OWNER
+        const STATUS = 0x80000; // QUERY_STATUS
+        const LSCALE = 0x200000; // QUERY_LSCALE
+        const RSCALE = 0x400000; // QUERY_RSCALE
+        const LINK = 0x800000; // QUERY_LINK
     }
 }
 
@@ -174,6 +220,136 @@ pub const EVENT_FLIP_SUMMON_SUCCESS: u32 = 0x1001;
 pub const EVENT_SPSUMMON_SUCCESS: u32 = 0x1002;
 pub const EVENT_DRAW: u32 = 0x1003;
 pub const EVENT_MOVE: u32 = 0x1004;
+pub const EVENT_REMOVE: u32 = 1011;
+pub const EVENT_TO_HAND: u32 = 1012;
+pub const EVENT_TO_DECK: u32 = 1013;
+pub const EVENT_TO_GRAVE: u32 = 1014;
+pub const EVENT_RELEASE: u32 = 1017;
+pub const EVENT_DISCARD: u32 = 1018;
+pub const EVENT_DESTROYED: u32 = 1029;
+pub const EVENT_CONTROL_CHANGED: u32 = 1120;
+pub const EVENT_EQUIP: u32 = 1121;
+pub const EVENT_TOSS_DICE: u32 = 1150;
+pub const EVENT_TOSS_COIN: u32 = 1151;
+
+// Effect type constants (EFFECT_TYPE_* in C++)
+pub const EFFECT_TYPE_SINGLE: u32 = 0x1;
+pub const EFFECT_TYPE_FIELD: u32 = 0x2;
+pub const EFFECT_TYPE_EQUIP: u32 = 0x4;
+pub const EFFECT_TYPE_ACTIVATE: u32 = 0x10;
+
+// Effect property flags (EFFECT_FLAG_* in C++)
+pub const EFFECT_FLAG_CANNOT_DISABLE: u32 = 0x400;
+pub const EFFECT_FLAG_PLAYER_TARGET: u32 = 0x800;
+pub const EFFECT_FLAG_UNCOPYABLE: u32 = 0x40000;
+pub const EFFECT_FLAG_SPSUM_PARAM: u32 = 0x100000;
+
+// Effect codes (EFFECT_* in C++)
+pub const EFFECT_CANNOT_CHANGE_CONTROL: u32 = 5;
+pub const EFFECT_CANNOT_SPECIAL_SUMMON: u32 = 22;
+pub const EFFECT_SPSUMMON_CONDITION: u32 = 30;
+pub const EFFECT_REVIVE_LIMIT: u32 = 31;
+pub const EFFECT_SPSUMMON_PROC: u32 = 34;
+pub const EFFECT_INDESTRUCTABLE: u32 = 40;
+pub const EFFECT_INDESTRUCTABLE_EFFECT: u32 = 41;
+pub const EFFECT_INDESTRUCTABLE_BATTLE: u32 = 42;
+pub const EFFECT_UNRELEASABLE_NONSUM: u32 = 44;
+pub const EFFECT_DESTROY_SUBSTITUTE: u32 = 45;
+pub const EFFECT_CANNOT_RELEASE: u32 = 46;
+pub const EFFECT_DESTROY_REPLACE: u32 = 50;
+pub const EFFECT_SEND_REPLACE: u32 = 52;
+pub const EFFECT_CANNOT_PLACE_COUNTER: u32 = 58;
+pub const EFFECT_LEAVE_FIELD_REDIRECT: u32 = 60;
+pub const EFFECT_TO_HAND_REDIRECT: u32 = 61;
+pub const EFFECT_TO_DECK_REDIRECT: u32 = 62;
+pub const EFFECT_TO_GRAVE_REDIRECT: u32 = 63;
+pub const EFFECT_REMOVE_REDIRECT: u32 = 64;
+pub const EFFECT_PUBLIC: u32 = 160;
+pub const EFFECT_CANNOT_BP: u32 = 185;
+pub const EFFECT_FUSION_MATERIAL: u32 = 230;
+pub const EFFECT_FUSION_SUBSTITUTE: u32 = 234;
+pub const EFFECT_CANNOT_BE_FUSION_MATERIAL: u32 = 235;
+pub const EFFECT_CANNOT_BE_SYNCHRO_MATERIAL: u32 = 236;
+pub const EFFECT_CANNOT_BE_XYZ_MATERIAL: u32 = 238;
+pub const EFFECT_CANNOT_BE_LINK_MATERIAL: u32 = 239;
+pub const EFFECT_SYNCHRO_LEVEL: u32 = 240;
+pub const EFFECT_RITUAL_LEVEL: u32 = 241;
+pub const EFFECT_XYZ_LEVEL: u32 = 242;
+pub const EFFECT_EXTRA_RITUAL_MATERIAL: u32 = 243;
+pub const EFFECT_NONTUNER: u32 = 244;
+pub const EFFECT_DISABLE_FIELD: u32 = 260;
+pub const EFFECT_HAND_LIMIT: u32 = 270;
+pub const EFFECT_ADD_FUSION_CODE: u32 = 340;
+pub const EFFECT_ADD_FUSION_SETCODE: u32 = 341;
+/// Counter permits and limits carry the counter type in their low bits
+pub const EFFECT_COUNTER_PERMIT: u32 = 0x10000;
+pub const EFFECT_COUNTER_LIMIT: u32 = 0x20000;
+/// Counter type flags: placed without a counter permit, or only on a card that is not disabled
+pub const COUNTER_WITHOUT_PERMIT: u16 = 0x1000;
+pub const COUNTER_NEED_ENABLE: u16 = 0x2000;
+
+// Summon types (SUMMON_TYPE_* in C++)
+pub const SUMMON_TYPE_SPECIAL: u32 = 0x40000000;
+pub const SUMMON_TYPE_FUSION: u32 = 0x43000000;
+pub const SUMMON_TYPE_RITUAL: u32 = 0x45000000;
+pub const SUMMON_TYPE_SYNCHRO: u32 = 0x46000000;
+pub const SUMMON_TYPE_XYZ: u32 = 0x49000000;
+pub const SUMMON_TYPE_PENDULUM: u32 = 0x4a000000;
+pub const SUMMON_TYPE_LINK: u32 = 0x4c000000;
+
+// Announce filter opcodes (OPCODE_* in C++), evaluated as a stack program against card data
+pub const OPCODE_ADD: u32 = 0x40000000;
+pub const OPCODE_SUB: u32 = 0x40000001;
+pub const OPCODE_MUL: u32 = 0x40000002;
+pub const OPCODE_DIV: u32 = 0x40000003;
+pub const OPCODE_AND: u32 = 0x40000004;
+pub const OPCODE_OR: u32 = 0x40000005;
+pub const OPCODE_NEG: u32 = 0x40000006;
+pub const OPCODE_NOT: u32 = 0x40000007;
+pub const OPCODE_ISCODE: u32 = 0x40000100;
+pub const OPCODE_ISSETCARD: u32 = 0x40000101;
+pub const OPCODE_ISTYPE: u32 = 0x40000102;
+pub const OPCODE_ISRACE: u32 = 0x40000103;
+pub const OPCODE_ISATTRIBUTE: u32 = 0x40000104;
+
+// Deck sequences (SEQ_DECK* in C++): where a card sent to the Deck is placed
+pub const SEQ_DECKTOP: u8 = 0;
+pub const SEQ_DECKBOTTOM: u8 = 1;
+pub const SEQ_DECKSHUFFLE: u8 = 2;
+
+// Player constants (PLAYER_* in C++)
+pub const PLAYER_NONE: u32 = 2;
+
+// Move reasons (REASON_* in C++)
+pub const REASON_DESTROY: u32 = 0x1;
+pub const REASON_RELEASE: u32 = 0x2;
+pub const REASON_MATERIAL: u32 = 0x8;
+pub const REASON_BATTLE: u32 = 0x20;
+pub const REASON_EFFECT: u32 = 0x40;
+pub const REASON_COST: u32 = 0x80;
+pub const REASON_ADJUST: u32 = 0x100;
+pub const REASON_LOST_TARGET: u32 = 0x200;
+pub const REASON_RULE: u32 = 0x400;
+pub const REASON_SPSUMMON: u32 = 0x800;
+pub const REASON_DISCARD: u32 = 0x4000;
+pub const REASON_FUSION: u32 = 0x40000;
+pub const REASON_SYNCHRO: u32 = 0x80000;
+pub const REASON_RITUAL: u32 = 0x100000;
+pub const REASON_XYZ: u32 = 0x200000;
+pub const REASON_REPLACE: u32 = 0x1000000;
+pub const REASON_REDIRECT: u32 = 0x4000000;
+pub const REASON_LINK: u32 = 0x10000000;
+pub const REASON_LOST_OVERLAY: u32 = 0x20000000;
+
+// Link arrows (LINK_MARKER_* in C++)
+pub const LINK_MARKER_BOTTOM_LEFT: u32 = 0x001;
+pub const LINK_MARKER_BOTTOM: u32 = 0x002;
+pub const LINK_MARKER_BOTTOM_RIGHT: u32 = 0x004;
+pub const LINK_MARKER_LEFT: u32 = 0x008;
+pub const LINK_MARKER_RIGHT: u32 = 0x020;
+pub const LINK_MARKER_TOP_LEFT: u32 = 0x040;
+pub const LINK_MARKER_TOP: u32 = 0x080;
+pub const LINK_MARKER_TOP_RIGHT: u32 = 0x100;
 
 // ChainInfo constants (for Duel.GetChainInfo)
 pub const CHAININFO_TRIGGERING_EFFECT: u32 = 0x1;
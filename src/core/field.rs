@@ -1,5 +1,5 @@
 use crate::core::types::CardId;
-use crate::core::enums::Location;
+use crate::core::enums::{Location, SEQ_DECKBOTTOM};
 
 /// Field stores lists of CardId for each player (0/1) and fixed-size zones.
 pub struct Field {
@@ -10,6 +10,12 @@ pub struct Field {
     pub extra: [Vec<CardId>; 2],
     pub mzone: [[Option<CardId>; 7]; 2],
     pub szone: [[Option<CardId>; 8]; 2],
+    /// Zones put out of use by EFFECT_DISABLE_FIELD: monster zones in bits 0..7, spell & trap zones in bits 8..15
+    pub disabled: [u32; 2],
+    /// In a tag duel, the Deck, hand and Extra Deck of the teammate waiting for their turn
+    pub tag_deck: [Vec<CardId>; 2],
+    pub tag_hand: [Vec<CardId>; 2],
+    pub tag_extra: [Vec<CardId>; 2],
 }
 
 impl Field {
@@ -22,17 +28,26 @@ impl Field {
             extra: [Vec::new(), Vec::new()],
             mzone: [[None; 7], [None; 7]],
             szone: [[None; 8], [None; 8]],
+            disabled: [0, 0],
+            tag_deck: [Vec::new(), Vec::new()],
+            tag_hand: [Vec::new(), Vec::new()],
+            tag_extra: [Vec::new(), Vec::new()],
         }
     }
 
     /// Add a card to the specified player/location/sequence.
-    /// For stacks (deck/hand/grave/remove/extra) we append to the vector.
-    /// For zones (mzone/szone) we place at the given sequence (index) and overwrite.
-    pub fn add_card(&mut self, player: u8, location: Location, card: CardId, sequence: u8) {
+    /// For stacks (deck/hand/grave/remove/extra) we append to the vector, except a card for the bottom of the deck.
+    /// For zones (mzone/szone) we place at the given sequence (index); an occupied or out-of-range zone
+    /// is refused and the call returns false.
+    pub fn add_card(&mut self, player: u8, location: Location, card: CardId, sequence: u8) -> bool {
         let p = player as usize;
         if location.contains(Location::DECK) {
-            // Insert at beginning to match C++ deck order (last card added is at position 0)
-            self.deck[p].insert(0, card);
+            // The end of the Vec is the top of the Deck; LOCATION_DECKBOT or SEQ_DECKBOTTOM puts the card at the bottom
+            if location.contains(Location::DECKBOT) || sequence == SEQ_DECKBOTTOM {
+                self.deck[p].insert(0, card);
+            } else {
+                self.deck[p].push(card);
+            }
         } else if location.contains(Location::HAND) {
             self.hand[p].push(card);
         } else if location.contains(Location::GRAVE) {
@@ -42,20 +57,19 @@ impl Field {
         } else if location.contains(Location::EXTRA) {
             self.extra[p].push(card);
         } else if location.contains(Location::MZONE) {
-            let idx = sequence as usize;
-            if idx < self.mzone[p].len() {
-                self.mzone[p][idx] = Some(card);
-            } else {
-                // ignore out-of-range placements silently for now
+            match self.mzone[p].get_mut(sequence as usize) {
+                Some(slot @ None) => *slot = Some(card),
+                _ => return false,
             }
         } else if location.contains(Location::SZONE) {
-            let idx = sequence as usize;
-            if idx < self.szone[p].len() {
-                self.szone[p][idx] = Some(card);
-            } else {
-                // ignore out-of-range placements silently for now
+            match self.szone[p].get_mut(sequence as usize) {
+                Some(slot @ None) => *slot = Some(card),
+                _ => return false,
             }
+        } else {
+            return false;
         }
+        true
     }
 
     /// Remove a card for a given player and location by sequence / index.
@@ -144,11 +158,11 @@ impl Field {
         None
     }
 
-    /// Find the first empty monster zone slot for a player
+    /// Find the first empty monster zone slot for a player that is not disabled
     pub fn find_empty_mzone_slot(&self, player: u8) -> Option<u8> {
         let p = player as usize;
         for (index, slot) in self.mzone[p].iter().enumerate() {
-            if slot.is_none() {
+            if slot.is_none() && self.disabled[p] & (1 << index) == 0 {
                 return Some(index as u8);
             }
         }
@@ -220,5 +234,19 @@ mod tests {
         }
         
         assert_eq!(f.find_empty_mzone_slot(0), None);
+
+        // Disabled zones are skipped
+        f.disabled[1] = 0b11;
+        assert_eq!(f.find_empty_mzone_slot(1), Some(2));
+    }
+
+    #[test]
+    fn add_card_refuses_taken_and_out_of_range_zones() {
+        let mut f = Field::new();
+        assert!(f.add_card(0, Location::MZONE, CardId::new(1), 2));
+        assert!(!f.add_card(0, Location::MZONE, CardId::new(2), 2), "zone already taken");
+        assert_eq!(f.mzone[0][2], Some(CardId::new(1)));
+        assert!(!f.add_card(0, Location::MZONE, CardId::new(3), 7));
+        assert!(!f.add_card(0, Location::SZONE, CardId::new(3), 8));
     }
 }
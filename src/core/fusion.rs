@@ -0,0 +1,260 @@
+//! Fusion summons: material checks through EFFECT_FUSION_MATERIAL, material selection and the card helpers the aux fusion procedures build on.
+
+use crate::core::card::set_code_matches;
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::group::Group;
+use crate::core::scripting::{duel_data, effect_function, effect_value};
+use crate::core::types::CardId;
+use mlua::{AnyUserData, Lua};
+
+impl DuelData {
+    /// Default fusion material pool of a player: monsters in the hand and monster zones.
+    pub fn fusion_material_pool(&self, player: u8) -> Vec<CardId> {
+        let p = player as usize;
+        self.field.hand[p].iter().copied()
+            .chain(self.field.mzone[p].iter().flatten().copied())
+            .filter(|id| self.cards.get(id.0 as usize).is_some_and(|c| c.current_stats.type_.contains(CardType::MONSTER)))
+            .collect()
+    }
+}
+
+/// Codes a card counts as when used as fusion material: code, alias and EFFECT_ADD_FUSION_CODE values.
+pub fn fusion_codes(lua: &Lua, card_id: CardId) -> mlua::Result<Vec<u32>> {
+    let data = duel_data(lua);
+    let (mut codes, added) = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(Vec::new());
+        };
+        let mut codes = vec![card.code];
+        if card.alias != 0 {
+            codes.push(card.alias);
+        }
+        (codes, data_guard.card_effects(card_id, EFFECT_ADD_FUSION_CODE))
+    };
+    for eid in added {
+        codes.push(effect_value(lua, &data, eid, (eid, card_id))?);
+    }
+    Ok(codes)
+}
+
+/// Archetype check for fusion material, including EFFECT_ADD_FUSION_SETCODE.
+pub fn is_fusion_set_card(lua: &Lua, card_id: CardId, set_code: u32) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let added = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        if card.is_set_card(set_code) {
+            return Ok(true);
+        }
+        data_guard.card_effects(card_id, EFFECT_ADD_FUSION_SETCODE)
+    };
+    for eid in added {
+        if set_code_matches(effect_value(lua, &data, eid, (eid, card_id))? as u64, set_code) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Whether a card may be used as material for `fusion` (EFFECT_CANNOT_BE_FUSION_MATERIAL).
+pub fn is_can_be_fusion_material(lua: &Lua, card_id: CardId, fusion: Option<CardId>) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let blockers = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        if !card.current_stats.type_.contains(CardType::MONSTER) {
+            return Ok(false);
+        }
+        data_guard.card_effects(card_id, EFFECT_CANNOT_BE_FUSION_MATERIAL)
+    };
+    for eid in blockers {
+        if effect_value(lua, &data, eid, (eid, fusion))? != 0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Whether a card can substitute for a named material of `fusion` (EFFECT_FUSION_SUBSTITUTE).
+/// A substitute effect without a value applies to every fusion monster.
+pub fn check_fusion_substitute(lua: &Lua, card_id: CardId, fusion: CardId) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let substitutes = data.lock().unwrap().card_effects(card_id, EFFECT_FUSION_SUBSTITUTE);
+    for eid in substitutes {
+        let unconditional = {
+            let data_guard = data.lock().unwrap();
+            let e = &data_guard.effects[eid.0 as usize];
+            e.value == 0 && e.value_fn.is_none()
+        };
+        if unconditional || effect_value(lua, &data, eid, (eid, fusion))? != 0 {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Whether `materials` (optionally forced to include `gc`) can fuse into `fusion`, via its material condition.
+pub fn check_fusion_material(lua: &Lua, fusion: CardId, materials: Group, gc: Option<CardId>, chkf: u32) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let Some(eid) = data.lock().unwrap().card_effects(fusion, EFFECT_FUSION_MATERIAL).first().copied() else {
+        return Ok(false);
+    };
+    match effect_function(lua, &data, eid, |e| &e.condition) {
+        Some(condition) => condition.call((eid, materials, gc, chkf)),
+        None => Ok(true),
+    }
+}
+
+/// Duel.SelectFusionMaterial runs in Lua, so the material operation it calls can wait on the host's selections.
+/// The chunk gets the Rust helpers that look up the operation and collect its Duel.SetFusionMaterial choice.
+const SELECT_FUSION_MATERIAL: &str = r#"
+    local material_operation, chosen_materials = ...
+    return function(player, c, mg, gc, chkf)
+        local e, operation = material_operation(c)
+        if operation then
+            operation(e, player, mg, 0, 0, nil, 0, 0, gc, chkf or PLAYER_NONE)
+        end
+        return chosen_materials()
+    end
+"#;
+
+/// Register the fusion functions on the global `Duel` table.
+pub fn register_fusion_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.GetFusionMaterial(player) -> Group of the default material pool
+    duel_table.set("GetFusionMaterial", lua.create_function(|lua, player: u8| {
+        let data = duel_data(lua);
+        let pool = data.lock().unwrap().fusion_material_pool(player);
+        Ok(Group(pool.into_iter().collect()))
+    })?)?;
+
+    // Duel.SelectFusionMaterial(player, c, mg[, gc, chkf]) -> Group
+    let material_operation = lua.create_function(|lua, fusion: CardId| {
+        let data = duel_data(lua);
+        data.lock().unwrap().fusion_materials.clear();
+        let Some(eid) = data.lock().unwrap().card_effects(fusion, EFFECT_FUSION_MATERIAL).first().copied() else {
+            return Ok((None, None));
+        };
+        Ok((Some(eid), effect_function(lua, &data, eid, |e| &e.operation)))
+    })?;
+    let chosen_materials = lua.create_function(|lua, ()| {
+        let chosen = std::mem::take(&mut duel_data(lua).lock().unwrap().fusion_materials);
+        Ok(Group(chosen.into_iter().collect()))
+    })?;
+    let select: mlua::Function = lua.load(SELECT_FUSION_MATERIAL).set_name("SelectFusionMaterial").call((material_operation, chosen_materials))?;
+    duel_table.set("SelectFusionMaterial", select)?;
+
+    // Duel.SetFusionMaterial(g) - called by material operations to report their choice
+    duel_table.set("SetFusionMaterial", lua.create_function(|lua, group: AnyUserData| {
+        let chosen: Vec<CardId> = group.borrow::<Group>()?.0.iter().copied().collect();
+        duel_data(lua).lock().unwrap().fusion_materials = chosen;
+        Ok(())
+    })?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::database::CardData;
+    use crate::core::enums::{CardPosition, CardStatus, CardType, Location, REASON_FUSION, REASON_MATERIAL, SUMMON_TYPE_FUSION};
+    use crate::core::processor::ProcessResult;
+    use crate::core::testing::{card, duel_with};
+    use crate::core::types::CardId;
+
+    fn cards() -> Vec<CardData> {
+        let mut cards = vec![card(8001, CardType::MONSTER | CardType::FUSION, 8)];
+        cards.extend((8002..8005).map(|code| card(code, CardType::MONSTER | CardType::NORMAL, 4)));
+        cards
+    }
+
+    /// Fusion monster 8001 needs 8002 + 8003, through aux.AddFusionProcCode2.
+    const SETUP: &str = r#"
+        fusion = Debug.AddCard(8001, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+        mat_a = Debug.AddCard(8002, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+        mat_b = Debug.AddCard(8003, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+        other = Debug.AddCard(8004, 0, 0, LOCATION_HAND, 1, POS_FACEDOWN)
+        fusion:EnableReviveLimit()
+        aux.AddFusionProcCode2(fusion, 8002, 8003, true, true)
+    "#;
+
+    #[test]
+    fn polymerization_flow_summons_from_extra() {
+        let mut duel = duel_with(&cards(), SETUP);
+
+        let operation = duel.lua.load(r#"
+            local mg = Duel.GetFusionMaterial(0)
+            can = fusion:CheckFusionMaterial(mg, nil, 0)
+            local mat = Duel.SelectFusionMaterial(0, fusion, mg, nil, 0)
+            fusion:SetMaterial(mat)
+            Duel.SendtoGrave(mat, REASON_EFFECT + REASON_MATERIAL + REASON_FUSION)
+            count = Duel.SpecialSummon(fusion, SUMMON_TYPE_FUSION, 0, 0, false, false, POS_FACEUP)
+            fusion:CompleteProcedure()
+        "#).into_function().unwrap();
+        duel.execute_operation(operation).unwrap();
+        // The material operation asks for each of the two materials in turn
+        for _ in 0..2 {
+            assert_eq!(duel.process(), ProcessResult::Waiting);
+            duel.set_responseb(&[1, 0]);
+        }
+        assert_eq!(duel.process(), ProcessResult::Waiting, "then the zone, one of the Extra Monster Zones");
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 5]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "POS_FACEUP leaves Attack or Defense Position to the player");
+        duel.set_responsei(CardPosition::FACEUP_DEFENSE.bits() as i32);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let (can, count): (bool, u32) = duel.lua.load("return can, count").eval().unwrap();
+        assert!(can);
+        assert_eq!(count, 1);
+
+        let data = duel.data.lock().unwrap();
+        let fusion = data.get_card(CardId::new(0)).unwrap();
+        assert_eq!(fusion.location, Location::MZONE);
+        assert_eq!(fusion.position.bits(), CardPosition::FACEUP_DEFENSE.bits());
+        assert_eq!(fusion.summon_type(), SUMMON_TYPE_FUSION);
+        assert_eq!(fusion.summon_location(), Location::EXTRA.bits());
+        assert!(fusion.has_status(CardStatus::PROC_COMPLETE));
+        assert_eq!(fusion.materials, vec![CardId::new(1), CardId::new(2)]);
+        assert!(data.field.extra[0].is_empty());
+        for id in 1..3 {
+            let mat = data.get_card(CardId::new(id)).unwrap();
+            assert_eq!(mat.location, Location::GRAVE);
+            assert_eq!(mat.reason & (REASON_MATERIAL | REASON_FUSION), REASON_MATERIAL | REASON_FUSION);
+        }
+        assert_eq!(data.get_card(CardId::new(3)).unwrap().location, Location::HAND);
+    }
+
+    #[test]
+    fn material_checks_honor_substitutes_and_restrictions() {
+        let duel = duel_with(&cards(), SETUP);
+        let (before, after, substitute, added): (bool, bool, bool, bool) = duel.lua.load(r#"
+            local before = fusion:CheckFusionMaterial(Duel.GetFusionMaterial(0), nil, 0)
+            local lock = Effect.CreateEffect(mat_b)
+            lock:SetType(EFFECT_TYPE_SINGLE)
+            lock:SetCode(EFFECT_CANNOT_BE_FUSION_MATERIAL)
+            lock:SetValue(1)
+            mat_b:RegisterEffect(lock)
+            local after = fusion:CheckFusionMaterial(Duel.GetFusionMaterial(0), nil, 0)
+            local sub = Effect.CreateEffect(other)
+            sub:SetType(EFFECT_TYPE_SINGLE)
+            sub:SetCode(EFFECT_FUSION_SUBSTITUTE)
+            other:RegisterEffect(sub)
+            local code = Effect.CreateEffect(other)
+            code:SetType(EFFECT_TYPE_SINGLE)
+            code:SetCode(EFFECT_ADD_FUSION_CODE)
+            code:SetValue(8003)
+            other:RegisterEffect(code)
+            return before, after, other:CheckFusionSubstitute(fusion), other:IsFusionCode(8003)
+        "#).eval().unwrap();
+        assert!(before);
+        assert!(!after, "the only 8003 can no longer be used as material");
+        assert!(substitute);
+        assert!(added);
+    }
+}
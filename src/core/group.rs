@@ -1,14 +1,96 @@
-use mlua::{UserData, UserDataMethods, MetaMethod};
+use mlua::{AnyUserData, Function, IntoLua, Lua, MultiValue, UserData, UserDataMethods, MetaMethod, Table, Value};
+use crate::core::duel::DuelData;
+use crate::core::enums::Location;
+use crate::core::prompt::{ready, select_cards, select_sum, set_prompting};
+use crate::core::response::find_sum;
+use crate::core::scripting::duel_data;
 use crate::core::types::CardId;
 
-/// Represents a collection of unique Card IDs
+/// Represents a collection of unique Card IDs, ordered by card id so selection indices are stable
 #[derive(Debug, Clone)]
-pub struct Group(pub std::collections::HashSet<CardId>);
+pub struct Group(pub std::collections::BTreeSet<CardId>);
 
 impl Group {
     /// Creates a new empty Group
     pub fn new() -> Self {
-        Group(std::collections::HashSet::new())
+        Group(std::collections::BTreeSet::new())
+    }
+
+    /// Cards of the group that pass the Lua filter `f(c, ...)`, skipping the excluded cards.
+    /// A nil filter accepts every card.
+    pub fn filtered<'lua>(&self, lua: &'lua Lua, f: &Option<Function<'lua>>, ex: &Value, args: &MultiValue<'lua>) -> mlua::Result<Vec<CardId>> {
+        let excluded = cards_of(ex);
+        let mut out = Vec::new();
+        for &card_id in self.0.iter().filter(|c| !excluded.contains(c)) {
+            let keep = match f {
+                Some(f) => {
+                    let mut call_args = args.clone();
+                    call_args.push_front(card_id.into_lua(lua)?);
+                    f.call::<_, bool>(call_args)?
+                }
+                None => true,
+            };
+            if keep {
+                out.push(card_id);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Cards of the group paired with the sum parameter `f(c, ...)` gives them.
+    pub fn sum_params<'lua>(&self, lua: &'lua Lua, f: &Function<'lua>, args: &MultiValue<'lua>) -> mlua::Result<Vec<(CardId, u32)>> {
+        let mut out = Vec::new();
+        for &card_id in &self.0 {
+            let mut call_args = args.clone();
+            call_args.push_front(card_id.into_lua(lua)?);
+            out.push((card_id, f.call::<_, u32>(call_args)?));
+        }
+        Ok(out)
+    }
+}
+
+impl DuelData {
+    /// Cards in locations `s` of `player`'s side and `o` of the opponent's.
+    pub fn field_group(&self, player: u8, s: u32, o: u32) -> Vec<CardId> {
+        let mut cards = Vec::new();
+        for (side, locations) in [(player, s), (1 - player, o)] {
+            let (p, locations) = (side as usize, Location::from_bits_truncate(locations));
+            if locations.contains(Location::MZONE) {
+                cards.extend(self.field.mzone[p].iter().flatten());
+            }
+            if locations.contains(Location::SZONE) {
+                cards.extend(self.field.szone[p].iter().flatten());
+            }
+            let piles = [
+                (Location::DECK, &self.field.deck[p]),
+                (Location::HAND, &self.field.hand[p]),
+                (Location::GRAVE, &self.field.grave[p]),
+                (Location::REMOVED, &self.field.remove[p]),
+                (Location::EXTRA, &self.field.extra[p]),
+            ];
+            for (location, pile) in piles {
+                if locations.contains(location) {
+                    cards.extend(pile.iter().copied());
+                }
+            }
+        }
+        cards
+    }
+}
+
+/// Cards named by a Card-or-Group argument such as `ex`; anything else (nil) names none.
+pub(crate) fn cards_of(value: &Value) -> Vec<CardId> {
+    match value {
+        Value::UserData(ud) => {
+            if let Ok(card_id) = ud.borrow::<CardId>() {
+                vec![*card_id]
+            } else if let Ok(group) = ud.borrow::<Group>() {
+                group.0.iter().copied().collect()
+            } else {
+                Vec::new()
+            }
+        }
+        _ => Vec::new(),
     }
 }
 
@@ -23,12 +105,168 @@ impl UserData for Group {
         methods.add_meta_method(MetaMethod::Len, |_, self_, ()| {
             Ok(self_.0.len() as u32)
         });
-        
+
+        // Methods: g:KeepAlive() / g:DeleteGroup() - groups are Lua-owned values, so there is nothing to pin or free
+        methods.add_method("KeepAlive", |_, _, ()| Ok(()));
+        methods.add_method("DeleteGroup", |_, _, ()| Ok(()));
+
         // Method: g:AddCard(card) - stub for now, accepts CardId for testing
         // In reality, this should accept a Card UserData, but we'll use CardId for initial testing
         methods.add_method_mut("AddCard", |_, self_, card_id: CardId| {
             self_.0.insert(card_id);
             Ok(())
         });
+
+        // Method: g:RemoveCard(c)
+        methods.add_method_mut("RemoveCard", |_, self_, card_id: CardId| {
+            self_.0.remove(&card_id);
+            Ok(())
+        });
+
+        // Method: g:IsContains(c)
+        methods.add_method("IsContains", |_, self_, card_id: CardId| {
+            Ok(self_.0.contains(&card_id))
+        });
+
+        // Method: g:GetFirst() - the card with the lowest id, or nil
+        methods.add_method("GetFirst", |_, self_, ()| {
+            Ok(self_.0.iter().next().copied())
+        });
+
+        // Method: g:Clone()
+        methods.add_method("Clone", |_, self_, ()| {
+            Ok(self_.clone())
+        });
+
+        // Method: g:Merge(other) - other may be a Card or a Group
+        methods.add_method_mut("Merge", |_, self_, other: Value| {
+            self_.0.extend(cards_of(&other));
+            Ok(())
+        });
+
+        // Method: g:Sub(other) - other may be a Card or a Group
+        methods.add_method_mut("Sub", |_, self_, other: Value| {
+            for card_id in cards_of(&other) {
+                self_.0.remove(&card_id);
+            }
+            Ok(())
+        });
+
+        // Method: g:Filter(f, ex, ...) -> Group
+        methods.add_method("Filter", |lua, self_, (f, ex, args): (Option<Function>, Value, MultiValue)| {
+            Ok(Group(self_.filtered(lua, &f, &ex, &args)?.into_iter().collect()))
+        });
+
+        // Method: g:FilterCount(f, ex, ...) -> count
+        methods.add_method("FilterCount", |lua, self_, (f, ex, args): (Option<Function>, Value, MultiValue)| {
+            Ok(self_.filtered(lua, &f, &ex, &args)?.len() as u32)
+        });
+
+        // Method: g:IsExists(f, count, ex, ...) - at least `count` cards pass the filter
+        methods.add_method("IsExists", |lua, self_, (f, count, ex, args): (Option<Function>, u32, Value, MultiValue)| {
+            Ok(self_.filtered(lua, &f, &ex, &args)?.len() >= count as usize)
+        });
+
+        // Method: g:CheckWithSumEqual(f, sum, min, max, ...) - some min..max cards whose f values add up to exactly `sum`
+        methods.add_method("CheckWithSumEqual", |lua, self_, (f, acc, min, max, args): (Function, u32, usize, usize, MultiValue)| {
+            let (must, cards) = sum_split(lua, self_, &f, &args)?;
+            Ok(find_sum(&params_of(&must), &params_of(&cards), acc, false, min, max).is_some())
+        });
+
+        // Method: g:CheckWithSumGreater(f, sum, ...) - some cards reach `sum` with every one of them needed
+        methods.add_method("CheckWithSumGreater", |lua, self_, (f, acc, args): (Function, u32, MultiValue)| {
+            let (must, cards) = sum_split(lua, self_, &f, &args)?;
+            let min = usize::from(must.is_empty());
+            Ok(find_sum(&params_of(&must), &params_of(&cards), acc, true, min, cards.len()).is_some())
+        });
+
+        // Methods that may wait on the host are Lua functions of the global Group table, see register_group_functions
+        methods.add_meta_function(MetaMethod::Index, |lua, (_, name): (Value, Value)| {
+            lua.globals().get::<_, Table>("Group")?.raw_get::<_, Value>(name)
+        });
     }
-}
\ No newline at end of file
+}
+
+/// Cards paired with their sum parameters.
+type SumCards = Vec<(CardId, u32)>;
+
+/// Cards forced by Duel.SetSelectedCard and the other cards of `g`, paired with their sum parameters `f(c, ...)`.
+/// The forced cards are used up by the check or selection that takes them.
+fn sum_split<'lua>(lua: &'lua Lua, g: &Group, f: &Function<'lua>, args: &MultiValue<'lua>) -> mlua::Result<(SumCards, SumCards)> {
+    let forced = std::mem::take(&mut duel_data(lua).lock().unwrap().must_select_cards);
+    let must = Group(forced.iter().copied().collect()).sum_params(lua, f, args)?;
+    let cards = Group(g.0.iter().copied().filter(|c| !forced.contains(c)).collect()).sum_params(lua, f, args)?;
+    Ok((must, cards))
+}
+
+fn params_of(cards: &[(CardId, u32)]) -> Vec<u32> {
+    cards.iter().map(|c| c.1).collect()
+}
+
+/// Let `player` add cards of `cards` to the forced `must` ones until their sum parameters reach `acc`; an empty
+/// group when no choice adds up. The group holds the chosen cards without the forced ones.
+#[allow(clippy::too_many_arguments)]
+fn select_with_sum<'lua>(lua: &'lua Lua, player: u8, must: SumCards, cards: SumCards, acc: u32, greater: bool, min: usize, max: usize) -> mlua::Result<MultiValue<'lua>> {
+    if find_sum(&params_of(&must), &params_of(&cards), acc, greater, min, max).is_none() {
+        return ready(lua, Group::new());
+    }
+    select_sum(lua, player, acc, greater, min, max, &must, cards, |lua, chosen| ready(lua, Group(chosen.into_iter().collect())))
+}
+
+/// Cards in `player`'s locations `s` and the opponent's `o` that pass `f(c, ...)`, without the excluded ones.
+fn matching_cards<'lua>(lua: &'lua Lua, f: &Option<Function<'lua>>, player: u8, s: u32, o: u32, ex: &Value<'lua>, args: &MultiValue<'lua>) -> mlua::Result<Vec<CardId>> {
+    let cards = duel_data(lua).lock().unwrap().field_group(player, s, o);
+    Group(cards.into_iter().collect()).filtered(lua, f, ex, args)
+}
+
+/// Register the Group methods that ask the host, on the global `Group` table, and the Duel functions that
+/// gather or pick cards across locations.
+pub fn register_group_functions(lua: &Lua) -> mlua::Result<()> {
+    let group_table: Table = lua.globals().get("Group")?;
+
+    // Method: g:Select(player, min, max, ex) -> Group
+    set_prompting(lua, &group_table, "Select", |lua, (g, player, min, max, ex): (AnyUserData, u8, usize, usize, Value)| {
+        let excluded = cards_of(&ex);
+        let cards: Vec<CardId> = g.borrow::<Group>()?.0.iter().copied().filter(|c| !excluded.contains(c)).collect();
+        select_cards(lua, player, cards, min, max, |lua, chosen| ready(lua, Group(chosen.into_iter().collect())))
+    })?;
+
+    // Method: g:SelectWithSumEqual(player, f, sum, min, max, ...) -> Group
+    set_prompting(lua, &group_table, "SelectWithSumEqual", |lua, (g, player, f, acc, min, max, args): (AnyUserData, u8, Function, u32, usize, usize, MultiValue)| {
+        let (must, cards) = sum_split(lua, &g.borrow::<Group>()?.clone(), &f, &args)?;
+        select_with_sum(lua, player, must, cards, acc, false, min, max)
+    })?;
+
+    // Method: g:SelectWithSumGreater(player, f, sum, ...) -> Group
+    set_prompting(lua, &group_table, "SelectWithSumGreater", |lua, (g, player, f, acc, args): (AnyUserData, u8, Function, u32, MultiValue)| {
+        let (must, cards) = sum_split(lua, &g.borrow::<Group>()?.clone(), &f, &args)?;
+        let (min, max) = (usize::from(must.is_empty()), cards.len());
+        select_with_sum(lua, player, must, cards, acc, true, min, max)
+    })?;
+
+    let duel_table: Table = lua.globals().get("Duel")?;
+
+    // Duel.GetMatchingGroup(f, player, s, o, ex, ...) -> Group
+    duel_table.set("GetMatchingGroup", lua.create_function(|lua, (f, player, s, o, ex, args): (Option<Function>, u8, u32, u32, Value, MultiValue)| {
+        Ok(Group(matching_cards(lua, &f, player, s, o, &ex, &args)?.into_iter().collect()))
+    })?)?;
+
+    // Duel.IsExistingMatchingCard(f, player, s, o, count, ex, ...)
+    duel_table.set("IsExistingMatchingCard", lua.create_function(|lua, (f, player, s, o, count, ex, args): (Option<Function>, u8, u32, u32, usize, Value, MultiValue)| {
+        Ok(matching_cards(lua, &f, player, s, o, &ex, &args)?.len() >= count)
+    })?)?;
+
+    // Duel.SelectMatchingCard(sel_player, f, player, s, o, min, max, ex, ...) -> Group
+    set_prompting(lua, &duel_table, "SelectMatchingCard", |lua, (sel_player, f, player, s, o, min, max, ex, args): (u8, Option<Function>, u8, u32, u32, usize, usize, Value, MultiValue)| {
+        let cards = matching_cards(lua, &f, player, s, o, &ex, &args)?;
+        select_cards(lua, sel_player, cards, min, max, |lua, chosen| ready(lua, Group(chosen.into_iter().collect())))
+    })?;
+
+    // Duel.SetSelectedCard(cards) - a Card or Group the next sum check or selection must include
+    duel_table.set("SetSelectedCard", lua.create_function(|lua, cards: Value| {
+        duel_data(lua).lock().unwrap().must_select_cards = cards_of(&cards);
+        Ok(())
+    })?)?;
+
+    Ok(())
+}
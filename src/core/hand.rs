@@ -0,0 +1,107 @@
+//! The hand size limit: at the End Phase the turn player discards down to it.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::messages::{CardCandidate, MsgSelectCard};
+use crate::core::response::{Prompt, Response};
+use crate::core::scripting::{duel_data, effect_value};
+use crate::core::types::CardId;
+use mlua::Lua;
+
+/// Cards a player may keep in hand at the end of their turn
+pub const HAND_LIMIT: usize = 6;
+
+/// `player`'s hand limit: the value of the latest EFFECT_HAND_LIMIT on them, else HAND_LIMIT.
+pub fn hand_limit(lua: &Lua, player: u8) -> mlua::Result<usize> {
+    let data = duel_data(lua);
+    let limits = data.lock().unwrap().player_effects(player, EFFECT_HAND_LIMIT);
+    match limits.last() {
+        Some(&eid) => Ok(effect_value(lua, &data, eid, eid)? as usize),
+        None => Ok(HAND_LIMIT),
+    }
+}
+
+impl DuelData {
+    /// Ask `player` which `count` cards of their hand to discard (MSG_SELECT_CARD) and wait for the answer.
+    pub fn request_discard(&mut self, player: u8, count: usize) {
+        let hand = &self.field.hand[player as usize];
+        let candidates = hand.iter()
+            .map(|&id| CardCandidate { code: self.cards[id.0 as usize].code, location: self.info_location(id) })
+            .collect();
+        let msg = MsgSelectCard { player, cancelable: false, min: count as u8, max: count as u8, cards: candidates };
+        self.prompt = Some(Prompt::SelectCard { count: hand.len(), min: count, max: count, cancelable: false });
+        self.write_message(&msg.encode());
+    }
+
+    /// The hand cards picked in answer to request_discard, or None while no valid answer was given.
+    pub fn take_discard(&mut self, player: u8) -> Option<Vec<CardId>> {
+        match self.take_response() {
+            Ok(Response::Cards(indices)) => Some(indices.into_iter().map(|i| self.field.hand[player as usize][i]).collect()),
+            Ok(_) => Some(Vec::new()),
+            Err(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::messages::MsgType;
+    use crate::core::processor::{ProcessResult, ProcessorUnit};
+    use crate::core::types::CardId;
+
+    fn end_phase_with_hand(duel: &mut Duel, size: u32) {
+        for code in 0..size {
+            duel.lua.load(format!("Debug.AddCard({}, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)", 9500 + code)).exec().expect("setup");
+        }
+        let mut data = duel.data.lock().unwrap();
+        data.message_buffer.clear();
+        data.processor_units.clear();
+        data.processor_units.push_back(ProcessorUnit::phase_event(0, Phase::END.bits()));
+    }
+
+    #[test]
+    fn turn_player_discards_down_to_six() {
+        let mut duel = Duel::new(0);
+        end_phase_with_hand(&mut duel, 8);
+        duel.lua.load(r#"
+            local e = Effect.CreateEffect(Card(0))
+            e:SetType(EFFECT_TYPE_SINGLE)
+            e:SetCode(EVENT_DISCARD)
+            Card(0):RegisterEffect(e)
+        "#).exec().expect("setup");
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        assert!(duel.data.lock().unwrap().message_buffer.contains(&MsgType::SelectCard.id()));
+        // Two cards must be picked; one is not enough
+        duel.set_responseb(&[1, 0]);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[2, 0, 7]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.field.hand[0].len(), 6);
+        assert_eq!(data.field.grave[0], vec![CardId::new(0), CardId::new(7)]);
+        let reason = data.get_card(CardId::new(7)).unwrap().reason;
+        assert_eq!(reason & (REASON_RULE | REASON_DISCARD), REASON_RULE | REASON_DISCARD);
+        assert!(data.triggered_effects.iter().any(|e| data.effects[e.0 as usize].code == EVENT_DISCARD), "EVENT_DISCARD was raised");
+    }
+
+    #[test]
+    fn hand_limit_effects_change_the_limit() {
+        let mut duel = Duel::new(0);
+        end_phase_with_hand(&mut duel, 8);
+        duel.lua.load(r#"
+            local e = Effect.CreateEffect(Card(0))
+            e:SetType(EFFECT_TYPE_FIELD)
+            e:SetCode(EFFECT_HAND_LIMIT)
+            e:SetProperty(EFFECT_FLAG_PLAYER_TARGET)
+            e:SetTargetRange(1, 0)
+            e:SetValue(8)
+            Duel.RegisterEffect(e, 0)
+        "#).exec().expect("setup");
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.field.hand[0].len(), 8);
+        assert!(data.processor_units.is_empty());
+    }
+}
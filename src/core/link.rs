@@ -0,0 +1,399 @@
+//! Link summons: link arrows and linked zones, the Extra Monster Zone placement rules and link material selection.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::group::Group;
+use crate::core::messages::MsgRetry;
+use crate::core::prompt::{ready, select_sum, set_prompting};
+use crate::core::response::check_sum_equal;
+use crate::core::scripting::{duel_data, effect_value};
+use crate::core::types::CardId;
+use mlua::{AnyUserData, Function, Lua, MultiValue};
+
+impl DuelData {
+    /// Zones the arrows of a Link monster point to: the controller's monster zones in bits 0..7
+    /// and the opponent's in bits 16..23 (card::get_linked_zone in C++).
+    pub fn card_linked_zone(&self, card_id: CardId) -> u32 {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return 0;
+        };
+        if !card.current_stats.type_.contains(CardType::LINK) || card.location != Location::MZONE {
+            return 0;
+        }
+        let marker = |m: u32| card.current_stats.link_marker & m != 0;
+        let s = card.sequence as u32;
+        let mut zones = 0;
+        if (1..=4).contains(&s) && marker(LINK_MARKER_LEFT) {
+            zones |= 1 << (s - 1);
+        }
+        if s <= 3 && marker(LINK_MARKER_RIGHT) {
+            zones |= 1 << (s + 1);
+        }
+        if (s == 0 && marker(LINK_MARKER_TOP_RIGHT)) || (s == 1 && marker(LINK_MARKER_TOP)) || (s == 2 && marker(LINK_MARKER_TOP_LEFT)) {
+            zones |= (1 << 5) | (1 << (16 + 6));
+        }
+        if (s == 2 && marker(LINK_MARKER_TOP_RIGHT)) || (s == 3 && marker(LINK_MARKER_TOP)) || (s == 4 && marker(LINK_MARKER_TOP_LEFT)) {
+            zones |= (1 << 6) | (1 << (16 + 5));
+        }
+        if s == 5 || s == 6 {
+            // Bottom arrows reach the controller's main zones, top arrows the opponent's (seen from their side)
+            let (bottom, top) = if s == 5 { ([0, 1, 2], [4, 3, 2]) } else { ([2, 3, 4], [2, 1, 0]) };
+            for (zone, m) in bottom.into_iter().zip([LINK_MARKER_BOTTOM_LEFT, LINK_MARKER_BOTTOM, LINK_MARKER_BOTTOM_RIGHT]) {
+                if marker(m) {
+                    zones |= 1 << zone;
+                }
+            }
+            for (zone, m) in top.into_iter().zip([LINK_MARKER_TOP_LEFT, LINK_MARKER_TOP, LINK_MARKER_TOP_RIGHT]) {
+                if marker(m) {
+                    zones |= 1 << (16 + zone);
+                }
+            }
+        }
+        zones
+    }
+
+    /// Monsters in the zones a Link monster points to.
+    pub fn linked_cards(&self, card_id: CardId) -> Vec<CardId> {
+        let Some(controller) = self.cards.get(card_id.0 as usize).map(|c| c.controller as usize) else {
+            return Vec::new();
+        };
+        let zones = self.card_linked_zone(card_id);
+        (0..7).filter(|i| zones & (1 << i) != 0).filter_map(|i| self.field.mzone[controller][i])
+            .chain((0..7).filter(|i| zones & (1 << (16 + i)) != 0).filter_map(|i| self.field.mzone[1 - controller][i]))
+            .collect()
+    }
+
+    /// Monster zones of `player` pointed to by any Link monster on the field, ignoring the `leaving` ones.
+    pub fn linked_zone(&self, player: u8, leaving: &[CardId]) -> u32 {
+        let p = player as usize;
+        let mut zones = 0;
+        for (side, shift) in [(p, 0), (1 - p, 16)] {
+            for id in self.field.mzone[side].iter().flatten().filter(|id| !leaving.contains(id)) {
+                zones |= (self.card_linked_zone(*id) >> shift) & 0x7f;
+            }
+        }
+        zones
+    }
+
+    /// Monster zones an Extra Deck monster may be summoned to, counting the zones `leaving` will free.
+    /// From Master Rule 4 on it needs an Extra Monster Zone or a linked zone; Master Rule 5 keeps that
+    /// only for Link monsters and face-up Pendulum monsters.
+    pub fn extra_summon_zones(&self, player: u8, card: Option<CardId>, leaving: &[CardId]) -> Vec<u8> {
+        let p = player as usize;
+        let taken = |side: usize, seq: usize| self.field.mzone[side][seq].is_some_and(|id| !leaving.contains(&id));
+        let main = (0..5u8).filter(|&s| !taken(p, s as usize));
+        if self.duel_rule < 4 {
+            return main.collect();
+        }
+        let restricted = self.duel_rule == 4 || card.and_then(|id| self.cards.get(id.0 as usize)).is_none_or(|c| {
+            c.current_stats.type_.contains(CardType::LINK)
+                || (c.current_stats.type_.contains(CardType::PENDULUM) && c.location == Location::EXTRA && c.position.intersects(CardPosition::FACEUP))
+        });
+        let linked = self.linked_zone(player, leaving);
+        let mut zones: Vec<u8> = main.filter(|&s| !restricted || linked & (1 << s) != 0).collect();
+        // Each player may occupy only one Extra Monster Zone
+        if !taken(p, 5) && !taken(p, 6) {
+            zones.extend((5..7u8).filter(|&s| !taken(1 - p, 11 - s as usize)));
+        }
+        zones.retain(|&s| self.field.disabled[p] & (1 << s) == 0);
+        zones
+    }
+}
+
+/// Link rating of a card (0 for non-Link monsters).
+fn link_rating(data: &DuelData, card_id: CardId) -> u32 {
+    data.cards.get(card_id.0 as usize)
+        .filter(|c| c.current_stats.type_.contains(CardType::LINK))
+        .map(|c| c.current_stats.link)
+        .unwrap_or(0)
+}
+
+/// Whether a card may be used as link material for `link` (EFFECT_CANNOT_BE_LINK_MATERIAL).
+pub fn is_can_be_link_material(lua: &Lua, card_id: CardId, link: Option<CardId>) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let blockers = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        if !card.current_stats.type_.contains(CardType::MONSTER) {
+            return Ok(false);
+        }
+        if card.location == Location::MZONE && !card.position.intersects(CardPosition::FACEUP) {
+            return Ok(false);
+        }
+        data_guard.card_effects(card_id, EFFECT_CANNOT_BE_LINK_MATERIAL)
+    };
+    for eid in blockers {
+        if effect_value(lua, &data, eid, (eid, link))? != 0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Candidate materials for `link` paired with their sum parameter: 1, or 1 | rating << 16 for Link monsters.
+fn link_candidates(lua: &Lua, link: CardId, f: &Option<Function>, mg: Option<AnyUserData>) -> mlua::Result<Vec<(CardId, u32)>> {
+    let pool: Vec<CardId> = match mg {
+        Some(mg) => mg.borrow::<Group>()?.0.iter().copied().collect(),
+        None => {
+            let data = duel_data(lua);
+            let data_guard = data.lock().unwrap();
+            let player = data_guard.get_card(link).map(|c| c.controller as usize).unwrap_or(0);
+            data_guard.field.mzone[player].iter().flatten().copied().collect()
+        }
+    };
+    let mut candidates = Vec::new();
+    for id in pool {
+        if id == link || !is_can_be_link_material(lua, id, Some(link))? {
+            continue;
+        }
+        let passes = match f {
+            Some(f) => f.call((id, link))?,
+            None => true,
+        };
+        if passes {
+            let rating = link_rating(&duel_data(lua).lock().unwrap(), id);
+            candidates.push((id, if rating > 1 { 1 | rating << 16 } else { 1 }));
+        }
+    }
+    Ok(candidates)
+}
+
+/// Search for link materials: which candidates add up to the link rating and still leave a zone for the Link monster.
+struct LinkSearch<'a> {
+    data: &'a DuelData,
+    player: u8,
+    link: CardId,
+    cards: &'a [(CardId, u32)],
+    min: usize,
+    max: usize,
+}
+
+impl LinkSearch<'_> {
+    fn is_valid(&self, chosen: &[(CardId, u32)]) -> bool {
+        let params: Vec<u32> = chosen.iter().map(|c| c.1).collect();
+        let leaving: Vec<CardId> = chosen.iter().map(|c| c.0).collect();
+        chosen.len() >= self.min
+            && check_sum_equal(&params, link_rating(self.data, self.link))
+            && !self.data.extra_summon_zones(self.player, Some(self.link), &leaving).is_empty()
+    }
+
+    fn extend(&self, start: usize, chosen: &mut Vec<(CardId, u32)>) -> bool {
+        if self.is_valid(chosen) {
+            return true;
+        }
+        if chosen.len() == self.max {
+            return false;
+        }
+        for i in start..self.cards.len() {
+            chosen.push(self.cards[i]);
+            if self.extend(i + 1, chosen) {
+                return true;
+            }
+            chosen.pop();
+        }
+        false
+    }
+
+    /// First choice of `min..=max` candidates that works.
+    fn find(&self) -> Option<Vec<CardId>> {
+        let mut chosen = Vec::new();
+        self.extend(0, &mut chosen).then(|| chosen.into_iter().map(|c| c.0).collect())
+    }
+}
+
+/// Let `player` pick the materials of `link` with MSG_SELECT_SUM. A choice that adds up but leaves
+/// the Link monster no zone is refused with MSG_RETRY and asked again.
+fn select_link_materials(lua: &Lua, player: u8, link: CardId, cards: Vec<(CardId, u32)>, min: usize, max: usize) -> mlua::Result<MultiValue<'_>> {
+    let rating = link_rating(&duel_data(lua).lock().unwrap(), link);
+    let candidates = cards.clone();
+    select_sum(lua, player, rating, false, min, max, &[], cards, move |lua, chosen| {
+        let data = duel_data(lua);
+        let mut data_guard = data.lock().unwrap();
+        if data_guard.extra_summon_zones(player, Some(link), &chosen).is_empty() {
+            data_guard.write_message(&MsgRetry.encode());
+            drop(data_guard);
+            return select_link_materials(lua, player, link, candidates, min, max);
+        }
+        ready(lua, Group(chosen.into_iter().collect()))
+    })
+}
+
+/// Register the link functions on the global `Duel` table.
+#[allow(clippy::type_complexity)]
+pub fn register_link_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.GetLinkedZone(player) -> monster zones of player pointed to by a link arrow
+    duel_table.set("GetLinkedZone", lua.create_function(|lua, player: u8| {
+        Ok(duel_data(lua).lock().unwrap().linked_zone(player, &[]))
+    })?)?;
+
+    // Duel.CheckLinkMaterial(c, f, minc, maxc[, mg])
+    duel_table.set("CheckLinkMaterial", lua.create_function(|lua, (link, f, min, max, mg): (CardId, Option<Function>, usize, usize, Option<AnyUserData>)| {
+        let cards = link_candidates(lua, link, &f, mg)?;
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        let player = data_guard.get_card(link).map(|c| c.controller).unwrap_or(0);
+        Ok(LinkSearch { data: &data_guard, player, link, cards: &cards, min, max }.find().is_some())
+    })?)?;
+
+    // Duel.SelectLinkMaterial(player, c, f, minc, maxc[, mg]) -> Group
+    set_prompting(lua, &duel_table, "SelectLinkMaterial", |lua, (player, link, f, min, max, mg): (u8, CardId, Option<Function>, usize, usize, Option<AnyUserData>)| {
+        let cards = link_candidates(lua, link, &f, mg)?;
+        let possible = {
+            let data = duel_data(lua);
+            let data_guard = data.lock().unwrap();
+            LinkSearch { data: &data_guard, player, link, cards: &cards, min, max }.find().is_some()
+        };
+        if !possible {
+            return ready(lua, Group::new());
+        }
+        select_link_materials(lua, player, link, cards, min, max)
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::database::CardData;
+    use crate::core::enums::*;
+    use crate::core::messages::MsgType;
+    use crate::core::processor::ProcessResult;
+    use crate::core::testing::{card, duel_with};
+    use crate::core::types::CardId;
+
+    fn link(code: u32, rating: u32, markers: u32) -> CardData {
+        CardData { link_marker: markers, ..card(code, CardType::MONSTER | CardType::LINK, rating) }
+    }
+
+    /// Link-2 procedure of `linker` written the way aux.AddLinkProcedure(c, nil, 2, 2) builds it.
+    const PROCEDURE: &str = r#"
+        linker:EnableReviveLimit()
+        local e = Effect.CreateEffect(linker)
+        e:SetType(EFFECT_TYPE_FIELD)
+        e:SetCode(EFFECT_SPSUMMON_PROC)
+        e:SetProperty(EFFECT_FLAG_CANNOT_DISABLE + EFFECT_FLAG_UNCOPYABLE)
+        e:SetRange(LOCATION_EXTRA)
+        e:SetCondition(function(e, c) return Duel.CheckLinkMaterial(c, nil, 2, 2) end)
+        e:SetTarget(function(e, tp, eg, ep, ev, re, r, rp, chk, c)
+            local g = Duel.SelectLinkMaterial(tp, c, nil, 2, 2)
+            if g:GetCount() == 0 then return false end
+            g:KeepAlive()
+            e:SetLabelObject(g)
+            return true
+        end)
+        e:SetOperation(function(e, tp, eg, ep, ev, re, r, rp, c)
+            local g = e:GetLabelObject()
+            c:SetMaterial(g)
+            Duel.SendtoGrave(g, REASON_MATERIAL + REASON_LINK)
+            g:DeleteGroup()
+        end)
+        e:SetValue(SUMMON_TYPE_LINK)
+        linker:RegisterEffect(e)
+    "#;
+
+    #[test]
+    fn link_procedure_summons_to_extra_monster_zone() {
+        let arrows = LINK_MARKER_BOTTOM_LEFT | LINK_MARKER_BOTTOM_RIGHT | LINK_MARKER_TOP;
+        let normal = CardType::MONSTER | CardType::NORMAL;
+        let cards = [link(9301, 2, arrows), card(9302, normal.clone(), 0), card(9303, normal, 0), link(9304, 1, LINK_MARKER_BOTTOM)];
+        let mut duel = duel_with(&cards, r#"
+            linker = Debug.AddCard(9301, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+            mat_a = Debug.AddCard(9302, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            mat_b = Debug.AddCard(9303, 0, 0, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)
+            second = Debug.AddCard(9304, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+        "#);
+        duel.lua.load(PROCEDURE).exec().expect("procedure");
+
+        duel.special_summon_rule(0, CardId::new(0));
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the materials are chosen by the player");
+        duel.set_responseb(&[1, 0]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "one link-1 material misses rating 2");
+        duel.set_responseb(&[2, 0, 1]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "either Extra Monster Zone will do");
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 5]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        {
+            let data = duel.data.lock().unwrap();
+            let linker = data.get_card(CardId::new(0)).unwrap();
+            assert_eq!((linker.location, linker.sequence), (Location::MZONE, 5), "no linked zones yet, so the EMZ");
+            assert_eq!(linker.summon_type(), SUMMON_TYPE_LINK);
+            assert_eq!(linker.materials.len(), 2);
+            assert_eq!(data.card_linked_zone(CardId::new(0)), 0b101 | 1 << (16 + 3));
+            // The EMZ is taken, so the next Link monster needs a linked main zone
+            assert_eq!(data.extra_summon_zones(0, Some(CardId::new(3)), &[]), vec![0, 2]);
+            // Player 1 sees the same zone as their seq 6
+            assert!(!data.is_location_useable(1, Location::MZONE, 6));
+            assert!(data.is_location_useable(1, Location::MZONE, 5));
+        }
+
+        let (zone, opp_zone, count, linked, arrow): (u32, u32, u32, u32, bool) = duel.lua.load(r#"
+            Debug.AddCard(9305, 0, 0, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)
+            Debug.AddCard(9306, 1, 1, LOCATION_MZONE, 3, POS_FACEUP_ATTACK)
+            return linker:GetLinkedZone(), linker:GetLinkedZone(1), linker:GetLinkedGroupCount(), Duel.GetLinkedZone(0),
+                linker:IsLinkMarker(LINK_MARKER_BOTTOM_LEFT + LINK_MARKER_TOP)
+        "#).eval().unwrap();
+        assert_eq!(zone, 0b101 | 1 << 19);
+        assert_eq!(opp_zone, 1 << 3 | 0b101 << 16);
+        assert_eq!(count, 2, "own zone 2 and the opponent's zone 3");
+        assert_eq!(linked, 0b101);
+        assert!(arrow);
+    }
+
+    #[test]
+    fn materials_that_leave_no_zone_are_asked_again() {
+        let mut cards = vec![link(9311, 2, LINK_MARKER_TOP)];
+        cards.extend((9312..9316).map(|code| card(code, CardType::MONSTER | CardType::NORMAL, 0)));
+        let mut duel = duel_with(&cards, r#"
+            linker = Debug.AddCard(9311, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+            Debug.AddCard(9312, 0, 0, LOCATION_MZONE, 5, POS_FACEUP_ATTACK)
+            Debug.AddCard(9313, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(9314, 0, 0, LOCATION_MZONE, 1, POS_FACEUP_ATTACK)
+            Debug.AddCard(9315, 1, 1, LOCATION_MZONE, 5, POS_FACEUP_ATTACK)
+        "#);
+        duel.lua.load(PROCEDURE).exec().expect("procedure");
+        duel.get_message();
+
+        duel.special_summon_rule(0, CardId::new(0));
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        // Both Extra Monster Zones are taken, so the materials have to free one: the candidates go
+        // by zone, and the two in the main zones come first
+        duel.get_message();
+        duel.set_responseb(&[2, 0, 1]);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        let messages = duel.get_message();
+        assert_eq!(messages[0], MsgType::Retry.id());
+        assert_eq!(messages[1], MsgType::SelectSum.id());
+        duel.set_responseb(&[2, 0, 2]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let data = duel.data.lock().unwrap();
+        let linker = data.get_card(CardId::new(0)).unwrap();
+        assert_eq!((linker.location, linker.sequence), (Location::MZONE, 5));
+    }
+
+    #[test]
+    fn extra_deck_zones_follow_master_rule() {
+        let cards = [card(9401, CardType::MONSTER | CardType::FUSION, 0), link(9402, 1, LINK_MARKER_TOP)];
+        let duel = duel_with(&cards, r#"
+            fusion = Debug.AddCard(9401, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+            link = Debug.AddCard(9402, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+            blocker = Debug.AddCard(9403, 1, 1, LOCATION_MZONE, 6, POS_FACEUP_ATTACK)
+        "#);
+        let mut data = duel.data.lock().unwrap();
+        let (fusion, link) = (Some(CardId::new(0)), Some(CardId::new(1)));
+
+        // Player 1's seq 6 is player 0's seq 5
+        assert!(!data.is_location_useable(0, Location::MZONE, 5));
+        assert_eq!(data.extra_summon_zones(0, fusion, &[]), vec![0, 1, 2, 3, 4, 6]);
+        assert_eq!(data.extra_summon_zones(0, link, &[]), vec![6]);
+        assert_eq!(data.extra_summon_zones(0, link, &[CardId::new(2)]), vec![5, 6], "the blocker leaving frees its EMZ");
+
+        data.duel_rule = 4;
+        assert_eq!(data.extra_summon_zones(0, fusion, &[]), vec![6]);
+        data.duel_rule = 3;
+        assert_eq!(data.extra_summon_zones(0, link, &[]), vec![0, 1, 2, 3, 4]);
+    }
+}
@@ -261,6 +261,108 @@ impl From<u8> for MsgType {
     }
 }
 
+impl MsgType {
+    /// Wire id of the message type
+    pub fn id(&self) -> u8 {
+        match self {
+            MsgType::Unknown(id) => *id,
+            MsgType::Retry => 1,
+            MsgType::Hint => 2,
+            MsgType::Waiting => 3,
+            MsgType::Start => 4,
+            MsgType::Win => 5,
+            MsgType::UpdateData => 6,
+            MsgType::UpdateCard => 7,
+            MsgType::RequestDeck => 8,
+            MsgType::SelectBattleCmd => 10,
+            MsgType::SelectIdleCmd => 11,
+            MsgType::SelectEffectYN => 12,
+            MsgType::SelectYesNo => 13,
+            MsgType::SelectOption => 14,
+            MsgType::SelectCard => 15,
+            MsgType::SelectChain => 16,
+            MsgType::SelectPlace => 18,
+            MsgType::SelectPosition => 19,
+            MsgType::SelectTribute => 20,
+            MsgType::SortChain => 21,
+            MsgType::SelectCounter => 22,
+            MsgType::SelectSum => 23,
+            MsgType::SelectDisField => 24,
+            MsgType::SortCard => 25,
+            MsgType::SelectUnselectCard => 26,
+            MsgType::ConfirmDeckTop => 30,
+            MsgType::ConfirmCards => 31,
+            MsgType::ShuffleDeck => 32,
+            MsgType::ShuffleHand => 33,
+            MsgType::RefreshDeck => 34,
+            MsgType::SwapGraveDeck => 35,
+            MsgType::ShuffleSetCard => 36,
+            MsgType::ReverseDeck => 37,
+            MsgType::DeckTop => 38,
+            MsgType::NewTurn => 40,
+            MsgType::NewPhase => 41,
+            MsgType::ConfirmExtraTop => 42,
+            MsgType::Move => 50,
+            MsgType::PosChange => 53,
+            MsgType::Set => 54,
+            MsgType::Swap => 55,
+            MsgType::FieldDisabled => 56,
+            MsgType::Summoning => 60,
+            MsgType::Summoned => 61,
+            MsgType::SPSummoning => 62,
+            MsgType::SPSummoned => 63,
+            MsgType::FlipSummoning => 64,
+            MsgType::FlipSummoned => 65,
+            MsgType::Chaining => 70,
+            MsgType::Chained => 71,
+            MsgType::ChainSolving => 72,
+            MsgType::ChainSolved => 73,
+            MsgType::ChainEnd => 74,
+            MsgType::ChainNegated => 75,
+            MsgType::ChainDisabled => 76,
+            MsgType::CardSelected => 80,
+            MsgType::RandomSelected => 81,
+            MsgType::BecomeTarget => 83,
+            MsgType::Draw => 90,
+            MsgType::Damage => 91,
+            MsgType::Recover => 92,
+            MsgType::Equip => 93,
+            MsgType::LpUpdate => 94,
+            MsgType::Unequip => 95,
+            MsgType::CardTarget => 96,
+            MsgType::CancelTarget => 97,
+            MsgType::PayLpCost => 100,
+            MsgType::AddCounter => 101,
+            MsgType::RemoveCounter => 102,
+            MsgType::Attack => 110,
+            MsgType::Battle => 111,
+            MsgType::AttackDisabled => 112,
+            MsgType::DamageStepStart => 113,
+            MsgType::DamageStepEnd => 114,
+            MsgType::MissedEffect => 120,
+            MsgType::BeChainTarget => 121,
+            MsgType::CreateRelation => 122,
+            MsgType::ReleaseRelation => 123,
+            MsgType::TossCoin => 130,
+            MsgType::TossDice => 131,
+            MsgType::RockPaperScissors => 132,
+            MsgType::HandRes => 133,
+            MsgType::AnnounceRace => 140,
+            MsgType::AnnounceAttrib => 141,
+            MsgType::AnnounceCard => 142,
+            MsgType::AnnounceNumber => 143,
+            MsgType::CardHint => 160,
+            MsgType::TagSwap => 161,
+            MsgType::ReloadField => 162,
+            MsgType::AiName => 163,
+            MsgType::ShowHint => 164,
+            MsgType::PlayerHint => 165,
+            MsgType::MatchKill => 170,
+            MsgType::CustomMsg => 180,
+        }
+    }
+}
+
 /// Parse a packet (first byte is message id), return the MsgType and the payload slice
 pub fn parse_packet(data: &[u8]) -> (MsgType, &[u8]) {
     if data.is_empty() { return (MsgType::Unknown(0), data); }
@@ -288,9 +390,49 @@ pub fn parse_replay_packet(data: &[u8]) -> (Option<u8>, MsgType, &[u8]) {
     }
 }
 
-// Payload parsers for some important message types
+// Payload parsers and encoders for the message types; `encode` returns the message id followed by the payload
 use std::io::Cursor;
 use byteorder::{LittleEndian, ReadBytesExt};
+
+/// Start an outgoing message buffer with its id byte
+fn begin(msg: MsgType) -> Vec<u8> {
+    vec![msg.id()]
+}
+
+/// Card location packed like ocgcore's get_info_location: controller, location, sequence, position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LocInfo { pub controller: u8, pub location: u8, pub sequence: u8, pub position: u8 }
+
+impl LocInfo {
+    pub fn parse(cursor: &mut Cursor<&[u8]>) -> Option<LocInfo> {
+        let controller = cursor.read_u8().ok()?;
+        let location = cursor.read_u8().ok()?;
+        let sequence = cursor.read_u8().ok()?;
+        let position = cursor.read_u8().ok()?;
+        Some(LocInfo { controller, location, sequence, position })
+    }
+
+    pub fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&[self.controller, self.location, self.sequence, self.position]);
+    }
+}
+
+/// Read a `u16 length, bytes, 0` string as used by MSG_AI_NAME and MSG_SHOW_HINT
+fn read_string(cursor: &mut Cursor<&[u8]>) -> Option<String> {
+    let len = cursor.read_u16::<LittleEndian>().ok()? as usize;
+    let start = cursor.position() as usize;
+    let bytes = cursor.get_ref().get(start..start + len)?;
+    cursor.set_position((start + len + 1) as u64);
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn write_string(buf: &mut Vec<u8>, text: &str) {
+    let bytes = text.as_bytes();
+    let len = bytes.len().min(u16::MAX as usize);
+    buf.extend_from_slice(&(len as u16).to_le_bytes());
+    buf.extend_from_slice(&bytes[..len]);
+    buf.push(0);
+}
 /// Start message payload
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MsgStart {
@@ -314,24 +456,80 @@ impl MsgStart {
         let e1 = cursor.read_u16::<LittleEndian>().ok()?;
         Some(MsgStart { player_type, duel_rule, lp: [lp0, lp1], deck_count: [d0, d1], extra_count: [e0, e1] })
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::Start);
+        buf.push(self.player_type);
+        buf.push(self.duel_rule);
+        for lp in self.lp { buf.extend_from_slice(&lp.to_le_bytes()); }
+        for c in self.deck_count { buf.extend_from_slice(&c.to_le_bytes()); }
+        for c in self.extra_count { buf.extend_from_slice(&c.to_le_bytes()); }
+        buf
+    }
 }
 
 /// New turn payload
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MsgNewTurn { pub player: u8 }
 
-impl MsgNewTurn { pub fn parse(payload: &[u8]) -> Option<MsgNewTurn> { Some(MsgNewTurn { player: Cursor::new(payload).read_u8().ok()? }) } }
+impl MsgNewTurn {
+    pub fn parse(payload: &[u8]) -> Option<MsgNewTurn> { Some(MsgNewTurn { player: Cursor::new(payload).read_u8().ok()? }) }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::NewTurn);
+        buf.push(self.player);
+        buf
+    }
+}
 
-/// Draw payload: player, count
+/// New phase payload: phase (u16)
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct MsgDraw { pub player: u8, pub count: u8 }
+pub struct MsgNewPhase { pub phase: u16 }
+
+impl MsgNewPhase {
+    pub fn parse(payload: &[u8]) -> Option<MsgNewPhase> { Some(MsgNewPhase { phase: Cursor::new(payload).read_u16::<LittleEndian>().ok()? }) }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::NewPhase);
+        buf.extend_from_slice(&self.phase.to_le_bytes());
+        buf
+    }
+}
+
+/// Draw payload: player, count, then the drawn codes (bit 31 set when drawn face-up)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgDraw { pub player: u8, pub count: u8, pub codes: Vec<u32> }
 
 impl MsgDraw {
     pub fn parse(payload: &[u8]) -> Option<MsgDraw> {
         let mut cursor = Cursor::new(payload);
         let player = cursor.read_u8().ok()?;
         let count = cursor.read_u8().ok()?;
-        Some(MsgDraw { player, count })
+        // Older captures only carry player and count
+        let codes = (0..count).map_while(|_| cursor.read_u32::<LittleEndian>().ok()).collect();
+        Some(MsgDraw { player, count, codes })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::Draw);
+        buf.push(self.player);
+        buf.push(self.count);
+        for code in self.codes.iter() { buf.extend_from_slice(&code.to_le_bytes()); }
+        buf
+    }
+}
+
+/// Shuffle deck payload: player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgShuffleDeck { pub player: u8 }
+
+impl MsgShuffleDeck {
+    pub fn parse(payload: &[u8]) -> Option<MsgShuffleDeck> { Some(MsgShuffleDeck { player: Cursor::new(payload).read_u8().ok()? }) }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::ShuffleDeck);
+        buf.push(self.player);
+        buf
     }
 }
 
@@ -346,6 +544,13 @@ impl MsgLpUpdate {
         let lp = cursor.read_u32::<LittleEndian>().ok()?;
         Some(MsgLpUpdate { player, lp })
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::LpUpdate);
+        buf.push(self.player);
+        buf.extend_from_slice(&self.lp.to_le_bytes());
+        buf
+    }
 }
 
 /// Move payload: code, from player, from loc, from seq, from pos, to player, to loc, to seq, to pos, reason
@@ -378,6 +583,15 @@ impl MsgMove {
         let reason = cursor.read_i32::<LittleEndian>().ok()?;
         Some(MsgMove { code, from_player, from_loc, from_seq, from_pos, to_player, to_loc, to_seq, to_pos, reason })
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::Move);
+        buf.extend_from_slice(&self.code.to_le_bytes());
+        buf.extend_from_slice(&[self.from_player, self.from_loc, self.from_seq, self.from_pos]);
+        buf.extend_from_slice(&[self.to_player, self.to_loc, self.to_seq, self.to_pos]);
+        buf.extend_from_slice(&self.reason.to_le_bytes());
+        buf
+    }
 }
 
 /// Summon payload: code, player, loc, seq, pos
@@ -394,6 +608,15 @@ impl MsgSummoning { pub fn parse(payload: &[u8]) -> Option<MsgSummoning> {
     let attack = if (cursor.position() as usize) + 4 <= payload.len() { Some(cursor.read_i32::<LittleEndian>().ok()?) } else { None };
     let level = if (cursor.position() as usize) + 1 <= payload.len() { Some(cursor.read_u8().ok()?) } else { None };
     Some(MsgSummoning { code, player, loc, seq, pos, attack, level })
+}
+
+pub fn encode(&self) -> Vec<u8> {
+    let mut buf = begin(MsgType::Summoning);
+    buf.extend_from_slice(&self.code.to_le_bytes());
+    buf.extend_from_slice(&[self.player, self.loc, self.seq, self.pos]);
+    if let Some(attack) = self.attack { buf.extend_from_slice(&attack.to_le_bytes()); }
+    if let Some(level) = self.level { buf.push(level); }
+    buf
 }}
 
 /// Special Summon payload: same as summoning
@@ -410,8 +633,31 @@ impl MsgSpSummoning { pub fn parse(payload: &[u8]) -> Option<MsgSpSummoning> {
     let attack = if (cursor.position() as usize) + 4 <= payload.len() { Some(cursor.read_i32::<LittleEndian>().ok()?) } else { None };
     let level = if (cursor.position() as usize) + 1 <= payload.len() { Some(cursor.read_u8().ok()?) } else { None };
     Some(MsgSpSummoning { code, player, loc, seq, pos, attack, level })
+}
+
+pub fn encode(&self) -> Vec<u8> {
+    let mut buf = begin(MsgType::SPSummoning);
+    buf.extend_from_slice(&self.code.to_le_bytes());
+    buf.extend_from_slice(&[self.player, self.loc, self.seq, self.pos]);
+    if let Some(attack) = self.attack { buf.extend_from_slice(&attack.to_le_bytes()); }
+    if let Some(level) = self.level { buf.push(level); }
+    buf
 }}
 
+/// Special summoned message: no payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSpSummoned;
+
+impl MsgSpSummoned {
+    pub fn parse(_payload: &[u8]) -> Option<MsgSpSummoned> {
+        Some(MsgSpSummoned)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        begin(MsgType::SPSummoned)
+    }
+}
+
 /// Chaining payload: code, src_player, src_loc, src_seq, src_sub, target_player, target_loc, target_seq, desc, type
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MsgChaining {
@@ -442,6 +688,702 @@ impl MsgChaining {
         let ctype = cursor.read_u8().ok()?;
         Some(MsgChaining { code, src_player, src_loc, src_seq, src_sub, trg_player, trg_loc, trg_seq, desc, ctype })
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::Chaining);
+        buf.extend_from_slice(&self.code.to_le_bytes());
+        buf.extend_from_slice(&[self.src_player, self.src_loc, self.src_seq, self.src_sub]);
+        buf.extend_from_slice(&[self.trg_player, self.trg_loc, self.trg_seq]);
+        buf.extend_from_slice(&self.desc.to_le_bytes());
+        buf.push(self.ctype);
+        buf
+    }
+}
+
+/// Chain solving / solved payload: chain link number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgChainSolving { pub count: u8 }
+
+impl MsgChainSolving {
+    pub fn parse(payload: &[u8]) -> Option<MsgChainSolving> { Some(MsgChainSolving { count: Cursor::new(payload).read_u8().ok()? }) }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![MsgType::ChainSolving.id(), self.count]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgChainSolved { pub count: u8 }
+
+impl MsgChainSolved {
+    pub fn parse(payload: &[u8]) -> Option<MsgChainSolved> { Some(MsgChainSolved { count: Cursor::new(payload).read_u8().ok()? }) }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![MsgType::ChainSolved.id(), self.count]
+    }
+}
+
+/// Chain end message: no payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgChainEnd;
+
+impl MsgChainEnd {
+    pub fn parse(_payload: &[u8]) -> Option<MsgChainEnd> {
+        Some(MsgChainEnd)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        begin(MsgType::ChainEnd)
+    }
+}
+
+/// One activatable effect offered by MSG_SELECT_CHAIN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainCandidate { pub edesc: u8, pub forced: u8, pub code: u32, pub location: LocInfo, pub desc: u32 }
+
+/// Select chain payload: player, count, special count, forced, hint timings, then each candidate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSelectChain {
+    pub player: u8,
+    pub spe_count: u8,
+    pub forced: u8,
+    pub hint_timing: [u32; 2],
+    pub chains: Vec<ChainCandidate>,
+}
+
+impl MsgSelectChain {
+    pub fn parse(payload: &[u8]) -> Option<MsgSelectChain> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let count = cursor.read_u8().ok()?;
+        let spe_count = cursor.read_u8().ok()?;
+        let forced = cursor.read_u8().ok()?;
+        let t0 = cursor.read_u32::<LittleEndian>().ok()?;
+        let t1 = cursor.read_u32::<LittleEndian>().ok()?;
+        let mut chains = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let edesc = cursor.read_u8().ok()?;
+            let forced = cursor.read_u8().ok()?;
+            let code = cursor.read_u32::<LittleEndian>().ok()?;
+            let location = LocInfo::parse(&mut cursor)?;
+            let desc = cursor.read_u32::<LittleEndian>().ok()?;
+            chains.push(ChainCandidate { edesc, forced, code, location, desc });
+        }
+        Some(MsgSelectChain { player, spe_count, forced, hint_timing: [t0, t1], chains })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::SelectChain);
+        buf.extend_from_slice(&[self.player, self.chains.len() as u8, self.spe_count, self.forced]);
+        for t in self.hint_timing { buf.extend_from_slice(&t.to_le_bytes()); }
+        for c in self.chains.iter() {
+            buf.push(c.edesc);
+            buf.push(c.forced);
+            buf.extend_from_slice(&c.code.to_le_bytes());
+            c.location.write(&mut buf);
+            buf.extend_from_slice(&c.desc.to_le_bytes());
+        }
+        buf
+    }
+}
+
+/// One card offered by a card selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardCandidate { pub code: u32, pub location: LocInfo }
+
+/// Select card payload: player, cancelable, min, max, count, then each candidate's code and location
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSelectCard {
+    pub player: u8,
+    pub cancelable: bool,
+    pub min: u8,
+    pub max: u8,
+    pub cards: Vec<CardCandidate>,
+}
+
+impl MsgSelectCard {
+    pub fn parse(payload: &[u8]) -> Option<MsgSelectCard> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let cancelable = cursor.read_u8().ok()? != 0;
+        let min = cursor.read_u8().ok()?;
+        let max = cursor.read_u8().ok()?;
+        let count = cursor.read_u8().ok()?;
+        let mut cards = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let code = cursor.read_u32::<LittleEndian>().ok()?;
+            let location = LocInfo::parse(&mut cursor)?;
+            cards.push(CardCandidate { code, location });
+        }
+        Some(MsgSelectCard { player, cancelable, min, max, cards })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::SelectCard);
+        buf.extend_from_slice(&[self.player, self.cancelable as u8, self.min, self.max, self.cards.len() as u8]);
+        for c in self.cards.iter() {
+            buf.extend_from_slice(&c.code.to_le_bytes());
+            c.location.write(&mut buf);
+        }
+        buf
+    }
+}
+
+/// One card offered by MSG_SELECT_SUM with its sum parameter (two alternatives packed in the low and high 16 bits)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SumCandidate { pub code: u32, pub controller: u8, pub location: u8, pub sequence: u8, pub param: u32 }
+
+impl SumCandidate {
+    fn parse(cursor: &mut Cursor<&[u8]>) -> Option<SumCandidate> {
+        let code = cursor.read_u32::<LittleEndian>().ok()?;
+        let controller = cursor.read_u8().ok()?;
+        let location = cursor.read_u8().ok()?;
+        let sequence = cursor.read_u8().ok()?;
+        let param = cursor.read_u32::<LittleEndian>().ok()?;
+        Some(SumCandidate { code, controller, location, sequence, param })
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.code.to_le_bytes());
+        buf.extend_from_slice(&[self.controller, self.location, self.sequence]);
+        buf.extend_from_slice(&self.param.to_le_bytes());
+    }
+}
+
+/// Select sum payload: mode (0 exact, 1 at least), player, acc, min, max, then the forced and the selectable cards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSelectSum {
+    pub greater: bool,
+    pub player: u8,
+    pub acc: u32,
+    pub min: u8,
+    pub max: u8,
+    pub must: Vec<SumCandidate>,
+    pub cards: Vec<SumCandidate>,
+}
+
+impl MsgSelectSum {
+    pub fn parse(payload: &[u8]) -> Option<MsgSelectSum> {
+        let mut cursor = Cursor::new(payload);
+        let greater = cursor.read_u8().ok()? != 0;
+        let player = cursor.read_u8().ok()?;
+        let acc = cursor.read_u32::<LittleEndian>().ok()?;
+        let min = cursor.read_u8().ok()?;
+        let max = cursor.read_u8().ok()?;
+        let must_count = cursor.read_u8().ok()?;
+        let must = (0..must_count).map(|_| SumCandidate::parse(&mut cursor)).collect::<Option<Vec<_>>>()?;
+        let count = cursor.read_u8().ok()?;
+        let cards = (0..count).map(|_| SumCandidate::parse(&mut cursor)).collect::<Option<Vec<_>>>()?;
+        Some(MsgSelectSum { greater, player, acc, min, max, must, cards })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::SelectSum);
+        buf.extend_from_slice(&[self.greater as u8, self.player]);
+        buf.extend_from_slice(&self.acc.to_le_bytes());
+        buf.extend_from_slice(&[self.min, self.max, self.must.len() as u8]);
+        for c in self.must.iter() {
+            c.write(&mut buf);
+        }
+        buf.push(self.cards.len() as u8);
+        for c in self.cards.iter() {
+            c.write(&mut buf);
+        }
+        buf
+    }
+}
+
+/// Select place payload: player, count, then the mask of zones that cannot be chosen
+/// (own monster zones in bits 0..7 and spell & trap zones in bits 8..15, the opponent's 16 bits higher)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSelectPlace { pub player: u8, pub count: u8, pub flag: u32 }
+
+impl MsgSelectPlace {
+    pub fn parse(payload: &[u8]) -> Option<MsgSelectPlace> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let count = cursor.read_u8().ok()?;
+        let flag = cursor.read_u32::<LittleEndian>().ok()?;
+        Some(MsgSelectPlace { player, count, flag })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(MsgType::SelectPlace)
+    }
+
+    /// MSG_SELECT_DISFIELD shares the layout of MSG_SELECT_PLACE.
+    pub fn encode_as(&self, ty: MsgType) -> Vec<u8> {
+        let mut buf = begin(ty);
+        buf.extend_from_slice(&[self.player, self.count]);
+        buf.extend_from_slice(&self.flag.to_le_bytes());
+        buf
+    }
+}
+
+/// Select position payload: player, code of the card, then the positions it may take
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSelectPosition { pub player: u8, pub code: u32, pub positions: u8 }
+
+impl MsgSelectPosition {
+    pub fn parse(payload: &[u8]) -> Option<MsgSelectPosition> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let code = cursor.read_u32::<LittleEndian>().ok()?;
+        let positions = cursor.read_u8().ok()?;
+        Some(MsgSelectPosition { player, code, positions })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::SelectPosition);
+        buf.push(self.player);
+        buf.extend_from_slice(&self.code.to_le_bytes());
+        buf.push(self.positions);
+        buf
+    }
+}
+
+/// Field disabled payload: disabled zones of player 0 in the low 16 bits, player 1 in the high ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgFieldDisabled { pub flag: u32 }
+
+impl MsgFieldDisabled {
+    pub fn parse(payload: &[u8]) -> Option<MsgFieldDisabled> {
+        Some(MsgFieldDisabled { flag: Cursor::new(payload).read_u32::<LittleEndian>().ok()? })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::FieldDisabled);
+        buf.extend_from_slice(&self.flag.to_le_bytes());
+        buf
+    }
+}
+
+/// Equip payload: the equip card's location, then its target's
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgEquip { pub card: LocInfo, pub target: LocInfo }
+
+impl MsgEquip {
+    pub fn parse(payload: &[u8]) -> Option<MsgEquip> {
+        let mut cursor = Cursor::new(payload);
+        let card = LocInfo::parse(&mut cursor)?;
+        let target = LocInfo::parse(&mut cursor)?;
+        Some(MsgEquip { card, target })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::Equip);
+        self.card.write(&mut buf);
+        self.target.write(&mut buf);
+        buf
+    }
+}
+
+/// Unequip payload: the location of the equip card that lost its target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgUnequip { pub card: LocInfo }
+
+impl MsgUnequip {
+    pub fn parse(payload: &[u8]) -> Option<MsgUnequip> {
+        Some(MsgUnequip { card: LocInfo::parse(&mut Cursor::new(payload))? })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::Unequip);
+        self.card.write(&mut buf);
+        buf
+    }
+}
+
+/// Card target payload: the location of the card that keeps targeting, then its target's
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgCardTarget { pub card: LocInfo, pub target: LocInfo }
+
+impl MsgCardTarget {
+    pub fn parse(payload: &[u8]) -> Option<MsgCardTarget> {
+        let mut cursor = Cursor::new(payload);
+        let card = LocInfo::parse(&mut cursor)?;
+        let target = LocInfo::parse(&mut cursor)?;
+        Some(MsgCardTarget { card, target })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(MsgType::CardTarget)
+    }
+
+    /// MSG_CANCEL_TARGET shares the layout of MSG_CARD_TARGET.
+    pub fn encode_as(&self, ty: MsgType) -> Vec<u8> {
+        let mut buf = begin(ty);
+        self.card.write(&mut buf);
+        self.target.write(&mut buf);
+        buf
+    }
+}
+
+/// Add counter payload: counter type, the card's controller, location and sequence, then the count
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgAddCounter { pub counter_type: u16, pub controller: u8, pub location: u8, pub sequence: u8, pub count: u16 }
+
+impl MsgAddCounter {
+    pub fn parse(payload: &[u8]) -> Option<MsgAddCounter> {
+        let mut cursor = Cursor::new(payload);
+        let counter_type = cursor.read_u16::<LittleEndian>().ok()?;
+        let controller = cursor.read_u8().ok()?;
+        let location = cursor.read_u8().ok()?;
+        let sequence = cursor.read_u8().ok()?;
+        let count = cursor.read_u16::<LittleEndian>().ok()?;
+        Some(MsgAddCounter { counter_type, controller, location, sequence, count })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(MsgType::AddCounter)
+    }
+
+    /// MSG_REMOVE_COUNTER shares the layout of MSG_ADD_COUNTER.
+    pub fn encode_as(&self, ty: MsgType) -> Vec<u8> {
+        let mut buf = begin(ty);
+        buf.extend_from_slice(&self.counter_type.to_le_bytes());
+        buf.extend_from_slice(&[self.controller, self.location, self.sequence]);
+        buf.extend_from_slice(&self.count.to_le_bytes());
+        buf
+    }
+}
+
+/// One card offered by MSG_SELECT_COUNTER with the number of counters it holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CounterCandidate { pub code: u32, pub controller: u8, pub location: u8, pub sequence: u8, pub count: u16 }
+
+/// Select counter payload: player, counter type, how many to remove, then the cards holding them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSelectCounter { pub player: u8, pub counter_type: u16, pub count: u16, pub cards: Vec<CounterCandidate> }
+
+impl MsgSelectCounter {
+    pub fn parse(payload: &[u8]) -> Option<MsgSelectCounter> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let counter_type = cursor.read_u16::<LittleEndian>().ok()?;
+        let count = cursor.read_u16::<LittleEndian>().ok()?;
+        let size = cursor.read_u8().ok()?;
+        let mut cards = Vec::with_capacity(size as usize);
+        for _ in 0..size {
+            let code = cursor.read_u32::<LittleEndian>().ok()?;
+            let controller = cursor.read_u8().ok()?;
+            let location = cursor.read_u8().ok()?;
+            let sequence = cursor.read_u8().ok()?;
+            let count = cursor.read_u16::<LittleEndian>().ok()?;
+            cards.push(CounterCandidate { code, controller, location, sequence, count });
+        }
+        Some(MsgSelectCounter { player, counter_type, count, cards })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::SelectCounter);
+        buf.push(self.player);
+        buf.extend_from_slice(&self.counter_type.to_le_bytes());
+        buf.extend_from_slice(&self.count.to_le_bytes());
+        buf.push(self.cards.len() as u8);
+        for c in self.cards.iter() {
+            buf.extend_from_slice(&c.code.to_le_bytes());
+            buf.extend_from_slice(&[c.controller, c.location, c.sequence]);
+            buf.extend_from_slice(&c.count.to_le_bytes());
+        }
+        buf
+    }
+}
+
+/// Swap payload: code and location of the first card, then of the second, after they changed places
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSwap { pub code1: u32, pub location1: LocInfo, pub code2: u32, pub location2: LocInfo }
+
+impl MsgSwap {
+    pub fn parse(payload: &[u8]) -> Option<MsgSwap> {
+        let mut cursor = Cursor::new(payload);
+        let code1 = cursor.read_u32::<LittleEndian>().ok()?;
+        let location1 = LocInfo::parse(&mut cursor)?;
+        let code2 = cursor.read_u32::<LittleEndian>().ok()?;
+        let location2 = LocInfo::parse(&mut cursor)?;
+        Some(MsgSwap { code1, location1, code2, location2 })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::Swap);
+        buf.extend_from_slice(&self.code1.to_le_bytes());
+        self.location1.write(&mut buf);
+        buf.extend_from_slice(&self.code2.to_le_bytes());
+        self.location2.write(&mut buf);
+        buf
+    }
+}
+
+/// A card shown by MSG_CONFIRM_CARDS or MSG_CONFIRM_DECKTOP: code, controller, location, sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfirmedCard { pub code: u32, pub controller: u8, pub location: u8, pub sequence: u8 }
+
+/// Confirm cards payload: the player the cards are shown to, count, then each card
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgConfirmCards { pub player: u8, pub cards: Vec<ConfirmedCard> }
+
+impl MsgConfirmCards {
+    pub fn parse(payload: &[u8]) -> Option<MsgConfirmCards> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let count = cursor.read_u8().ok()?;
+        let cards = (0..count).map(|_| {
+            let code = cursor.read_u32::<LittleEndian>().ok()?;
+            let controller = cursor.read_u8().ok()?;
+            let location = cursor.read_u8().ok()?;
+            let sequence = cursor.read_u8().ok()?;
+            Some(ConfirmedCard { code, controller, location, sequence })
+        }).collect::<Option<Vec<_>>>()?;
+        Some(MsgConfirmCards { player, cards })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(MsgType::ConfirmCards)
+    }
+
+    /// MSG_CONFIRM_DECKTOP shares the layout, with the Deck's owner as player and the top card first,
+    /// as does MSG_SORT_CARD with the sorting player.
+    pub fn encode_as(&self, ty: MsgType) -> Vec<u8> {
+        let mut buf = begin(ty);
+        buf.extend_from_slice(&[self.player, self.cards.len() as u8]);
+        for card in self.cards.iter() {
+            buf.extend_from_slice(&card.code.to_le_bytes());
+            buf.extend_from_slice(&[card.controller, card.location, card.sequence]);
+        }
+        buf
+    }
+}
+
+/// Shuffle hand payload: player, count, then the codes in their new order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgShuffleHand { pub player: u8, pub codes: Vec<u32> }
+
+impl MsgShuffleHand {
+    pub fn parse(payload: &[u8]) -> Option<MsgShuffleHand> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let count = cursor.read_u8().ok()?;
+        let codes = (0..count).map(|_| cursor.read_u32::<LittleEndian>().ok()).collect::<Option<Vec<_>>>()?;
+        Some(MsgShuffleHand { player, codes })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::ShuffleHand);
+        buf.extend_from_slice(&[self.player, self.codes.len() as u8]);
+        for code in self.codes.iter() { buf.extend_from_slice(&code.to_le_bytes()); }
+        buf
+    }
+}
+
+/// Shuffle set card payload: location, count, the cards' locations before the shuffle, then after it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgShuffleSetCard { pub location: u8, pub before: Vec<LocInfo>, pub after: Vec<LocInfo> }
+
+impl MsgShuffleSetCard {
+    pub fn parse(payload: &[u8]) -> Option<MsgShuffleSetCard> {
+        let mut cursor = Cursor::new(payload);
+        let location = cursor.read_u8().ok()?;
+        let count = cursor.read_u8().ok()?;
+        let before = (0..count).map(|_| LocInfo::parse(&mut cursor)).collect::<Option<Vec<_>>>()?;
+        let after = (0..count).map(|_| LocInfo::parse(&mut cursor)).collect::<Option<Vec<_>>>()?;
+        Some(MsgShuffleSetCard { location, before, after })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::ShuffleSetCard);
+        buf.extend_from_slice(&[self.location, self.before.len() as u8]);
+        for info in self.before.iter().chain(self.after.iter()) { info.write(&mut buf); }
+        buf
+    }
+}
+
+/// Deck top payload: player, how far from the top, then the code with the high bit set when it is face-up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgDeckTop { pub player: u8, pub sequence: u8, pub code: u32 }
+
+impl MsgDeckTop {
+    pub fn parse(payload: &[u8]) -> Option<MsgDeckTop> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let sequence = cursor.read_u8().ok()?;
+        let code = cursor.read_u32::<LittleEndian>().ok()?;
+        Some(MsgDeckTop { player, sequence, code })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::DeckTop);
+        buf.extend_from_slice(&[self.player, self.sequence]);
+        buf.extend_from_slice(&self.code.to_le_bytes());
+        buf
+    }
+}
+
+/// Swap grave and deck payload: the player whose Deck and GY traded places
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSwapGraveDeck { pub player: u8 }
+
+impl MsgSwapGraveDeck {
+    pub fn parse(payload: &[u8]) -> Option<MsgSwapGraveDeck> { Some(MsgSwapGraveDeck { player: Cursor::new(payload).read_u8().ok()? }) }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![MsgType::SwapGraveDeck.id(), self.player]
+    }
+}
+
+/// Reverse deck message: no payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgReverseDeck;
+
+impl MsgReverseDeck {
+    pub fn parse(_payload: &[u8]) -> Option<MsgReverseDeck> {
+        Some(MsgReverseDeck)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        begin(MsgType::ReverseDeck)
+    }
+}
+
+/// Tag swap payload: player, main/extra/face-up extra/hand counts, the Deck top code (0 unless it
+/// is face-up), then the hand and Extra Deck codes with the high bit set for face-up cards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgTagSwap { pub player: u8, pub main_count: u8, pub extra_p_count: u8, pub top_code: u32, pub hand: Vec<u32>, pub extra: Vec<u32> }
+
+impl MsgTagSwap {
+    pub fn parse(payload: &[u8]) -> Option<MsgTagSwap> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let main_count = cursor.read_u8().ok()?;
+        let extra_count = cursor.read_u8().ok()?;
+        let extra_p_count = cursor.read_u8().ok()?;
+        let hand_count = cursor.read_u8().ok()?;
+        let top_code = cursor.read_u32::<LittleEndian>().ok()?;
+        let hand = (0..hand_count).map(|_| cursor.read_u32::<LittleEndian>().ok()).collect::<Option<Vec<_>>>()?;
+        let extra = (0..extra_count).map(|_| cursor.read_u32::<LittleEndian>().ok()).collect::<Option<Vec<_>>>()?;
+        Some(MsgTagSwap { player, main_count, extra_p_count, top_code, hand, extra })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::TagSwap);
+        buf.extend_from_slice(&[self.player, self.main_count, self.extra.len() as u8, self.extra_p_count, self.hand.len() as u8]);
+        buf.extend_from_slice(&self.top_code.to_le_bytes());
+        for code in self.hand.iter().chain(self.extra.iter()) {
+            buf.extend_from_slice(&code.to_le_bytes());
+        }
+        buf
+    }
+}
+
+/// Select option payload: player, count, then one u32 per option (string ids, or numbers and opcodes)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSelectOption { pub player: u8, pub options: Vec<u32> }
+
+impl MsgSelectOption {
+    pub fn parse(payload: &[u8]) -> Option<MsgSelectOption> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let count = cursor.read_u8().ok()?;
+        let options = (0..count).map(|_| cursor.read_u32::<LittleEndian>().ok()).collect::<Option<Vec<_>>>()?;
+        Some(MsgSelectOption { player, options })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(MsgType::SelectOption)
+    }
+
+    /// MSG_ANNOUNCE_CARD (filter opcodes) and MSG_ANNOUNCE_NUMBER (the numbers) share this layout.
+    pub fn encode_as(&self, ty: MsgType) -> Vec<u8> {
+        let mut buf = begin(ty);
+        buf.extend_from_slice(&[self.player, self.options.len() as u8]);
+        for option in &self.options {
+            buf.extend_from_slice(&option.to_le_bytes());
+        }
+        buf
+    }
+}
+
+/// Announce race payload: player, how many to announce, the announceable races
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgAnnounceRace { pub player: u8, pub count: u8, pub available: u32 }
+
+impl MsgAnnounceRace {
+    pub fn parse(payload: &[u8]) -> Option<MsgAnnounceRace> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let count = cursor.read_u8().ok()?;
+        let available = cursor.read_u32::<LittleEndian>().ok()?;
+        Some(MsgAnnounceRace { player, count, available })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(MsgType::AnnounceRace)
+    }
+
+    /// MSG_ANNOUNCE_ATTRIB shares the layout of MSG_ANNOUNCE_RACE.
+    pub fn encode_as(&self, ty: MsgType) -> Vec<u8> {
+        let mut buf = begin(ty);
+        buf.extend_from_slice(&[self.player, self.count]);
+        buf.extend_from_slice(&self.available.to_le_bytes());
+        buf
+    }
+}
+
+/// Toss coin payload: player, count, then one result per coin (1 for heads)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgTossCoin { pub player: u8, pub results: Vec<u8> }
+
+impl MsgTossCoin {
+    pub fn parse(payload: &[u8]) -> Option<MsgTossCoin> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        let count = cursor.read_u8().ok()?;
+        let results = (0..count).map(|_| cursor.read_u8().ok()).collect::<Option<Vec<_>>>()?;
+        Some(MsgTossCoin { player, results })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(MsgType::TossCoin)
+    }
+
+    /// MSG_TOSS_DICE shares the layout of MSG_TOSS_COIN, with one die face per result.
+    pub fn encode_as(&self, ty: MsgType) -> Vec<u8> {
+        let mut buf = begin(ty);
+        buf.extend_from_slice(&[self.player, self.results.len() as u8]);
+        buf.extend_from_slice(&self.results);
+        buf
+    }
+}
+
+/// Rock paper scissors payload: the player asked for a hand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgRockPaperScissors { pub player: u8 }
+
+impl MsgRockPaperScissors {
+    pub fn parse(payload: &[u8]) -> Option<MsgRockPaperScissors> {
+        let mut cursor = Cursor::new(payload);
+        let player = cursor.read_u8().ok()?;
+        Some(MsgRockPaperScissors { player })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![MsgType::RockPaperScissors.id(), self.player]
+    }
+}
+
+/// Hand result payload: both hands packed as hand0 + (hand1 << 2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgHandRes { pub hands: [u8; 2] }
+
+impl MsgHandRes {
+    pub fn parse(payload: &[u8]) -> Option<MsgHandRes> {
+        let mut cursor = Cursor::new(payload);
+        let packed = cursor.read_u8().ok()?;
+        Some(MsgHandRes { hands: [packed & 0x3, (packed >> 2) & 0x3] })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![MsgType::HandRes.id(), self.hands[0] + (self.hands[1] << 2)]
+    }
 }
 
 /// Retry message: no payload
@@ -452,6 +1394,10 @@ impl MsgRetry {
     pub fn parse(_payload: &[u8]) -> Option<MsgRetry> {
         Some(MsgRetry)
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        begin(MsgType::Retry)
+    }
 }
 
 /// Win message: player, reason
@@ -465,6 +1411,10 @@ impl MsgWin {
         let reason = cursor.read_u8().ok()?;
         Some(MsgWin { player, reason })
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![MsgType::Win.id(), self.player, self.reason]
+    }
 }
 
 /// Hint message: type, player, data
@@ -479,6 +1429,14 @@ impl MsgHint {
         let data = cursor.read_i32::<LittleEndian>().ok()?;
         Some(MsgHint { hint_type, player, data })
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::Hint);
+        buf.push(self.hint_type);
+        buf.push(self.player);
+        buf.extend_from_slice(&self.data.to_le_bytes());
+        buf
+    }
 }
 
 /// Waiting message: no payload
@@ -489,6 +1447,10 @@ impl MsgWaiting {
     pub fn parse(_payload: &[u8]) -> Option<MsgWaiting> {
         Some(MsgWaiting)
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        begin(MsgType::Waiting)
+    }
 }
 
 /// Update Data message: flag
@@ -501,6 +1463,10 @@ impl MsgUpdateData {
         let flag = cursor.read_u8().ok()?;
         Some(MsgUpdateData { flag })
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![MsgType::UpdateData.id(), self.flag]
+    }
 }
 
 /// Update Card message: flag, code
@@ -514,6 +1480,13 @@ impl MsgUpdateCard {
         let code = cursor.read_i32::<LittleEndian>().ok()? as u32;
         Some(MsgUpdateCard { flag, code })
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::UpdateCard);
+        buf.push(self.flag);
+        buf.extend_from_slice(&self.code.to_le_bytes());
+        buf
+    }
 }
 
 /// Request Deck message: no payload
@@ -524,23 +1497,143 @@ impl MsgRequestDeck {
     pub fn parse(_payload: &[u8]) -> Option<MsgRequestDeck> {
         Some(MsgRequestDeck)
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        begin(MsgType::RequestDeck)
+    }
 }
 
-/// Show Hint message: string
+/// Show Hint message: u16 length, message bytes, NUL
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MsgShowHint { pub message: String }
 
 impl MsgShowHint {
     pub fn parse(payload: &[u8]) -> Option<MsgShowHint> {
-        // Convert UTF-16 string to Rust String
-        let utf16_bytes: Vec<u16> = payload
-            .chunks_exact(2)
-            .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
-            .take_while(|&c| c != 0) // Stop at null terminator
-            .collect();
-        let message = String::from_utf16_lossy(&utf16_bytes);
+        let message = read_string(&mut Cursor::new(payload))?;
         Some(MsgShowHint { message })
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::ShowHint);
+        write_string(&mut buf, &self.message);
+        buf
+    }
+}
+
+/// AI name message: u16 length, name bytes, NUL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgAiName { pub name: String }
+
+impl MsgAiName {
+    pub fn parse(payload: &[u8]) -> Option<MsgAiName> {
+        let name = read_string(&mut Cursor::new(payload))?;
+        Some(MsgAiName { name })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::AiName);
+        write_string(&mut buf, &self.name);
+        buf
+    }
+}
+
+/// Per-player part of MSG_RELOAD_FIELD
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReloadFieldPlayer {
+    pub lp: u32,
+    /// (position, overlay count) of each occupied monster zone
+    pub mzone: [Option<(u8, u8)>; 7],
+    /// position of each occupied spell & trap zone
+    pub szone: [Option<u8>; 8],
+    pub deck: u8,
+    pub hand: u8,
+    pub grave: u8,
+    pub remove: u8,
+    pub extra: u8,
+    pub extra_p: u8,
+}
+
+/// A chain link in MSG_RELOAD_FIELD
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadFieldChain {
+    pub code: u32,
+    pub location: LocInfo,
+    pub trigger_controller: u8,
+    pub trigger_location: u8,
+    pub trigger_sequence: u8,
+    pub desc: u32,
+}
+
+/// Reload field message: duel rule, both players' field state, current chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgReloadField { pub duel_rule: u8, pub players: [ReloadFieldPlayer; 2], pub chains: Vec<ReloadFieldChain> }
+
+impl MsgReloadField {
+    pub fn parse(payload: &[u8]) -> Option<MsgReloadField> {
+        let mut cursor = Cursor::new(payload);
+        let duel_rule = cursor.read_u8().ok()?;
+        let mut players: [ReloadFieldPlayer; 2] = Default::default();
+        for player in players.iter_mut() {
+            player.lp = cursor.read_u32::<LittleEndian>().ok()?;
+            for zone in player.mzone.iter_mut() {
+                if cursor.read_u8().ok()? != 0 {
+                    *zone = Some((cursor.read_u8().ok()?, cursor.read_u8().ok()?));
+                }
+            }
+            for zone in player.szone.iter_mut() {
+                if cursor.read_u8().ok()? != 0 {
+                    *zone = Some(cursor.read_u8().ok()?);
+                }
+            }
+            player.deck = cursor.read_u8().ok()?;
+            player.hand = cursor.read_u8().ok()?;
+            player.grave = cursor.read_u8().ok()?;
+            player.remove = cursor.read_u8().ok()?;
+            player.extra = cursor.read_u8().ok()?;
+            player.extra_p = cursor.read_u8().ok()?;
+        }
+        let count = cursor.read_u8().ok()?;
+        let mut chains = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let code = cursor.read_u32::<LittleEndian>().ok()?;
+            let location = LocInfo::parse(&mut cursor)?;
+            let trigger_controller = cursor.read_u8().ok()?;
+            let trigger_location = cursor.read_u8().ok()?;
+            let trigger_sequence = cursor.read_u8().ok()?;
+            let desc = cursor.read_u32::<LittleEndian>().ok()?;
+            chains.push(ReloadFieldChain { code, location, trigger_controller, trigger_location, trigger_sequence, desc });
+        }
+        Some(MsgReloadField { duel_rule, players, chains })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = begin(MsgType::ReloadField);
+        buf.push(self.duel_rule);
+        for player in self.players.iter() {
+            buf.extend_from_slice(&player.lp.to_le_bytes());
+            for zone in player.mzone.iter() {
+                match zone {
+                    Some((pos, overlays)) => buf.extend_from_slice(&[1, *pos, *overlays]),
+                    None => buf.push(0),
+                }
+            }
+            for zone in player.szone.iter() {
+                match zone {
+                    Some(pos) => buf.extend_from_slice(&[1, *pos]),
+                    None => buf.push(0),
+                }
+            }
+            buf.extend_from_slice(&[player.deck, player.hand, player.grave, player.remove, player.extra, player.extra_p]);
+        }
+        buf.push(self.chains.len() as u8);
+        for c in self.chains.iter() {
+            buf.extend_from_slice(&c.code.to_le_bytes());
+            c.location.write(&mut buf);
+            buf.extend_from_slice(&[c.trigger_controller, c.trigger_location, c.trigger_sequence]);
+            buf.extend_from_slice(&c.desc.to_le_bytes());
+        }
+        buf
+    }
 }
 
 /// Refresh Deck message: no payload
@@ -551,6 +1644,10 @@ impl MsgRefreshDeck {
     pub fn parse(_payload: &[u8]) -> Option<MsgRefreshDeck> {
         Some(MsgRefreshDeck)
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        begin(MsgType::RefreshDeck)
+    }
 }
 
 /// Parse STOC (Server To Client) network packets
@@ -589,3 +1686,174 @@ pub fn parse_replay_packet_enhanced(data: &[u8]) -> (StocType, MsgType, &[u8]) {
     parse_stoc_packet(data)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode, split the id off again and check the parser gives back the same message.
+    fn round_trip<T: PartialEq + std::fmt::Debug>(msg: &T, ty: MsgType, encode: fn(&T) -> Vec<u8>, parse: fn(&[u8]) -> Option<T>) -> Vec<u8> {
+        let bytes = encode(msg);
+        let (parsed_type, payload) = parse_packet(&bytes);
+        assert_eq!(parsed_type, ty);
+        assert_eq!(parse(payload).as_ref(), Some(msg));
+        bytes
+    }
+
+    #[test]
+    fn msg_type_id_matches_wire_value() {
+        for id in 0..=255u8 {
+            assert_eq!(MsgType::from(id).id(), id);
+        }
+    }
+
+    #[test]
+    fn simple_messages_round_trip() {
+        let start = MsgStart { player_type: 0x10, duel_rule: 5, lp: [8000, 4000], deck_count: [40, 41], extra_count: [15, 3] };
+        assert_eq!(round_trip(&start, MsgType::Start, MsgStart::encode, MsgStart::parse).len(), 19);
+        assert_eq!(round_trip(&MsgNewTurn { player: 1 }, MsgType::NewTurn, MsgNewTurn::encode, MsgNewTurn::parse), vec![40, 1]);
+        assert_eq!(round_trip(&MsgNewPhase { phase: 0x04 }, MsgType::NewPhase, MsgNewPhase::encode, MsgNewPhase::parse), vec![41, 4, 0]);
+        let draw = MsgDraw { player: 0, count: 2, codes: vec![89631139, 46986414] };
+        assert_eq!(round_trip(&draw, MsgType::Draw, MsgDraw::encode, MsgDraw::parse).len(), 11);
+        round_trip(&MsgShuffleDeck { player: 1 }, MsgType::ShuffleDeck, MsgShuffleDeck::encode, MsgShuffleDeck::parse);
+        round_trip(&MsgLpUpdate { player: 1, lp: 7000 }, MsgType::LpUpdate, MsgLpUpdate::encode, MsgLpUpdate::parse);
+        round_trip(&MsgRetry, MsgType::Retry, MsgRetry::encode, MsgRetry::parse);
+        round_trip(&MsgWaiting, MsgType::Waiting, MsgWaiting::encode, MsgWaiting::parse);
+        round_trip(&MsgRequestDeck, MsgType::RequestDeck, MsgRequestDeck::encode, MsgRequestDeck::parse);
+        round_trip(&MsgRefreshDeck, MsgType::RefreshDeck, MsgRefreshDeck::encode, MsgRefreshDeck::parse);
+        round_trip(&MsgWin { player: 0, reason: 1 }, MsgType::Win, MsgWin::encode, MsgWin::parse);
+        round_trip(&MsgHint { hint_type: 3, player: 0, data: 501 }, MsgType::Hint, MsgHint::encode, MsgHint::parse);
+        round_trip(&MsgUpdateData { flag: 4 }, MsgType::UpdateData, MsgUpdateData::encode, MsgUpdateData::parse);
+        round_trip(&MsgUpdateCard { flag: 1, code: 1234 }, MsgType::UpdateCard, MsgUpdateCard::encode, MsgUpdateCard::parse);
+        round_trip(&MsgChainSolving { count: 2 }, MsgType::ChainSolving, MsgChainSolving::encode, MsgChainSolving::parse);
+        round_trip(&MsgChainSolved { count: 2 }, MsgType::ChainSolved, MsgChainSolved::encode, MsgChainSolved::parse);
+        round_trip(&MsgChainEnd, MsgType::ChainEnd, MsgChainEnd::encode, MsgChainEnd::parse);
+        round_trip(&MsgSpSummoned, MsgType::SPSummoned, MsgSpSummoned::encode, MsgSpSummoned::parse);
+        let place = MsgSelectPlace { player: 0, count: 1, flag: 0xffff_e0e3 };
+        assert_eq!(round_trip(&place, MsgType::SelectPlace, MsgSelectPlace::encode, MsgSelectPlace::parse).len(), 7);
+        assert_eq!(place.encode_as(MsgType::SelectDisField)[0], MsgType::SelectDisField.id());
+        let position = MsgSelectPosition { player: 1, code: 89631139, positions: 0x5 };
+        assert_eq!(round_trip(&position, MsgType::SelectPosition, MsgSelectPosition::encode, MsgSelectPosition::parse).len(), 7);
+        round_trip(&MsgFieldDisabled { flag: 0x0001_0100 }, MsgType::FieldDisabled, MsgFieldDisabled::encode, MsgFieldDisabled::parse);
+    }
+
+    #[test]
+    fn card_messages_round_trip() {
+        let mv = MsgMove { code: 55144522, from_player: 0, from_loc: 0x01, from_seq: 3, from_pos: 0x0a, to_player: 0, to_loc: 0x02, to_seq: 0, to_pos: 0x0a, reason: 0x40 };
+        assert_eq!(round_trip(&mv, MsgType::Move, MsgMove::encode, MsgMove::parse).len(), 17);
+        let summon = MsgSummoning { code: 1, player: 0, loc: 0x04, seq: 2, pos: 0x01, attack: None, level: None };
+        assert_eq!(round_trip(&summon, MsgType::Summoning, MsgSummoning::encode, MsgSummoning::parse).len(), 9);
+        let sp = MsgSpSummoning { code: 2, player: 1, loc: 0x04, seq: 5, pos: 0x04, attack: Some(2500), level: Some(7) };
+        round_trip(&sp, MsgType::SPSummoning, MsgSpSummoning::encode, MsgSpSummoning::parse);
+        let chaining = MsgChaining { code: 3, src_player: 0, src_loc: 0x08, src_seq: 1, src_sub: 0x05, trg_player: 0, trg_loc: 0x08, trg_seq: 1, desc: 48, ctype: 1 };
+        round_trip(&chaining, MsgType::Chaining, MsgChaining::encode, MsgChaining::parse);
+        let select = MsgSelectChain {
+            player: 1,
+            spe_count: 0,
+            forced: 0,
+            hint_timing: [0x1, 0x2],
+            chains: vec![ChainCandidate { edesc: 0, forced: 1, code: 44095762, location: LocInfo { controller: 1, location: 0x08, sequence: 2, position: 0x0a }, desc: 0 }],
+        };
+        assert_eq!(round_trip(&select, MsgType::SelectChain, MsgSelectChain::encode, MsgSelectChain::parse).len(), 1 + 12 + 14);
+        let cards = MsgSelectCard {
+            player: 0,
+            cancelable: false,
+            min: 1,
+            max: 2,
+            cards: vec![CardCandidate { code: 89631139, location: LocInfo { controller: 0, location: 0x02, sequence: 0, position: 0x0a } }],
+        };
+        assert_eq!(round_trip(&cards, MsgType::SelectCard, MsgSelectCard::encode, MsgSelectCard::parse).len(), 1 + 5 + 8);
+        let tuner = SumCandidate { code: 63977008, controller: 0, location: 0x04, sequence: 0, param: 3 };
+        let sum = MsgSelectSum {
+            greater: false,
+            player: 0,
+            acc: 7,
+            min: 1,
+            max: 2,
+            must: vec![tuner],
+            cards: vec![SumCandidate { sequence: 1, param: 4, ..tuner }, SumCandidate { sequence: 2, param: 2 | (4 << 16), ..tuner }],
+        };
+        assert_eq!(round_trip(&sum, MsgType::SelectSum, MsgSelectSum::encode, MsgSelectSum::parse).len(), 1 + 9 + 11 + 1 + 22);
+        let equip = LocInfo { controller: 0, location: 0x08, sequence: 1, position: 0x01 };
+        let target = LocInfo { controller: 1, location: 0x04, sequence: 2, position: 0x01 };
+        assert_eq!(round_trip(&MsgEquip { card: equip, target }, MsgType::Equip, MsgEquip::encode, MsgEquip::parse).len(), 9);
+        round_trip(&MsgUnequip { card: equip }, MsgType::Unequip, MsgUnequip::encode, MsgUnequip::parse);
+        let card_target = MsgCardTarget { card: equip, target };
+        round_trip(&card_target, MsgType::CardTarget, MsgCardTarget::encode, MsgCardTarget::parse);
+        assert_eq!(card_target.encode_as(MsgType::CancelTarget)[0], MsgType::CancelTarget.id());
+        let swap = MsgSwap { code1: 1, location1: equip, code2: 2, location2: target };
+        assert_eq!(round_trip(&swap, MsgType::Swap, MsgSwap::encode, MsgSwap::parse).len(), 17);
+        let counter = MsgAddCounter { counter_type: 0x1, controller: 0, location: 0x04, sequence: 2, count: 3 };
+        assert_eq!(round_trip(&counter, MsgType::AddCounter, MsgAddCounter::encode, MsgAddCounter::parse).len(), 8);
+        assert_eq!(counter.encode_as(MsgType::RemoveCounter)[0], MsgType::RemoveCounter.id());
+        let holder = CounterCandidate { code: 70791313, controller: 0, location: 0x08, sequence: 1, count: 2 };
+        let select = MsgSelectCounter { player: 0, counter_type: 0x1, count: 3, cards: vec![holder, CounterCandidate { sequence: 3, count: 4, ..holder }] };
+        assert_eq!(round_trip(&select, MsgType::SelectCounter, MsgSelectCounter::encode, MsgSelectCounter::parse).len(), 1 + 6 + 2 * 9);
+    }
+
+    #[test]
+    fn announce_messages_round_trip() {
+        let options = MsgSelectOption { player: 1, options: vec![60, 61] };
+        assert_eq!(round_trip(&options, MsgType::SelectOption, MsgSelectOption::encode, MsgSelectOption::parse).len(), 1 + 2 + 8);
+        assert_eq!(options.encode_as(MsgType::AnnounceCard)[0], MsgType::AnnounceCard.id());
+        assert_eq!(options.encode_as(MsgType::AnnounceNumber)[0], MsgType::AnnounceNumber.id());
+        let race = MsgAnnounceRace { player: 0, count: 1, available: 0x3ffffff };
+        assert_eq!(round_trip(&race, MsgType::AnnounceRace, MsgAnnounceRace::encode, MsgAnnounceRace::parse).len(), 7);
+        assert_eq!(race.encode_as(MsgType::AnnounceAttrib)[0], MsgType::AnnounceAttrib.id());
+    }
+
+    #[test]
+    fn random_messages_round_trip() {
+        let coins = MsgTossCoin { player: 1, results: vec![1, 0, 1] };
+        assert_eq!(round_trip(&coins, MsgType::TossCoin, MsgTossCoin::encode, MsgTossCoin::parse), vec![130, 1, 3, 1, 0, 1]);
+        assert_eq!(coins.encode_as(MsgType::TossDice)[0], MsgType::TossDice.id());
+        round_trip(&MsgRockPaperScissors { player: 0 }, MsgType::RockPaperScissors, MsgRockPaperScissors::encode, MsgRockPaperScissors::parse);
+        assert_eq!(round_trip(&MsgHandRes { hands: [3, 2] }, MsgType::HandRes, MsgHandRes::encode, MsgHandRes::parse), vec![133, 3 + (2 << 2)]);
+    }
+
+    #[test]
+    fn public_information_messages_round_trip() {
+        let cards = vec![ConfirmedCard { code: 89631139, controller: 0, location: 0x2, sequence: 3 }];
+        let confirm = MsgConfirmCards { player: 1, cards };
+        let bytes = round_trip(&confirm, MsgType::ConfirmCards, MsgConfirmCards::encode, MsgConfirmCards::parse);
+        assert_eq!(bytes.len(), 1 + 2 + 7);
+        assert_eq!(confirm.encode_as(MsgType::ConfirmDeckTop)[0], MsgType::ConfirmDeckTop.id());
+        round_trip(&MsgShuffleHand { player: 0, codes: vec![1, 2, 3] }, MsgType::ShuffleHand, MsgShuffleHand::encode, MsgShuffleHand::parse);
+        let before = vec![LocInfo { controller: 1, location: 0x4, sequence: 0, position: 0x8 }, LocInfo { controller: 1, location: 0x4, sequence: 2, position: 0x8 }];
+        let after = vec![before[1], before[0]];
+        let shuffled = round_trip(&MsgShuffleSetCard { location: 0x4, before, after }, MsgType::ShuffleSetCard, MsgShuffleSetCard::encode, MsgShuffleSetCard::parse);
+        assert_eq!(&shuffled[1..3], &[0x4, 2]);
+    }
+
+    #[test]
+    fn deck_messages_round_trip() {
+        let top = round_trip(&MsgDeckTop { player: 1, sequence: 0, code: 89631139 | 0x80000000 }, MsgType::DeckTop, MsgDeckTop::encode, MsgDeckTop::parse);
+        assert_eq!(top.len(), 7);
+        assert_eq!(round_trip(&MsgSwapGraveDeck { player: 1 }, MsgType::SwapGraveDeck, MsgSwapGraveDeck::encode, MsgSwapGraveDeck::parse), vec![35, 1]);
+        round_trip(&MsgReverseDeck, MsgType::ReverseDeck, MsgReverseDeck::encode, MsgReverseDeck::parse);
+        let swap = MsgTagSwap { player: 0, main_count: 30, extra_p_count: 1, top_code: 0, hand: vec![1, 2], extra: vec![3 | 0x80000000] };
+        assert_eq!(round_trip(&swap, MsgType::TagSwap, MsgTagSwap::encode, MsgTagSwap::parse).len(), 22);
+    }
+
+    #[test]
+    fn string_and_field_messages_round_trip() {
+        let hint = round_trip(&MsgShowHint { message: "Win this turn".to_string() }, MsgType::ShowHint, MsgShowHint::encode, MsgShowHint::parse);
+        assert_eq!(&hint[1..3], &13u16.to_le_bytes());
+        assert_eq!(*hint.last().unwrap(), 0, "strings are NUL terminated");
+        round_trip(&MsgAiName { name: "Puzzle".to_string() }, MsgType::AiName, MsgAiName::encode, MsgAiName::parse);
+
+        let mut players: [ReloadFieldPlayer; 2] = Default::default();
+        players[0].lp = 100;
+        players[0].mzone[2] = Some((0x01, 2));
+        players[0].szone[5] = Some(0x05);
+        players[1].lp = 8000;
+        players[1].deck = 35;
+        players[1].extra = 2;
+        players[1].extra_p = 1;
+        let chains = vec![ReloadFieldChain { code: 7, location: LocInfo { controller: 0, location: 0x08, sequence: 1, position: 0x05 }, trigger_controller: 0, trigger_location: 0x08, trigger_sequence: 1, desc: 112 }];
+        let reload = MsgReloadField { duel_rule: 5, players, chains };
+        let bytes = round_trip(&reload, MsgType::ReloadField, MsgReloadField::encode, MsgReloadField::parse);
+        // id, rule, then per player: lp, 7 + 8 zone flags, 6 counts; plus the occupied zones and the chain
+        assert_eq!(bytes.len(), 2 + 2 * (4 + 15 + 6) + 2 + 1 + 1 + 15);
+    }
+}
@@ -13,3 +13,29 @@ pub mod database;
 pub mod replay;
 pub mod messages;
 pub mod processor;
+pub mod debug;
+pub mod response;
+pub mod prompt;
+pub mod query;
+pub mod summon;
+pub mod fusion;
+pub mod synchro;
+pub mod xyz;
+pub mod link;
+pub mod pendulum;
+pub mod ritual;
+pub mod zone;
+pub mod relation;
+pub mod counter;
+pub mod token;
+pub mod control;
+pub mod replace;
+pub mod coin;
+pub mod announce;
+pub mod public;
+pub mod deck;
+pub mod hand;
+pub mod options;
+pub mod tag;
+#[cfg(test)]
+pub mod testing;
@@ -0,0 +1,131 @@
+//! Duel options: the ocgcore duel flag bits and the master rule carried in its upper half.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::{DuelFlag, EFFECT_CANNOT_BP};
+use crate::core::scripting::duel_data;
+use mlua::Lua;
+
+/// Master rule used when the duel flag does not name one
+pub const DEFAULT_DUEL_RULE: u8 = 5;
+
+/// How a duel is played: the DUEL_* option flags and the master rule, which decides the
+/// pendulum zones (rule 4 and up use the outer spell & trap zones), Extra Monster Zones and link rules.
+///
+/// The engine acts on PSEUDO_SHUFFLE, TAG_MODE, OBSOLETE_RULING (first-turn draw), RETURN_DECK_TOP,
+/// ATTACK_FIRST_TURN (IsAbleToEnterBP) and SIMPLE_AI (player 1's zones are picked for them).
+/// TEST_MODE and OLD_REPLAY only concern hosts and replays: they are kept for Duel.IsDuelType.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DuelOptions {
+    pub flags: DuelFlag,
+    pub duel_rule: u8,
+}
+
+impl Default for DuelOptions {
+    fn default() -> Self {
+        DuelOptions { flags: DuelFlag::empty(), duel_rule: DEFAULT_DUEL_RULE }
+    }
+}
+
+impl DuelOptions {
+    /// Split a duel flag as ocgcore's start_duel does: the master rule is in the upper 16 bits;
+    /// without one, DUEL_OBSOLETE_RULING means rule 1 and anything else the default rule.
+    pub fn from_duel_flag(flag: u32) -> Self {
+        let flags = DuelFlag::from_bits_truncate(flag & 0xffff);
+        let duel_rule = match (flag >> 16) as u8 {
+            0 if flags.contains(DuelFlag::OBSOLETE_RULING) => 1,
+            0 => DEFAULT_DUEL_RULE,
+            rule => rule,
+        };
+        DuelOptions { flags, duel_rule }
+    }
+
+    /// The duel flag these options are stored as in replays.
+    pub fn duel_flag(&self) -> u32 {
+        self.flags.bits() | (self.duel_rule as u32) << 16
+    }
+}
+
+impl DuelData {
+    /// Play the duel under `options`.
+    pub fn apply_options(&mut self, options: DuelOptions) {
+        self.duel_options = options.flags;
+        self.duel_rule = options.duel_rule;
+    }
+
+    /// Whether the turn player may enter the Battle Phase: never on the first turn of the
+    /// duel unless DUEL_ATTACK_FIRST_TURN, nor while EFFECT_CANNOT_BP applies to them.
+    pub fn is_able_to_enter_bp(&self) -> bool {
+        (self.turn > 1 || self.duel_options.contains(DuelFlag::ATTACK_FIRST_TURN))
+            && self.player_effects(self.turn_player, EFFECT_CANNOT_BP).is_empty()
+    }
+}
+
+/// Register the duel option queries on the global `Duel` table.
+pub fn register_options_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.IsDuelType(flag) - whether any of the DUEL_* flags is set
+    duel_table.set("IsDuelType", lua.create_function(|lua, flag: u32| {
+        Ok(duel_data(lua).lock().unwrap().duel_options.intersects(DuelFlag::from_bits_truncate(flag)))
+    })?)?;
+
+    // Duel.GetMasterRule()
+    duel_table.set("GetMasterRule", lua.create_function(|lua, ()| {
+        Ok(duel_data(lua).lock().unwrap().duel_rule)
+    })?)?;
+
+    // Duel.IsAbleToEnterBP()
+    duel_table.set("IsAbleToEnterBP", lua.create_function(|lua, ()| {
+        Ok(duel_data(lua).lock().unwrap().is_able_to_enter_bp())
+    })?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::duel::Duel;
+
+    #[test]
+    fn duel_flag_carries_the_master_rule() {
+        let options = DuelOptions::from_duel_flag(4 << 16 | 0x02 | 0x10);
+        assert_eq!(options, DuelOptions { flags: DuelFlag::ATTACK_FIRST_TURN | DuelFlag::PSEUDO_SHUFFLE, duel_rule: 4 });
+        assert_eq!(options.duel_flag(), 4 << 16 | 0x12);
+        assert_eq!(DuelOptions::from_duel_flag(0x08).duel_rule, 1, "obsolete ruling without a rule is rule 1");
+        assert_eq!(DuelOptions::from_duel_flag(0), DuelOptions::default());
+
+        let duel = Duel::with_options(0, DuelOptions::from_duel_flag(3 << 16 | 0x40));
+        let (rule, ai, tag): (u8, bool, bool) = duel.lua.load("return Duel.GetMasterRule(), Duel.IsDuelType(DUEL_SIMPLE_AI), Duel.IsDuelType(DUEL_TAG_MODE)").eval().unwrap();
+        assert_eq!((rule, ai, tag), (3, true, false));
+        assert_eq!(duel.data.lock().unwrap().pzone_sequence(1), 7, "rule 3 keeps the separate pendulum zones");
+    }
+
+    #[test]
+    fn option_flags_change_first_turn_and_shuffles() {
+        let plain = Duel::new(3);
+        plain.data.lock().unwrap().turn = 1;
+        assert!(!plain.lua.load("return Duel.IsAbleToEnterBP()").eval::<bool>().unwrap());
+
+        let options = DuelOptions { flags: DuelFlag::ATTACK_FIRST_TURN | DuelFlag::PSEUDO_SHUFFLE, duel_rule: 5 };
+        let mut duel = Duel::with_options(3, options);
+        duel.load_deck(0, &[1001, 1002, 1003, 1004, 1005], &[]);
+        let order = duel.data.lock().unwrap().field.deck[0].clone();
+        duel.data.lock().unwrap().start_count = [0, 0];
+        duel.start_duel();
+        duel.shuffle_deck(0);
+        let mut data = duel.data.lock().unwrap();
+        assert_eq!(data.field.deck[0], order, "pseudo shuffle leaves the Deck in loading order");
+        data.turn = 1;
+        assert!(data.is_able_to_enter_bp());
+    }
+
+    #[test]
+    fn simple_ai_picks_zones_for_player_one() {
+        let options = DuelOptions { flags: DuelFlag::SIMPLE_AI, duel_rule: 5 };
+        let duel = Duel::with_options(0, options);
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.auto_place(1, 0b10110), Some(1), "player 1 is not asked");
+        assert_eq!(data.auto_place(0, 0b10110), None, "player 0 still chooses");
+    }
+}
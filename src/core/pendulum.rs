@@ -0,0 +1,280 @@
+//! Pendulum summons: the two pendulum zones, their scales and the once-per-turn summon from the hand and the face-up Extra Deck.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::group::Group;
+use crate::core::messages::{MsgRetry, MsgSpSummoned};
+use crate::core::prompt::{ready, select_cards, wrap};
+use crate::core::scripting::duel_data;
+use crate::core::summon::{finish_special_summon, is_can_be_special_summoned};
+use crate::core::types::CardId;
+use crate::core::zone::place_each;
+use mlua::{Lua, MultiValue};
+
+impl DuelData {
+    /// Card in a player's pendulum zone (0 = left, 1 = right).
+    pub fn pzone_card(&self, player: u8, index: u8) -> Option<CardId> {
+        let seq = self.pzone_sequence(index) as usize;
+        self.field.szone.get(player as usize)?.get(seq).copied().flatten()
+    }
+
+    /// Scale of a card in a pendulum zone: the left scale on the left, the right scale on the right.
+    pub fn current_scale(&self, card_id: CardId) -> u32 {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return 0;
+        };
+        if self.pzone_card(card.controller, 1) == Some(card_id) {
+            card.current_stats.rscale
+        } else {
+            card.current_stats.lscale
+        }
+    }
+
+    /// Lower and upper scale of a player's pendulum zones, if both are occupied.
+    pub fn pendulum_scales(&self, player: u8) -> Option<(u32, u32)> {
+        let left = self.current_scale(self.pzone_card(player, 0)?);
+        let right = self.current_scale(self.pzone_card(player, 1)?);
+        Some((left.min(right), left.max(right)))
+    }
+
+    /// Monsters in the hand and face-up Pendulums in the Extra Deck whose level lies strictly between the scales.
+    pub fn pendulum_candidates(&self, player: u8) -> Vec<CardId> {
+        let Some((low, high)) = self.pendulum_scales(player) else {
+            return Vec::new();
+        };
+        let p = player as usize;
+        let in_range = |id: &CardId| self.cards.get(id.0 as usize).is_some_and(|c| {
+            let level = if c.current_stats.type_.intersects(CardType::XYZ | CardType::LINK) { 0 } else { c.current_stats.level };
+            c.current_stats.type_.contains(CardType::MONSTER) && level > low && level < high
+        });
+        let from_extra = self.field.extra[p].iter()
+            .filter(|id| self.cards.get(id.0 as usize).is_some_and(|c| {
+                c.position.intersects(CardPosition::FACEUP) && c.current_stats.type_.contains(CardType::PENDULUM)
+            }));
+        self.field.hand[p].iter().chain(from_extra).copied().filter(in_range).collect()
+    }
+
+    /// Most monsters a pendulum summon can place: the free main zones plus a free Extra Monster Zone.
+    pub fn pendulum_summon_limit(&self, player: u8) -> usize {
+        let emz = self.duel_rule >= 4 && self.extra_summon_zones(player, None, &[]).iter().any(|&s| s >= 5);
+        self.free_mzone_count(player) + emz as usize
+    }
+
+    /// Most face-up Pendulums, `card` among them, a pendulum summon can bring from the Extra Deck: from
+    /// Master Rule 4 on one Extra Monster Zone and the linked zones, before that any free main zone.
+    pub fn pendulum_extra_limit(&self, player: u8, card: CardId) -> usize {
+        let zones = self.extra_summon_zones(player, Some(card), &[]);
+        zones.iter().filter(|&&s| s < 5).count() + zones.iter().any(|&s| s >= 5) as usize
+    }
+}
+
+/// Cards `player` could pendulum summon right now; empty once they have pendulum summoned this turn.
+pub fn pendulum_summonable(lua: &Lua, player: u8) -> mlua::Result<Vec<CardId>> {
+    let data = duel_data(lua);
+    let candidates = {
+        let data_guard = data.lock().unwrap();
+        if data_guard.pendulum_summoned.get(player as usize).is_none_or(|&done| done) || data_guard.pendulum_summon_limit(player) == 0 {
+            return Ok(Vec::new());
+        }
+        data_guard.pendulum_candidates(player)
+    };
+    let mut out = Vec::new();
+    for card_id in candidates {
+        if is_can_be_special_summoned(lua, card_id, None, SUMMON_TYPE_PENDULUM, player, false, false, CardPosition::FACEUP_ATTACK.bits(), player)? {
+            out.push(card_id);
+        }
+    }
+    Ok(out)
+}
+
+/// Registry name of the function running a pendulum summon (Duel.PendulumSummon).
+pub(crate) const PENDULUM_SUMMON: &str = "osiris.pendulum.summon";
+
+/// Pendulum summon: let `player` pick any number of candidates, place them face-up and raise EVENT_SPSUMMON_SUCCESS.
+/// More Extra Deck monsters than they have zones for are refused with MSG_RETRY and asked again.
+/// Gives the number of monsters summoned.
+fn pendulum_summon(lua: &Lua, player: u8) -> mlua::Result<MultiValue<'_>> {
+    let candidates = pendulum_summonable(lua, player)?;
+    if candidates.is_empty() {
+        return ready(lua, 0);
+    }
+    let limit = duel_data(lua).lock().unwrap().pendulum_summon_limit(player);
+    select_cards(lua, player, candidates, 1, limit, move |lua, mut chosen| {
+        // Extra Deck monsters are the picky ones about zones, so they go down first
+        {
+            let data = duel_data(lua);
+            let mut data_guard = data.lock().unwrap();
+            chosen.sort_by_key(|id| data_guard.cards[id.0 as usize].location != Location::EXTRA);
+            let extra = chosen.iter().take_while(|id| data_guard.cards[id.0 as usize].location == Location::EXTRA).count();
+            if extra > 0 && extra > data_guard.pendulum_extra_limit(player, chosen[0]) {
+                data_guard.write_message(&MsgRetry.encode());
+                drop(data_guard);
+                return pendulum_summon(lua, player);
+            }
+        }
+        let zones = move |lua: &Lua, id: CardId| Ok(Some((player, duel_data(lua).lock().unwrap().summon_zones(id, player))));
+        let place = move |lua: &Lua, id: CardId, seq: Option<u8>| {
+            let data = duel_data(lua);
+            let mut data_guard = data.lock().unwrap();
+            let placed = seq.is_some_and(|seq| data_guard.special_summon_place(id, SUMMON_TYPE_PENDULUM, player, CardPosition::FACEUP_ATTACK, seq));
+            if placed {
+                data_guard.cards[id.0 as usize].set_status(CardStatus::PROC_COMPLETE);
+            }
+            Ok(placed)
+        };
+        place_each(lua, Location::MZONE, chosen, Vec::new(), zones, place, move |lua, placed| {
+            let count = placed.len() as u32;
+            if count > 0 {
+                let data = duel_data(lua);
+                {
+                    let mut data_guard = data.lock().unwrap();
+                    data_guard.pendulum_summoned[player as usize] = true;
+                    data_guard.write_message(&MsgSpSummoned.encode());
+                }
+                finish_special_summon(lua, data, placed, player);
+            }
+            ready(lua, count)
+        })
+    })
+}
+
+/// Register the pendulum functions on the global `Duel` table.
+pub fn register_pendulum_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.GetPendulumScales(player) -> lower, upper scale, or nil without two pendulum zones
+    duel_table.set("GetPendulumScales", lua.create_function(|lua, player: u8| {
+        let data = duel_data(lua);
+        let scales = data.lock().unwrap().pendulum_scales(player);
+        Ok((scales.map(|s| s.0), scales.map(|s| s.1)))
+    })?)?;
+
+    // Duel.GetPendulumSummonableGroup(player) -> Group
+    duel_table.set("GetPendulumSummonableGroup", lua.create_function(|lua, player: u8| {
+        Ok(Group(pendulum_summonable(lua, player)?.into_iter().collect()))
+    })?)?;
+
+    // Duel.IsPlayerCanPendulumSummon(player) -> bool
+    duel_table.set("IsPlayerCanPendulumSummon", lua.create_function(|lua, player: u8| {
+        Ok(!pendulum_summonable(lua, player)?.is_empty())
+    })?)?;
+
+    // Duel.PendulumSummon(player) -> count
+    let summon = wrap(lua, lua.create_function(pendulum_summon)?)?;
+    lua.set_named_registry_value(PENDULUM_SUMMON, summon.clone())?;
+    duel_table.set("PendulumSummon", summon)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::database::CardData;
+    use crate::core::enums::*;
+    use crate::core::messages::MsgRetry;
+    use crate::core::processor::ProcessResult;
+    use crate::core::testing::{card, duel_with};
+    use crate::core::types::CardId;
+
+    fn pendulum(code: u32, level: u32, scale: u32) -> CardData {
+        CardData { lscale: scale, rscale: scale, ..card(code, CardType::MONSTER | CardType::PENDULUM, level) }
+    }
+
+    #[test]
+    fn pendulum_summon_from_hand_and_extra_once_per_turn() {
+        let cards = [pendulum(9501, 4, 1), pendulum(9502, 4, 8), pendulum(9503, 4, 2), pendulum(9504, 5, 3), pendulum(9505, 8, 0), pendulum(9506, 4, 0)];
+        let mut duel = duel_with(&cards, r#"
+            Debug.AddCard(9501, 0, 0, LOCATION_PZONE, 0, POS_FACEUP)
+            Debug.AddCard(9502, 0, 0, LOCATION_PZONE, 1, POS_FACEUP)
+            Debug.AddCard(9503, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9504, 0, 0, LOCATION_EXTRA, 0, POS_FACEUP_DEFENSE)
+            Debug.AddCard(9505, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9506, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+        "#);
+        {
+            let data = duel.data.lock().unwrap();
+            assert_eq!((data.cards[1].location, data.cards[1].sequence), (Location::SZONE, 4));
+            assert_eq!(data.current_scale(CardId::new(1)), 8);
+            assert_eq!(data.pendulum_scales(0), Some((1, 8)));
+            assert_eq!(data.pendulum_candidates(0), vec![CardId::new(2), CardId::new(3)], "level 8 is not below the scale, face-down extra does not count");
+        }
+
+        duel.pendulum_summon(0);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the monsters are chosen by the player");
+        // Pick both candidates
+        duel.set_responseb(&[2, 0, 1]);
+        // Then a zone for each, the Extra Deck one first
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 5]);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 0]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        {
+            let data = duel.data.lock().unwrap();
+            let from_extra = data.get_card(CardId::new(3)).unwrap();
+            assert_eq!((from_extra.location, from_extra.sequence), (Location::MZONE, 5), "a face-up Pendulum needs the EMZ under Master Rule 5");
+            assert_eq!(from_extra.summon_type(), SUMMON_TYPE_PENDULUM);
+            let from_hand = data.get_card(CardId::new(2)).unwrap();
+            assert_eq!((from_hand.location, from_hand.sequence), (Location::MZONE, 0));
+            assert!(data.pendulum_summoned[0]);
+        }
+        let again: bool = duel.lua.load("return Duel.IsPlayerCanPendulumSummon(0)").eval().unwrap();
+        assert!(!again, "only once per turn");
+    }
+
+    #[test]
+    fn extra_deck_pendulums_are_limited_to_their_zones() {
+        let mut cards = vec![pendulum(9511, 4, 1), pendulum(9512, 4, 8)];
+        cards.extend((9513..9516).map(|code| pendulum(code, 4, 2)));
+        let mut duel = duel_with(&cards, r#"
+            Debug.AddCard(9511, 0, 0, LOCATION_PZONE, 0, POS_FACEUP)
+            Debug.AddCard(9512, 0, 0, LOCATION_PZONE, 1, POS_FACEUP)
+            Debug.AddCard(9513, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9514, 0, 0, LOCATION_EXTRA, 0, POS_FACEUP_DEFENSE)
+            Debug.AddCard(9515, 0, 0, LOCATION_EXTRA, 0, POS_FACEUP_DEFENSE)
+        "#);
+        {
+            let data = duel.data.lock().unwrap();
+            assert_eq!(data.pendulum_extra_limit(0, CardId::new(3)), 1, "no Link monsters, so only the Extra Monster Zone");
+        }
+
+        duel.pendulum_summon(0);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.get_message();
+        // Both Extra Deck ones cannot fit into the one Extra Monster Zone
+        duel.set_responseb(&[3, 0, 1, 2]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "asked again");
+        assert!(duel.get_message().starts_with(&MsgRetry.encode()));
+        duel.set_responseb(&[2, 0, 1]);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 5]);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 0]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.get_card(CardId::new(3)).unwrap().sequence, 5);
+        assert_eq!(data.get_card(CardId::new(2)).unwrap().sequence, 0);
+        assert_eq!(data.get_card(CardId::new(4)).unwrap().location, Location::EXTRA);
+    }
+
+    #[test]
+    fn face_up_pendulums_leaving_the_field_go_to_the_extra_deck() {
+        let cards: Vec<_> = (9601..9604).map(|code| pendulum(code, 4, 2)).collect();
+        let duel = duel_with(&cards, r#"
+            Debug.AddCard(9601, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(9602, 0, 0, LOCATION_MZONE, 1, POS_FACEDOWN_DEFENSE)
+            Debug.AddCard(9603, 1, 0, LOCATION_PZONE, 0, POS_FACEUP)
+        "#);
+        let mut data = duel.data.lock().unwrap();
+        for id in 0..3 {
+            data.send_card_to(CardId::new(id), 0, Location::GRAVE, REASON_DESTROY);
+        }
+        let faceup = data.get_card(CardId::new(0)).unwrap();
+        assert_eq!(faceup.location, Location::EXTRA);
+        assert!(faceup.position.intersects(CardPosition::FACEUP));
+        assert_eq!(data.get_card(CardId::new(1)).unwrap().location, Location::GRAVE, "face-down ones still go to the GY");
+        let scale = data.get_card(CardId::new(2)).unwrap();
+        assert_eq!((scale.location, scale.controller), (Location::EXTRA, 1), "back to its owner's Extra Deck");
+        assert!(data.field.extra[1].contains(&CardId::new(2)));
+    }
+}
@@ -1,7 +1,7 @@
 //! Processor Unit Queue implementation
 //! Based on ygopro's core.units architecture
 
-
+use std::sync::Arc;
 
 /// Result of processing a unit
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,6 +12,8 @@ pub enum ProcessResult {
     Waiting,
     /// Processing ended (no more units)
     End,
+    /// A script raised an error; Duel::take_error returns it
+    Error,
 }
 
 /// Types of processor units
@@ -41,6 +43,8 @@ pub enum ProcessorType {
     Position,
     /// Special summon
     SpecialSummon,
+    /// Pendulum summon
+    PendulumSummon,
     /// Normal summon
     NormalSummon,
     /// Set monster
@@ -55,6 +59,8 @@ pub enum ProcessorType {
     SolveChain,
     /// Add chain (execute cost and target before adding to chain)
     AddChain,
+    /// Run a Lua function as a script, the way a chain link's operation runs
+    ExecuteOperation,
 }
 
 /// A processor unit representing a discrete processing step
@@ -68,6 +74,8 @@ pub struct ProcessorUnit {
     pub arg1: u32,
     /// Generic argument 2
     pub arg2: u32,
+    /// Lua function an ExecuteOperation unit runs
+    pub operation: Option<Arc<mlua::RegistryKey>>,
 }
 
 impl ProcessorUnit {
@@ -78,6 +86,7 @@ impl ProcessorUnit {
             step,
             arg1,
             arg2,
+            operation: None,
         }
     }
 
@@ -95,4 +104,9 @@ impl ProcessorUnit {
     pub fn solve_chain(step: u32) -> Self {
         Self::new(ProcessorType::SolveChain, step, 0, 0)
     }
+
+    /// Create a unit running the Lua function stored under `operation`
+    pub fn execute_operation(operation: mlua::RegistryKey) -> Self {
+        Self { operation: Some(Arc::new(operation)), ..Self::new(ProcessorType::ExecuteOperation, 0, 0, 0) }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,154 @@
+//! Prompts asked from scripts. A Rust call cannot be suspended from Lua, so a prompting function returns
+//! to a small Lua wrapper instead: either its results, or the prompt it wrote and how to carry on. The
+//! wrapper then yields the script's coroutine, and the processor resumes it once the host has answered.
+
+use crate::core::messages::{CardCandidate, MsgSelectCard, MsgSelectSum, SumCandidate};
+use crate::core::response::{Prompt, Response};
+use crate::core::scripting::duel_data;
+use crate::core::types::CardId;
+use mlua::{FromLuaMulti, Function, IntoLuaMulti, Lua, MultiValue, Table, Value};
+
+/// Lua side of the prompting functions. `raw` returns true and its results, or false and the function
+/// that continues once the host answered, which returns the same way and so may ask again.
+const AWAIT: &str = r#"
+    local function await(ready, ...)
+        if ready then
+            return ...
+        end
+        local continue = ...
+        coroutine.yield()
+        return await(continue())
+    end
+    return function(raw)
+        return function(...)
+            return await(raw(...))
+        end
+    end
+"#;
+
+/// Registry name of the function turning a raw prompting function into the one scripts call.
+const WRAP: &str = "osiris.prompt.wrap";
+
+/// Results of a prompting function that did not have to ask.
+pub fn ready<'lua>(lua: &'lua Lua, values: impl IntoLuaMulti<'lua>) -> mlua::Result<MultiValue<'lua>> {
+    let mut out = values.into_lua_multi(lua)?;
+    out.push_front(Value::Boolean(true));
+    Ok(out)
+}
+
+/// Ask the host: write `msg`, leave `prompt` pending and suspend the script until it is answered.
+/// `then` carries on with the validated answer and returns like a prompting function.
+pub fn ask<'lua, F>(lua: &'lua Lua, prompt: Prompt, msg: &[u8], then: F) -> mlua::Result<MultiValue<'lua>>
+where
+    F: for<'a> FnOnce(&'a Lua, Response) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+{
+    {
+        let data = duel_data(lua);
+        let mut data_guard = data.lock().unwrap();
+        data_guard.write_message(msg);
+        data_guard.prompt = Some(prompt);
+    }
+    let mut then = Some(then);
+    let resume = lua.create_function_mut(move |lua, ()| {
+        let answer = duel_data(lua).lock().unwrap().answer.take();
+        match (answer, then.take()) {
+            (Some(answer), Some(then)) => then(lua, answer),
+            _ => Err(mlua::Error::RuntimeError("a prompt was resumed without an answer".to_string())),
+        }
+    })?;
+    (false, resume).into_lua_multi(lua)
+}
+
+/// The function scripts call for the raw prompting function `raw`.
+pub fn wrap<'lua>(lua: &'lua Lua, raw: Function<'lua>) -> mlua::Result<Function<'lua>> {
+    lua.named_registry_value::<Function>(WRAP)?.call(raw)
+}
+
+/// Set `table[name]` to a prompting function. `raw` answers through `ready` or `ask`.
+pub fn set_prompting<'lua, A, F>(lua: &'lua Lua, table: &Table<'lua>, name: &str, raw: F) -> mlua::Result<()>
+where
+    A: FromLuaMulti<'lua>,
+    F: Fn(&'lua Lua, A) -> mlua::Result<MultiValue<'lua>> + Send + 'static,
+{
+    table.set(name, wrap(lua, lua.create_function(raw)?)?)
+}
+
+/// The answer a prompt's decoding cannot give, reported as a script error.
+pub fn unexpected(answer: Response) -> mlua::Error {
+    mlua::Error::RuntimeError(format!("unexpected answer {:?}", answer))
+}
+
+/// Let `player` pick between `min` and `max` of `cards` (MSG_SELECT_CARD); `then` carries on with the chosen cards.
+pub fn select_cards<'lua, F>(lua: &'lua Lua, player: u8, cards: Vec<CardId>, min: usize, max: usize, then: F) -> mlua::Result<MultiValue<'lua>>
+where
+    F: for<'a> FnOnce(&'a Lua, Vec<CardId>) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+{
+    let max = max.min(cards.len());
+    let min = min.min(max);
+    if max == 0 {
+        return then(lua, Vec::new());
+    }
+    let msg = {
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        let candidates = cards.iter()
+            .map(|&id| CardCandidate { code: data_guard.cards.get(id.0 as usize).map(|c| c.code).unwrap_or(0), location: data_guard.info_location(id) })
+            .collect();
+        MsgSelectCard { player, cancelable: false, min: min as u8, max: max as u8, cards: candidates }
+    };
+    let prompt = Prompt::SelectCard { count: cards.len(), min, max, cancelable: false };
+    ask(lua, prompt, &msg.encode(), move |lua, answer| match answer {
+        Response::Cards(indices) => then(lua, indices.into_iter().map(|i| cards[i]).collect()),
+        answer => Err(unexpected(answer)),
+    })
+}
+
+/// Let `player` add cards to the forced `must` ones until their sum parameters reach `acc` (MSG_SELECT_SUM).
+/// `then` carries on with the chosen cards, without the forced ones.
+#[allow(clippy::too_many_arguments)]
+pub fn select_sum<'lua, F>(lua: &'lua Lua, player: u8, acc: u32, greater: bool, min: usize, max: usize, must: &[(CardId, u32)], cards: Vec<(CardId, u32)>, then: F) -> mlua::Result<MultiValue<'lua>>
+where
+    F: for<'a> FnOnce(&'a Lua, Vec<CardId>) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+{
+    let msg = {
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        let candidate = |&(id, param): &(CardId, u32)| {
+            let loc = data_guard.info_location(id);
+            SumCandidate {
+                code: data_guard.cards.get(id.0 as usize).map(|c| c.code).unwrap_or(0),
+                controller: loc.controller,
+                location: loc.location,
+                sequence: loc.sequence,
+                param,
+            }
+        };
+        MsgSelectSum {
+            greater,
+            player,
+            acc,
+            min: min as u8,
+            max: max as u8,
+            must: must.iter().map(candidate).collect(),
+            cards: cards.iter().map(candidate).collect(),
+        }
+    };
+    let prompt = Prompt::SelectSum {
+        acc,
+        greater,
+        min,
+        max,
+        must: must.iter().map(|m| m.1).collect(),
+        values: cards.iter().map(|c| c.1).collect(),
+    };
+    ask(lua, prompt, &msg.encode(), move |lua, answer| match answer {
+        Response::Cards(indices) => then(lua, indices.into_iter().map(|i| cards[i].0).collect()),
+        answer => Err(unexpected(answer)),
+    })
+}
+
+/// Register the Lua side of the prompting functions. Must run before any of them is set.
+pub fn register_prompt_functions(lua: &Lua) -> mlua::Result<()> {
+    let wrap: Function = lua.load(AWAIT).eval()?;
+    lua.set_named_registry_value(WRAP, wrap)
+}
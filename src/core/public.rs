@@ -0,0 +1,216 @@
+//! Public information: which cards each player may see, confirming and revealing cards, and
+//! shuffling the hand or set cards so that what was revealed becomes hidden again.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::group::cards_of;
+use crate::core::messages::{ConfirmedCard, MsgConfirmCards, MsgShuffleHand, MsgShuffleSetCard, MsgType};
+use crate::core::scripting::duel_data;
+use crate::core::types::CardId;
+use mlua::Lua;
+
+impl DuelData {
+    /// Whether both players may see a card: cards in the GY, face-up cards, overlay units and
+    /// hand cards under EFFECT_PUBLIC (field effects are taken by range, their targets are not checked).
+    pub fn is_public(&self, card_id: CardId) -> bool {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return false;
+        };
+        if card.location.intersects(Location::GRAVE | Location::OVERLAY) {
+            return true;
+        }
+        if card.location == Location::HAND {
+            return !self.affecting_effects(card_id, EFFECT_PUBLIC).is_empty();
+        }
+        !card.location.intersects(Location::DECK) && card.position.intersects(CardPosition::FACEUP)
+    }
+
+    /// Whether `player` may see a card's code: public cards, cards confirmed to them and
+    /// their own cards anywhere but the Deck.
+    pub fn is_known_to(&self, card_id: CardId, player: u8) -> bool {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return false;
+        };
+        card.confirmed_to & (1 << player) != 0
+            || (card.controller == player && !card.location.intersects(Location::DECK))
+            || self.is_public(card_id)
+    }
+
+    /// Write a message carrying card codes at the given offsets; each code is marked hidden
+    /// for a player who may not know that card, see Duel::get_player_messages.
+    pub fn write_message_hiding(&mut self, msg: &[u8], codes: &[(usize, CardId)]) {
+        let start = self.message_buffer.len();
+        for &(offset, card_id) in codes {
+            for player in 0..2 {
+                if !self.is_known_to(card_id, player) {
+                    self.hidden_codes.push((start + offset, player));
+                }
+            }
+        }
+        self.write_message(msg);
+    }
+
+    /// Show cards to `player` with MSG_CONFIRM_CARDS; they stay known to them until they move or are shuffled.
+    pub fn confirm_cards(&mut self, player: u8, cards: &[CardId]) {
+        let mut confirmed = Vec::new();
+        for &card_id in cards {
+            let Some(card) = self.cards.get_mut(card_id.0 as usize) else { continue };
+            card.confirmed_to |= 1 << player;
+            confirmed.push(ConfirmedCard { code: card.code, controller: card.controller, location: card.location.bits() as u8, sequence: card.sequence });
+        }
+        if !confirmed.is_empty() {
+            self.write_message(&MsgConfirmCards { player, cards: confirmed }.encode());
+        }
+    }
+
+    /// Reveal the top `count` cards of `player`'s Deck to both players (MSG_CONFIRM_DECKTOP). Returns them top first.
+    pub fn confirm_decktop(&mut self, player: u8, count: usize) -> Vec<CardId> {
+        let deck = &self.field.deck[player as usize];
+        let top: Vec<(usize, CardId)> = deck.iter().copied().enumerate().rev().take(count).collect();
+        let mut confirmed = Vec::new();
+        for &(sequence, card_id) in top.iter() {
+            let card = &mut self.cards[card_id.0 as usize];
+            card.confirmed_to = 0b11;
+            confirmed.push(ConfirmedCard { code: card.code, controller: player, location: Location::DECK.bits() as u8, sequence: sequence as u8 });
+        }
+        if !confirmed.is_empty() {
+            self.write_message(&MsgConfirmCards { player, cards: confirmed }.encode_as(MsgType::ConfirmDeckTop));
+        }
+        top.into_iter().map(|(_, card_id)| card_id).collect()
+    }
+
+    /// Shuffle `player`'s hand (MSG_SHUFFLE_HAND); cards that were only confirmed are hidden again.
+    /// The opponent's copy of the message does not carry the codes of cards they may not see.
+    pub fn shuffle_hand(&mut self, player: u8) {
+        let p = player as usize;
+        if self.field.hand[p].len() <= 1 {
+            return;
+        }
+        self.random.shuffle_vector(&mut self.field.hand[p], 0, usize::MAX);
+        let hand = self.field.hand[p].clone();
+        for (sequence, &card_id) in hand.iter().enumerate() {
+            let card = &mut self.cards[card_id.0 as usize];
+            card.sequence = sequence as u8;
+            card.confirmed_to = 0;
+        }
+        let codes = hand.iter().map(|c| self.cards[c.0 as usize].code).collect();
+        let offsets: Vec<(usize, CardId)> = hand.iter().enumerate().map(|(i, &c)| (3 + 4 * i, c)).collect();
+        self.write_message_hiding(&MsgShuffleHand { player, codes }.encode(), &offsets);
+    }
+
+    /// Shuffle face-down cards of one player's monster or spell & trap zones among their zones
+    /// (MSG_SHUFFLE_SET_CARD); overlay units stay attached. Returns false if the cards cannot be shuffled.
+    pub fn shuffle_set_cards(&mut self, cards: &[CardId]) -> bool {
+        let Some(first) = cards.first().and_then(|c| self.cards.get(c.0 as usize)) else {
+            return false;
+        };
+        let (player, location) = (first.controller, first.location);
+        let shuffleable = cards.iter().all(|c| self.cards.get(c.0 as usize).is_some_and(|card| {
+            card.controller == player && card.location == location && card.sequence < 5
+                && card.position.intersects(CardPosition::FACEDOWN)
+        }));
+        if cards.len() <= 1 || !shuffleable || !location.intersects(Location::MZONE | Location::SZONE) {
+            return false;
+        }
+        let before: Vec<_> = cards.iter().map(|&c| self.info_location(c)).collect();
+        let mut sequences: Vec<u8> = cards.iter().map(|c| self.cards[c.0 as usize].sequence).collect();
+        self.random.shuffle_vector(&mut sequences, 0, usize::MAX);
+        for &card_id in cards {
+            self.field.remove_card(player, location, self.cards[card_id.0 as usize].sequence);
+        }
+        for (&card_id, &sequence) in cards.iter().zip(sequences.iter()) {
+            self.field.add_card(player, location, card_id, sequence);
+            let card = &mut self.cards[card_id.0 as usize];
+            card.sequence = sequence;
+            card.confirmed_to = 0;
+        }
+        let after = cards.iter().map(|&c| self.info_location(c)).collect();
+        self.write_message(&MsgShuffleSetCard { location: location.bits() as u8, before, after }.encode());
+        true
+    }
+}
+
+/// Register the confirm and shuffle functions on the global `Duel` table.
+pub fn register_public_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.ConfirmCards(player, targets) - show a card or group to player
+    duel_table.set("ConfirmCards", lua.create_function(|lua, (player, targets): (u8, mlua::Value)| {
+        duel_data(lua).lock().unwrap().confirm_cards(player, &cards_of(&targets));
+        Ok(())
+    })?)?;
+
+    // Duel.ConfirmDecktop(player, count) - reveal the top cards of player's Deck
+    duel_table.set("ConfirmDecktop", lua.create_function(|lua, (player, count): (u8, usize)| {
+        duel_data(lua).lock().unwrap().confirm_decktop(player, count);
+        Ok(())
+    })?)?;
+
+    // Duel.ShuffleHand(player)
+    duel_table.set("ShuffleHand", lua.create_function(|lua, player: u8| {
+        duel_data(lua).lock().unwrap().shuffle_hand(player);
+        Ok(())
+    })?)?;
+
+    // Duel.ShuffleSetCard(targets) - face-down cards of one player's zones
+    duel_table.set("ShuffleSetCard", lua.create_function(|lua, targets: mlua::Value| {
+        duel_data(lua).lock().unwrap().shuffle_set_cards(&cards_of(&targets));
+        Ok(())
+    })?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::messages::{ConfirmedCard, MsgConfirmCards, MsgType};
+    use crate::core::types::CardId;
+
+    #[test]
+    fn confirmed_cards_stay_known_until_the_hand_is_shuffled() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9991, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9992, 0, 0, LOCATION_HAND, 1, POS_FACEDOWN)
+            Debug.AddCard(9993, 0, 0, LOCATION_GRAVE, 0, POS_FACEUP)
+        "#).exec().expect("setup");
+        duel.get_message();
+        let (hidden, grave): (bool, bool) = duel.lua.load("Duel.ConfirmCards(1, Card(0)) return Card(0):IsPublic(), Card(2):IsPublic()").eval().unwrap();
+        assert_eq!((hidden, grave), (false, true));
+        {
+            let data = duel.data.lock().unwrap();
+            assert!(data.is_known_to(CardId::new(0), 1));
+            assert!(!data.is_known_to(CardId::new(1), 1));
+            let shown = ConfirmedCard { code: 9991, controller: 0, location: Location::HAND.bits() as u8, sequence: 0 };
+            assert_eq!(data.message_buffer, MsgConfirmCards { player: 1, cards: vec![shown] }.encode());
+        }
+        duel.get_message();
+        duel.lua.load("Duel.ShuffleHand(0)").exec().unwrap();
+        let [own, opponent] = duel.get_player_messages();
+        assert_eq!(own[0], MsgType::ShuffleHand.id());
+        assert!(own[3..].chunks(4).all(|code| code != [0; 4]), "the owner sees their hand");
+        assert!(opponent[3..].chunks(4).all(|code| code == [0; 4]), "the confirmed card is hidden again");
+        assert_eq!(duel.data.lock().unwrap().get_card(CardId::new(0)).unwrap().confirmed_to, 0);
+    }
+
+    #[test]
+    fn drawn_and_decktop_cards_are_hidden_from_the_opponent() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9994, 1, 1, LOCATION_DECK, 0, POS_FACEDOWN)
+            Debug.AddCard(9995, 1, 1, LOCATION_DECK, 0, POS_FACEDOWN)
+        "#).exec().expect("setup");
+        duel.get_message();
+        duel.lua.load("Duel.ConfirmDecktop(1, 1) Duel.Draw(1, 2)").exec().unwrap();
+        let [opponent, own] = duel.get_player_messages();
+        let decktop = MsgConfirmCards { player: 1, cards: vec![ConfirmedCard { code: 9995, controller: 1, location: 0x1, sequence: 1 }] };
+        let shown = decktop.encode_as(MsgType::ConfirmDeckTop);
+        assert!(own.starts_with(&shown) && opponent.starts_with(&shown), "the Deck top is shown to both players");
+        let draw = &own[shown.len()..];
+        assert_eq!(&draw[..3], &[MsgType::Draw.id(), 1, 2]);
+        assert_eq!(&draw[3..], [9995u32.to_le_bytes(), 9994u32.to_le_bytes()].concat());
+        assert_eq!(&opponent[shown.len() + 3..], [0u8; 8], "the opponent only learns how many cards were drawn");
+    }
+}
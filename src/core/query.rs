@@ -0,0 +1,266 @@
+//! Binary state queries for hosts (query_card / query_field_card / query_field_info).
+//! Layouts follow ocgcore: every card record starts with its total byte length and the flags actually written.
+
+use crate::core::card::Card;
+use crate::core::duel::DuelData;
+use crate::core::enums::{CardStatus, CardType, Location, QueryFlag};
+use crate::core::types::CardId;
+
+/// Record written for an empty zone or a missing card: just the length field.
+const EMPTY_RECORD: [u8; 4] = 4u32.to_le_bytes();
+
+impl DuelData {
+    /// Encode the queried fields of a single card.
+    pub fn card_infos(&self, card_id: CardId, flags: QueryFlag) -> Vec<u8> {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return EMPTY_RECORD.to_vec();
+        };
+        let mut flags = flags;
+        let mut body: Vec<u32> = Vec::new();
+        let stats = &card.current_stats;
+        if flags.contains(QueryFlag::CODE) {
+            body.push(card.code);
+        }
+        if flags.contains(QueryFlag::POSITION) {
+            body.push(self.info_location_u32(card_id));
+        }
+        if flags.contains(QueryFlag::ALIAS) {
+            body.push(if card.alias != 0 { card.alias } else { card.code });
+        }
+        if flags.contains(QueryFlag::TYPE) {
+            body.push(stats.type_.bits());
+        }
+        if flags.contains(QueryFlag::LEVEL) {
+            body.push(query_level(card));
+        }
+        if flags.contains(QueryFlag::RANK) {
+            body.push(if stats.type_.contains(CardType::XYZ) { stats.rank } else { 0 });
+        }
+        if flags.contains(QueryFlag::ATTRIBUTE) {
+            body.push(stats.attribute.bits());
+        }
+        if flags.contains(QueryFlag::RACE) {
+            body.push(stats.race.bits());
+        }
+        if flags.contains(QueryFlag::ATTACK) {
+            body.push(stats.attack as u32);
+        }
+        if flags.contains(QueryFlag::DEFENSE) {
+            body.push(stats.defense as u32);
+        }
+        if flags.contains(QueryFlag::BASE_ATTACK) {
+            body.push(stats.base_attack as u32);
+        }
+        if flags.contains(QueryFlag::BASE_DEFENSE) {
+            body.push(stats.base_defense as u32);
+        }
+        if flags.contains(QueryFlag::REASON) {
+            body.push(card.reason);
+        }
+        if flags.contains(QueryFlag::REASON_CARD) {
+            body.push(0);
+        }
+        if flags.contains(QueryFlag::EQUIP_CARD) {
+            match card.equip_target {
+                Some(target) => body.push(self.info_location_u32(target)),
+                None => flags.remove(QueryFlag::EQUIP_CARD),
+            }
+        }
+        if flags.contains(QueryFlag::TARGET_CARD) {
+            body.push(card.effect_target_cards.len() as u32);
+            body.extend(card.effect_target_cards.iter().map(|&t| self.info_location_u32(t)));
+        }
+        if flags.contains(QueryFlag::OVERLAY_CARD) {
+            body.push(card.xyz_materials.len() as u32);
+            body.extend(card.xyz_materials.iter().filter_map(|m| self.cards.get(m.0 as usize)).map(|m| m.code));
+        }
+        if flags.contains(QueryFlag::COUNTERS) {
+            body.push(card.counters.len() as u32);
+            body.extend(card.counters.iter().map(|(&ty, &count)| ty as u32 | ((count as u32) << 16)));
+        }
+        if flags.contains(QueryFlag::OWNER) {
+            body.push(card.owner as u32);
+        }
+        if flags.contains(QueryFlag::STATUS) {
+            body.push((card.status.clone() & (CardStatus::DISABLED | CardStatus::FORBIDDEN | CardStatus::PROC_COMPLETE)).bits());
+        }
+        if flags.contains(QueryFlag::LSCALE) {
+            body.push(stats.lscale);
+        }
+        if flags.contains(QueryFlag::RSCALE) {
+            body.push(stats.rscale);
+        }
+        if flags.contains(QueryFlag::LINK) {
+            let is_link = stats.type_.contains(CardType::LINK);
+            body.push(if is_link { stats.link } else { 0 });
+            body.push(if is_link { stats.link_marker } else { 0 });
+        }
+        let mut out = Vec::with_capacity(8 + body.len() * 4);
+        out.extend_from_slice(&((8 + body.len() * 4) as u32).to_le_bytes());
+        out.extend_from_slice(&flags.bits().to_le_bytes());
+        for v in body {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out
+    }
+
+    /// Card at a zone slot or stack index, if any.
+    pub fn card_at(&self, player: u8, location: Location, sequence: u8) -> Option<CardId> {
+        let p = player as usize;
+        let s = sequence as usize;
+        if player > 1 {
+            return None;
+        }
+        if location == Location::MZONE {
+            self.field.mzone[p].get(s).copied().flatten()
+        } else if location == Location::SZONE {
+            self.field.szone[p].get(s).copied().flatten()
+        } else {
+            self.field_stack(player, location).and_then(|v| v.get(s).copied())
+        }
+    }
+
+    /// query_card: one card record, or an empty record when nothing is there.
+    pub fn query_card(&self, player: u8, location: Location, sequence: u8, flags: QueryFlag) -> Vec<u8> {
+        match self.card_at(player, location, sequence) {
+            Some(card_id) => self.card_infos(card_id, flags),
+            None => EMPTY_RECORD.to_vec(),
+        }
+    }
+
+    /// query_field_count: number of cards in a stack location.
+    pub fn query_field_count(&self, player: u8, location: Location) -> u32 {
+        self.field_stack(player, location).map(|v| v.len() as u32).unwrap_or(0)
+    }
+
+    /// query_field_card: records for every slot of a zone, or every card of a stack.
+    pub fn query_field_card(&self, player: u8, location: Location, flags: QueryFlag) -> Vec<u8> {
+        let mut out = Vec::new();
+        if player > 1 {
+            return out;
+        }
+        let p = player as usize;
+        let slots: Vec<Option<CardId>> = if location == Location::MZONE {
+            self.field.mzone[p].to_vec()
+        } else if location == Location::SZONE {
+            self.field.szone[p].to_vec()
+        } else {
+            self.field_stack(player, location).map(|v| v.iter().copied().map(Some).collect()).unwrap_or_default()
+        };
+        for slot in slots {
+            match slot {
+                Some(card_id) => out.extend(self.card_infos(card_id, flags)),
+                None => out.extend_from_slice(&EMPTY_RECORD),
+            }
+        }
+        out
+    }
+
+    /// query_field_info: the MSG_RELOAD_FIELD snapshot of both players.
+    pub fn query_field_info(&self) -> Vec<u8> {
+        self.reload_field_message().encode()
+    }
+
+    fn info_location_u32(&self, card_id: CardId) -> u32 {
+        let loc = self.info_location(card_id);
+        u32::from_le_bytes([loc.controller, loc.location, loc.sequence, loc.position])
+    }
+
+    fn field_stack(&self, player: u8, location: Location) -> Option<&Vec<CardId>> {
+        let p = player as usize;
+        if player > 1 {
+            None
+        } else if location == Location::DECK {
+            Some(&self.field.deck[p])
+        } else if location == Location::HAND {
+            Some(&self.field.hand[p])
+        } else if location == Location::GRAVE {
+            Some(&self.field.grave[p])
+        } else if location == Location::REMOVED {
+            Some(&self.field.remove[p])
+        } else if location == Location::EXTRA {
+            Some(&self.field.extra[p])
+        } else {
+            None
+        }
+    }
+}
+
+/// Xyz and Link monsters (and NO_LEVEL cards) have no level.
+fn query_level(card: &Card) -> u32 {
+    let stats = &card.current_stats;
+    if stats.type_.intersects(CardType::XYZ | CardType::LINK) || card.has_status(CardStatus::NO_LEVEL) {
+        0
+    } else {
+        stats.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::{CardPosition, CardStatus, CardType, Location, QueryFlag};
+    use crate::core::messages::{parse_packet, MsgReloadField, MsgType};
+
+    fn words(bytes: &[u8]) -> Vec<u32> {
+        bytes.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+    }
+
+    #[test]
+    fn query_card_layout() {
+        let duel = Duel::new(0);
+        let mut data = duel.data.lock().unwrap();
+        let xyz = data.new_card(4001, 0);
+        let equip = data.new_card(4002, 0);
+        {
+            let c = &mut data.cards[xyz.0 as usize];
+            c.current_stats.type_ = CardType::MONSTER | CardType::XYZ;
+            c.current_stats.level = 4;
+            c.current_stats.rank = 4;
+            c.current_stats.attack = 2500;
+            c.counters.insert(0x1, 2);
+        }
+        data.place_card(xyz, 0, Location::MZONE, 1, CardPosition::FACEUP_ATTACK);
+        data.place_card(equip, 0, Location::SZONE, 3, CardPosition::FACEUP_ATTACK);
+        data.cards[equip.0 as usize].equip_target = Some(xyz);
+
+        let flags = QueryFlag::CODE | QueryFlag::POSITION | QueryFlag::LEVEL | QueryFlag::RANK | QueryFlag::ATTACK | QueryFlag::COUNTERS;
+        let rec = words(&data.query_card(0, Location::MZONE, 1, flags));
+        assert_eq!(rec, vec![4 * 9, flags.bits(), 4001, 0x01 << 24 | 1 << 16 | 0x04 << 8, 0, 4, 2500, 1, 0x1 | 2 << 16]);
+
+        // Equip flag is dropped when the card is not equipped
+        let rec = words(&data.query_card(0, Location::MZONE, 1, QueryFlag::EQUIP_CARD));
+        assert_eq!(rec, vec![8, 0]);
+        let rec = words(&data.query_card(0, Location::SZONE, 3, QueryFlag::EQUIP_CARD));
+        assert_eq!(rec, vec![12, QueryFlag::EQUIP_CARD.bits(), 0x01 << 24 | 1 << 16 | 0x04 << 8]);
+
+        // Only the statuses clients show are reported
+        data.cards[xyz.0 as usize].set_status(CardStatus::FORBIDDEN | CardStatus::SUMMON_TURN);
+        let rec = words(&data.query_card(0, Location::MZONE, 1, QueryFlag::STATUS));
+        assert_eq!(rec, vec![12, QueryFlag::STATUS.bits(), CardStatus::FORBIDDEN.bits()]);
+
+        assert_eq!(data.query_card(0, Location::MZONE, 0, flags), vec![4, 0, 0, 0], "empty zone");
+    }
+
+    #[test]
+    fn query_field_card_and_info() {
+        let mut duel = Duel::new(0);
+        duel.create_card(5001, 1);
+        duel.create_card(5002, 1);
+        let data = duel.data.lock().unwrap();
+
+        let zone = data.query_field_card(0, Location::MZONE, QueryFlag::CODE);
+        assert_eq!(zone.len(), 7 * 4, "seven empty monster zones");
+        let deck = words(&data.query_field_card(1, Location::DECK, QueryFlag::CODE));
+        assert_eq!(deck.len(), 6);
+        assert_eq!(deck[0], 12);
+        assert_eq!(data.query_field_count(1, Location::DECK), 2);
+
+        let info = data.query_field_info();
+        let (ty, payload) = parse_packet(&info);
+        assert_eq!(ty, MsgType::ReloadField);
+        let msg = MsgReloadField::parse(payload).expect("field info parses as MSG_RELOAD_FIELD");
+        assert_eq!(msg.players[1].deck, 2);
+        assert_eq!(msg.players[0].lp, 8000);
+    }
+}
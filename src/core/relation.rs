@@ -0,0 +1,355 @@
+//! The Field Zone, equip cards and card relations: continuous targets (SetCardTarget) and the
+//! card/effect relations behind IsRelateToEffect and IsRelateToChain.
+
+use crate::core::chain::ChainLink;
+use crate::core::duel::{Duel, DuelData};
+use crate::core::enums::*;
+use crate::core::group::Group;
+use crate::core::messages::{MsgCardTarget, MsgEquip, MsgType, MsgUnequip};
+use crate::core::prompt::{ready, set_prompting};
+use crate::core::replace::send_to;
+use crate::core::scripting::duel_data;
+use crate::core::types::{CardId, EffectId};
+use crate::core::zone::select_place;
+use mlua::Lua;
+
+impl DuelData {
+    /// Card at a player's location and sequence; FZONE and PZONE are looked up by their own numbering.
+    pub fn field_card(&self, player: u8, location: Location, sequence: u8) -> Option<CardId> {
+        let p = player as usize;
+        let seq = sequence as usize;
+        if location == Location::MZONE {
+            self.field.mzone.get(p)?.get(seq).copied().flatten()
+        } else if location == Location::SZONE {
+            self.field.szone.get(p)?.get(seq).copied().flatten()
+        } else if location == Location::FZONE {
+            self.field.szone.get(p)?.get(5).copied().flatten()
+        } else if location == Location::PZONE {
+            self.pzone_card(player, sequence)
+        } else if location == Location::DECK {
+            self.field.deck.get(p)?.get(seq).copied()
+        } else if location == Location::HAND {
+            self.field.hand.get(p)?.get(seq).copied()
+        } else if location == Location::GRAVE {
+            self.field.grave.get(p)?.get(seq).copied()
+        } else if location == Location::REMOVED {
+            self.field.remove.get(p)?.get(seq).copied()
+        } else if location == Location::EXTRA {
+            self.field.extra.get(p)?.get(seq).copied()
+        } else {
+            None
+        }
+    }
+
+    /// Field Spells that have to leave to make room in `player`'s Field Zone for `incoming`: the one already there.
+    /// Before Master Rule 3 only one Field Spell may be on the whole field, so the opponent's goes as well.
+    pub fn displaced_field_spells(&self, player: u8, incoming: CardId) -> Vec<CardId> {
+        let mut players = vec![player];
+        if self.duel_rule <= 2 {
+            players.push(1 - player);
+        }
+        players.into_iter()
+            .filter_map(|p| self.field_card(p, Location::FZONE, 0).filter(|&id| id != incoming))
+            .collect()
+    }
+
+    /// Whether `equip` could be equipped to `target`, a face-up monster.
+    pub fn can_equip(&self, equip: CardId, target: CardId) -> bool {
+        let Some(t) = self.cards.get(target.0 as usize) else {
+            return false;
+        };
+        equip != target && t.location == Location::MZONE && t.position.intersects(CardPosition::FACEUP) && self.get_card(equip).is_some()
+    }
+
+    /// Whether a card sits in one of the five spell & trap zones, where an equip card stays when it is equipped.
+    pub fn is_in_szone(&self, card_id: CardId) -> bool {
+        self.get_card(card_id).is_some_and(|c| c.location == Location::SZONE && c.sequence < 5)
+    }
+
+    /// Equip `equip` to the face-up monster `target`, putting it into a spell & trap zone of `player` first
+    /// (the one picked beforehand, see zone::select_place) unless it is already in one. A previous target
+    /// is let go of. Returns false if nothing was equipped.
+    pub fn equip(&mut self, player: u8, equip: CardId, target: CardId) -> bool {
+        if !self.can_equip(equip, target) {
+            return false;
+        }
+        if !self.is_in_szone(equip) && !self.send_card_to(equip, player, Location::SZONE, REASON_EFFECT) {
+            return false;
+        }
+        self.unequip(equip);
+        let card = &mut self.cards[equip.0 as usize];
+        card.position = CardPosition::FACEUP;
+        card.equip_target = Some(target);
+        self.cards[target.0 as usize].equip_cards.push(equip);
+        let msg = MsgEquip { card: self.info_location(equip), target: self.info_location(target) };
+        self.write_message(&msg.encode());
+        true
+    }
+
+    /// Let go of an equip card's target, announcing it with MSG_UNEQUIP.
+    pub fn unequip(&mut self, equip: CardId) {
+        let Some(target) = self.cards.get_mut(equip.0 as usize).and_then(|c| c.equip_target.take()) else {
+            return;
+        };
+        self.cards[target.0 as usize].equip_cards.retain(|&id| id != equip);
+        let msg = MsgUnequip { card: self.info_location(equip) };
+        self.write_message(&msg.encode());
+    }
+
+    /// `card` keeps targeting `target` (SetCardTarget), announced with MSG_CARD_TARGET.
+    pub fn set_card_target(&mut self, card: CardId, target: CardId) {
+        if self.cards.get(card.0 as usize).is_none_or(|c| c.effect_target_cards.contains(&target)) || self.cards.get(target.0 as usize).is_none() {
+            return;
+        }
+        self.cards[card.0 as usize].effect_target_cards.push(target);
+        self.cards[target.0 as usize].effect_target_owners.push(card);
+        let msg = MsgCardTarget { card: self.info_location(card), target: self.info_location(target) };
+        self.write_message(&msg.encode());
+    }
+
+    /// Stop `card` targeting `target`, announced with MSG_CANCEL_TARGET.
+    pub fn cancel_card_target(&mut self, card: CardId, target: CardId) {
+        if self.cards.get(card.0 as usize).is_none_or(|c| !c.effect_target_cards.contains(&target)) {
+            return;
+        }
+        self.cards[card.0 as usize].effect_target_cards.retain(|&id| id != target);
+        self.cards[target.0 as usize].effect_target_owners.retain(|&id| id != card);
+        let msg = MsgCardTarget { card: self.info_location(card), target: self.info_location(target) };
+        self.write_message(&msg.encode_as(MsgType::CancelTarget));
+    }
+
+    /// Break the equip and target relations of a card that left the field.
+    /// Returns its equip cards still on the field, which lost their target and have to be destroyed.
+    pub fn leave_field_relations(&mut self, card_id: CardId) -> Vec<CardId> {
+        let Some(card) = self.cards.get_mut(card_id.0 as usize) else {
+            return Vec::new();
+        };
+        let target = card.equip_target.take();
+        let equips = std::mem::take(&mut card.equip_cards);
+        let targets = std::mem::take(&mut card.effect_target_cards);
+        let owners = std::mem::take(&mut card.effect_target_owners);
+        if let Some(target) = target {
+            self.cards[target.0 as usize].equip_cards.retain(|&id| id != card_id);
+        }
+        for target in targets {
+            self.cards[target.0 as usize].effect_target_owners.retain(|&id| id != card_id);
+        }
+        for owner in owners {
+            self.cards[owner.0 as usize].effect_target_cards.retain(|&id| id != card_id);
+        }
+        let mut orphans = Vec::new();
+        for equip in equips {
+            self.cards[equip.0 as usize].equip_target = None;
+            if self.cards[equip.0 as usize].location.intersects(Location::ONFIELD) {
+                let msg = MsgUnequip { card: self.info_location(equip) };
+                self.write_message(&msg.encode());
+                orphans.push(equip);
+            }
+        }
+        orphans
+    }
+
+    /// Forget the card and effect relations of a card that moved, and who it had been confirmed to.
+    pub fn drop_relations(&mut self, card_id: CardId) {
+        if let Some(card) = self.cards.get_mut(card_id.0 as usize) {
+            card.card_relations.clear();
+            card.effect_relations.clear();
+            card.confirmed_to = 0;
+        }
+    }
+
+    /// Relate a card to an effect (CreateEffectRelation).
+    pub fn relate_to_effect(&mut self, card_id: CardId, effect: EffectId) {
+        if let Some(card) = self.cards.get_mut(card_id.0 as usize) {
+            if !card.effect_relations.contains(&effect) {
+                card.effect_relations.push(effect);
+            }
+        }
+    }
+
+    /// Relations a new chain link brings: an activated card and the link's targets relate to its effect.
+    pub fn relate_chain_link(&mut self, link: &ChainLink) {
+        let activated = self.effects.get(link.effect_id.0 as usize)
+            .filter(|e| e.type_ & EFFECT_TYPE_ACTIVATE != 0)
+            .map(|e| e.owner);
+        let targets = link.target_cards.iter().flat_map(|g| g.0.iter().copied());
+        for card_id in activated.into_iter().chain(targets).collect::<Vec<_>>() {
+            self.relate_to_effect(card_id, link.effect_id);
+        }
+    }
+
+    /// Effect of a chain link: 0 is the one resolving now, otherwise the 1-based link number.
+    pub fn chain_effect(&self, index: usize) -> Option<EffectId> {
+        let link = if index == 0 {
+            self.current_chain_link.as_ref().or(self.chain.links.last())
+        } else {
+            self.chain.links.get(index - 1)
+        };
+        link.map(|l| l.effect_id)
+    }
+}
+
+/// Register the Field Zone and equip functions on the global `Duel` table.
+pub fn register_relation_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.Equip(player, equip_card, target) -> bool, raising EVENT_EQUIP
+    set_prompting(lua, &duel_table, "Equip", |lua, (player, equip, target): (u8, CardId, CardId)| {
+        let zones = {
+            let data = duel_data(lua);
+            let data_guard = data.lock().unwrap();
+            if !data_guard.can_equip(equip, target) {
+                return ready(lua, false);
+            }
+            // An equip card already in a spell & trap zone stays there
+            if data_guard.is_in_szone(equip) { None } else { Some(data_guard.useable_zones(player, Location::SZONE, 0x1f)) }
+        };
+        let equip_to = move |lua: &Lua| {
+            let data = duel_data(lua);
+            let equipped = data.lock().unwrap().equip(player, equip, target);
+            if equipped {
+                Duel::raise_event_static(lua, data, EVENT_EQUIP, Some(Group([equip].into_iter().collect())), player, None);
+            }
+            equipped
+        };
+        let Some(zones) = zones else {
+            return ready(lua, equip_to(lua));
+        };
+        select_place(lua, player, Location::SZONE, zones, move |lua, seq| {
+            duel_data(lua).lock().unwrap().cards[equip.0 as usize].to_field_sequence = seq;
+            ready(lua, seq.is_some() && equip_to(lua))
+        })
+    })?;
+
+    // Duel.MoveToField(c, move_player, target_player, dest, pos, enabled) -> bool
+    set_prompting(lua, &duel_table, "MoveToField", |lua, (card_id, _move_player, target_player, dest, pos): (CardId, u8, u8, u32, u32)| {
+        let location = Location::from_bits_truncate(dest);
+        let move_to = move |lua: &Lua, seq: Option<u8>| -> mlua::Result<bool> {
+            let data = duel_data(lua);
+            let (old_position, (reason_player, effect)) = {
+                let mut data_guard = data.lock().unwrap();
+                let Some(old_position) = data_guard.get_card(card_id).map(|c| c.position) else {
+                    return Ok(false);
+                };
+                // Set the position first so MSG_MOVE already shows it
+                let card = &mut data_guard.cards[card_id.0 as usize];
+                card.position = CardPosition::from_bits_truncate(pos);
+                card.to_field_sequence = seq;
+                (old_position, data_guard.reason_context())
+            };
+            let moved = !send_to(lua, &[card_id], Some(target_player), location, None, REASON_EFFECT, reason_player, effect)?.0.is_empty();
+            if !moved {
+                data.lock().unwrap().cards[card_id.0 as usize].position = old_position;
+            }
+            Ok(moved)
+        };
+        // The Field Zone and the pendulum zones leave nothing to pick
+        if location != Location::MZONE && location != Location::SZONE {
+            return ready(lua, move_to(lua, None)?);
+        }
+        let zones = duel_data(lua).lock().unwrap().useable_zones(target_player, location, 0x1f);
+        select_place(lua, target_player, location, zones, move |lua, seq| {
+            ready(lua, seq.is_some() && move_to(lua, seq)?)
+        })
+    })?;
+
+    // Duel.GetFieldCard(player, location, seq) -> Card or nil
+    duel_table.set("GetFieldCard", lua.create_function(|lua, (player, location, seq): (u8, u32, u8)| {
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        Ok(data_guard.field_card(player, Location::from_bits_truncate(location), seq))
+    })?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::messages::{MsgCardTarget, MsgUnequip};
+    use crate::core::processor::ProcessResult;
+    use crate::core::types::CardId;
+
+    #[test]
+    fn field_spells_replace_each_other() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9801, 0, 0, LOCATION_FZONE, 0, POS_FACEUP)
+            Debug.AddCard(9802, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9803, 1, 1, LOCATION_FZONE, 0, POS_FACEUP)
+        "#).exec().expect("setup");
+        let (moved, field): (bool, CardId) = duel.lua.load(r#"
+            local moved = Duel.MoveToField(Card(1), 0, 0, LOCATION_FZONE, POS_FACEUP, true)
+            return moved, Duel.GetFieldCard(0, LOCATION_FZONE, 0)
+        "#).eval().unwrap();
+        assert!(moved);
+        assert_eq!(field, CardId::new(1));
+        {
+            let mut data = duel.data.lock().unwrap();
+            let new = data.get_card(CardId::new(1)).unwrap();
+            assert_eq!((new.location, new.sequence, new.position.bits()), (Location::SZONE, 5, CardPosition::FACEUP.bits()));
+            let old = data.get_card(CardId::new(0)).unwrap();
+            assert_eq!((old.location, old.reason), (Location::GRAVE, REASON_RULE));
+            assert_eq!(data.get_card(CardId::new(2)).unwrap().location, Location::SZONE, "the opponent keeps theirs");
+            // Under the old master rules the opponent's Field Spell goes too
+            data.duel_rule = 2;
+        }
+        duel.lua.load("Duel.MoveToField(Card(0), 0, 0, LOCATION_FZONE, POS_FACEUP, true)").exec().unwrap();
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.get_card(CardId::new(0)).unwrap().location, Location::SZONE);
+        assert_eq!(data.get_card(CardId::new(1)).unwrap().location, Location::GRAVE);
+        assert_eq!(data.get_card(CardId::new(2)).unwrap().location, Location::GRAVE);
+    }
+
+    #[test]
+    fn equips_and_card_targets_follow_their_target() {
+        let mut duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9811, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(9812, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9813, 0, 0, LOCATION_SZONE, 1, POS_FACEUP)
+            Debug.AddCard(9814, 1, 1, LOCATION_MZONE, 0, POS_FACEDOWN_DEFENSE)
+            local e = Effect.CreateEffect(Card(1))
+            e:SetType(EFFECT_TYPE_SINGLE)
+            e:SetCode(EVENT_DESTROYED)
+            Card(1):RegisterEffect(e)
+        "#).exec().expect("setup");
+        duel.execute_operation(duel.lua.load(r#"
+            equipped = Duel.Equip(0, Card(1), Card(0))
+            refused = Duel.Equip(0, Card(2), Card(3))
+        "#).into_function().unwrap()).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the equip card from the hand needs a zone");
+        duel.set_responseb(&[0, Location::SZONE.bits() as u8, 3]);
+        assert_eq!(duel.process(), ProcessResult::Continue, "the one already on the field does not");
+        let (equipped, refused, target, count, targets): (bool, bool, CardId, u32, u32) = duel.lua.load(r#"
+            Card(2):SetCardTarget(Card(0))
+            return equipped, refused, Card(1):GetEquipTarget(), Card(0):GetEquipGroup():GetCount(), Card(2):GetCardTargetCount()
+        "#).eval().unwrap();
+        assert!(equipped);
+        assert!(!refused, "face-down monsters cannot be equipped");
+        assert_eq!((target, count, targets), (CardId::new(0), 1, 1));
+        let mut data = duel.data.lock().unwrap();
+        let equip = data.get_card(CardId::new(1)).unwrap();
+        assert_eq!((equip.location, equip.sequence), (Location::SZONE, 3));
+        assert!(equip.position.intersects(CardPosition::FACEUP));
+        let continuous = data.info_location(CardId::new(2));
+        let monster = data.info_location(CardId::new(0));
+        let announced = MsgCardTarget { card: continuous, target: monster }.encode();
+        assert!(data.message_buffer.ends_with(&announced));
+
+        // The monster leaving destroys its equip and ends the continuous target
+        data.message_buffer.clear();
+        let equip_place = data.info_location(CardId::new(1));
+        drop(data);
+        duel.lua.load("Duel.SendtoGrave(Card(0), REASON_EFFECT)").exec().unwrap();
+        let data = duel.data.lock().unwrap();
+        let equip = data.get_card(CardId::new(1)).unwrap();
+        assert_eq!(equip.location, Location::GRAVE);
+        assert_eq!(equip.reason, REASON_RULE | REASON_LOST_TARGET | REASON_DESTROY);
+        assert!(data.triggered_effects.iter().any(|e| data.effects[e.0 as usize].code == EVENT_DESTROYED), "EVENT_DESTROYED was raised");
+        assert_eq!(equip.equip_target, None);
+        assert!(data.get_card(CardId::new(2)).unwrap().effect_target_cards.is_empty());
+        assert!(data.message_buffer.windows(5).any(|w| w == MsgUnequip { card: equip_place }.encode().as_slice()));
+    }
+}
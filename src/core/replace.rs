@@ -0,0 +1,556 @@
+//! The card movement pipeline: destroying and sending cards through indestructibility,
+//! destroy substitutes, destroy and send replacements and leave-field redirects before they move.
+
+use crate::core::duel::{Duel, DuelData};
+use crate::core::enums::*;
+use crate::core::group::{cards_of, Group};
+use crate::core::scripting::{duel_data, effect_function, effect_value};
+use crate::core::types::{CardId, EffectId};
+use mlua::Lua;
+use std::sync::{Arc, Mutex};
+
+impl DuelData {
+    /// Effects with `code` that may apply to a card: its own single effects, equip effects of its
+    /// equip cards and active field effects whose target range covers its location. Targets are not checked.
+    pub fn affecting_effects(&self, card_id: CardId, code: u32) -> Vec<EffectId> {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return Vec::new();
+        };
+        let own = card.effects.iter().copied()
+            .filter(|eid| self.effects.get(eid.0 as usize)
+                .is_some_and(|e| e.code == code && e.type_ & (EFFECT_TYPE_FIELD | EFFECT_TYPE_EQUIP) == 0));
+        let equipped = card.equip_cards.iter()
+            .filter_map(|equip| self.cards.get(equip.0 as usize))
+            .flat_map(|equip| equip.effects.iter().copied())
+            .filter(|eid| self.effects.get(eid.0 as usize)
+                .is_some_and(|e| e.code == code && e.type_ & EFFECT_TYPE_EQUIP != 0));
+        let field = self.field_effects(code).into_iter().filter(|eid| {
+            let e = &self.effects[eid.0 as usize];
+            let source = e.player.unwrap_or_else(|| self.cards.get(e.owner.0 as usize).map(|c| c.controller).unwrap_or(0));
+            let range = if source == card.controller { e.target_range.0 } else { e.target_range.1 };
+            card.location.bits() & range != 0
+        });
+        own.chain(equipped).chain(field).collect()
+    }
+
+    /// Active field effects with `code` that are not aimed at players.
+    pub fn field_effects(&self, code: u32) -> Vec<EffectId> {
+        self.effects.iter().enumerate()
+            .filter(|(_, e)| e.code == code && e.type_ & EFFECT_TYPE_FIELD != 0 && e.flag & EFFECT_FLAG_PLAYER_TARGET == 0)
+            .filter(|(_, e)| self.is_effect_active(e))
+            .map(|(i, _)| EffectId::new(i as u32))
+            .collect()
+    }
+
+    /// Record why and where a card is about to move, so replacements and redirects can ask it.
+    /// Returns the reason and reason player it had before.
+    pub fn set_move_reason(&mut self, card_id: CardId, reason: u32, reason_player: u8, destination: Location) -> (u32, u8) {
+        let Some(card) = self.cards.get_mut(card_id.0 as usize) else {
+            return (0, 0);
+        };
+        let previous = (card.reason, card.reason_player);
+        card.reason = reason;
+        card.reason_player = reason_player;
+        card.destination = destination;
+        previous
+    }
+
+    /// Put back the reason of a card whose move was replaced.
+    pub fn restore_move_reason(&mut self, card_id: CardId, (reason, reason_player): (u32, u8)) {
+        if let Some(card) = self.cards.get_mut(card_id.0 as usize) {
+            card.reason = reason;
+            card.reason_player = reason_player;
+            card.destination = Location::empty();
+        }
+    }
+
+    /// Player and effect behind a move made now: the resolving chain link's, or the turn player's.
+    pub fn reason_context(&self) -> (u8, Option<EffectId>) {
+        match &self.current_chain_link {
+            Some(link) => (link.trigger_player, Some(link.effect_id)),
+            None => (self.turn_player, None),
+        }
+    }
+}
+
+/// Controller of the card handling an effect, or the player it was registered to.
+fn handler_player(data: &Arc<Mutex<DuelData>>, eid: EffectId) -> u8 {
+    let data_guard = data.lock().unwrap();
+    let Some(effect) = data_guard.effects.get(eid.0 as usize) else {
+        return 0;
+    };
+    effect.player.unwrap_or_else(|| data_guard.cards.get(effect.owner.0 as usize).map(|c| c.controller).unwrap_or(0))
+}
+
+/// Effects with `code` that apply to a card right now: their condition holds and field effects' targets accept it.
+pub fn card_affecting_effects(lua: &Lua, card_id: CardId, code: u32) -> mlua::Result<Vec<EffectId>> {
+    let data = duel_data(lua);
+    let candidates = data.lock().unwrap().affecting_effects(card_id, code);
+    let mut out = Vec::new();
+    for eid in candidates {
+        if let Some(condition) = effect_function(lua, &data, eid, |e| &e.condition) {
+            if !condition.call::<_, bool>(eid)? {
+                continue;
+            }
+        }
+        let is_field = data.lock().unwrap().effects[eid.0 as usize].type_ & EFFECT_TYPE_FIELD != 0;
+        if is_field {
+            if let Some(target) = effect_function(lua, &data, eid, |e| &e.target) {
+                if !target.call::<_, bool>((eid, card_id))? {
+                    continue;
+                }
+            }
+        }
+        out.push(eid);
+    }
+    Ok(out)
+}
+
+/// Whether a card survives being destroyed for `reason` (EFFECT_INDESTRUCTABLE, and _EFFECT or _BATTLE
+/// depending on the reason). Rule destruction ignores all of them.
+pub fn is_indestructible(lua: &Lua, card_id: CardId, reason: u32, reason_player: u8, reason_effect: Option<EffectId>) -> mlua::Result<bool> {
+    if reason & REASON_RULE != 0 {
+        return Ok(false);
+    }
+    let data = duel_data(lua);
+    for eid in card_affecting_effects(lua, card_id, EFFECT_INDESTRUCTABLE)? {
+        if effect_value(lua, &data, eid, (eid, reason_effect, reason, reason_player))? != 0 {
+            return Ok(true);
+        }
+    }
+    if reason & REASON_EFFECT != 0 {
+        for eid in card_affecting_effects(lua, card_id, EFFECT_INDESTRUCTABLE_EFFECT)? {
+            if effect_value(lua, &data, eid, (eid, reason_effect, reason_player))? != 0 {
+                return Ok(true);
+            }
+        }
+    }
+    if reason & REASON_BATTLE != 0 {
+        for eid in card_affecting_effects(lua, card_id, EFFECT_INDESTRUCTABLE_BATTLE)? {
+            if effect_value(lua, &data, eid, (eid, mlua::Nil))? != 0 {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Ask a replacement effect whether it replaces the move of `eg` (its target with chk 0, then chk 1)
+/// and run its operation if it does.
+fn run_replacement(lua: &Lua, data: &Arc<Mutex<DuelData>>, eid: EffectId, eg: &[CardId], reason: u32, reason_player: u8, reason_effect: Option<EffectId>) -> mlua::Result<bool> {
+    let tp = handler_player(data, eid);
+    let eg = Group(eg.iter().copied().collect());
+    if let Some(target) = effect_function(lua, data, eid, |e| &e.target) {
+        for chk in [0u32, 1] {
+            if !target.call::<_, bool>((eid, tp, eg.clone(), reason_player, 0u32, reason_effect, reason, reason_player, chk))? {
+                return Ok(false);
+            }
+        }
+    }
+    if let Some(operation) = effect_function(lua, data, eid, |e| &e.operation) {
+        operation.call::<_, ()>((eid, tp, eg, reason_player, 0u32, reason_effect, reason, reason_player))?;
+    }
+    Ok(true)
+}
+
+/// Apply the replacement effects with `code` (EFFECT_DESTROY_REPLACE or EFFECT_SEND_REPLACE) and return
+/// the cards that still move. A card's own replacements cover only it; field replacements cover the
+/// cards their value accepts.
+fn apply_replacements(lua: &Lua, code: u32, targets: Vec<CardId>, reason: u32, reason_player: u8, reason_effect: Option<EffectId>) -> mlua::Result<Vec<CardId>> {
+    let data = duel_data(lua);
+    let mut remaining = targets;
+    for card_id in remaining.clone() {
+        let own = data.lock().unwrap().card_effects(card_id, code);
+        for eid in own {
+            if run_replacement(lua, &data, eid, &[card_id], reason, reason_player, reason_effect)? {
+                remaining.retain(|&c| c != card_id);
+                break;
+            }
+        }
+    }
+    let field = data.lock().unwrap().field_effects(code);
+    for eid in field {
+        let mut covered = Vec::new();
+        for &card_id in &remaining {
+            if effect_value(lua, &data, eid, (eid, card_id))? != 0 {
+                covered.push(card_id);
+            }
+        }
+        if !covered.is_empty() && run_replacement(lua, &data, eid, &remaining, reason, reason_player, reason_effect)? {
+            remaining.retain(|c| !covered.contains(c));
+        }
+    }
+    Ok(remaining)
+}
+
+/// Where a card sent to `location` really goes. A card leaving the field follows
+/// EFFECT_LEAVE_FIELD_REDIRECT first (banishment, then Deck, hand and GY take precedence in that order),
+/// then the redirect of whatever its destination is now.
+pub fn redirect(lua: &Lua, card_id: CardId, location: Location) -> mlua::Result<Location> {
+    let data = duel_data(lua);
+    let (on_field, token) = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(location);
+        };
+        (card.location.intersects(Location::ONFIELD), card.current_stats.type_.contains(CardType::TOKEN))
+    };
+    if token {
+        return Ok(location);
+    }
+    let mut location = location;
+    if on_field && !location.intersects(Location::ONFIELD) {
+        let mut redirects = 0;
+        for eid in card_affecting_effects(lua, card_id, EFFECT_LEAVE_FIELD_REDIRECT)? {
+            redirects |= effect_value(lua, &data, eid, (eid, card_id))?;
+        }
+        if let Some(to) = [Location::REMOVED, Location::DECK, Location::HAND, Location::GRAVE].into_iter().find(|to| redirects & to.bits() != 0) {
+            location = to;
+        }
+    }
+    let code = match location {
+        Location::HAND => EFFECT_TO_HAND_REDIRECT,
+        Location::DECK => EFFECT_TO_DECK_REDIRECT,
+        Location::GRAVE => EFFECT_TO_GRAVE_REDIRECT,
+        Location::REMOVED => EFFECT_REMOVE_REDIRECT,
+        _ => return Ok(location),
+    };
+    for eid in card_affecting_effects(lua, card_id, code)? {
+        let to = effect_value(lua, &data, eid, (eid, card_id))?;
+        if to != 0 {
+            return Ok(Location::from_bits_truncate(to));
+        }
+    }
+    Ok(location)
+}
+
+/// Send cards to `location` of `player` (each card's owner when None): send replacements first,
+/// then redirects, then the move itself. Each moved card keeps its final reason, with REASON_REDIRECT
+/// when it ended up somewhere else. Raises the event of each destination, then EVENT_DISCARD for
+/// cards discarded from the hand, and returns the moved cards. LOCATION_DECKBOT and LOCATION_DECKSHF count as the Deck until the cards are placed.
+#[allow(clippy::too_many_arguments)]
+pub fn send_to(lua: &Lua, targets: &[CardId], player: Option<u8>, placement: Location, position: Option<CardPosition>, reason: u32, reason_player: u8, reason_effect: Option<EffectId>) -> mlua::Result<Group> {
+    let data = duel_data(lua);
+    let location = if placement.contains(Location::DECK) { Location::DECK } else { placement };
+    let previous: Vec<(CardId, (u32, u8))> = {
+        let mut data_guard = data.lock().unwrap();
+        let known: Vec<CardId> = targets.iter().copied().filter(|&id| data_guard.get_card(id).is_some()).collect();
+        known.into_iter().map(|id| (id, data_guard.set_move_reason(id, reason, reason_player, location))).collect()
+    };
+    let candidates = previous.iter().map(|&(id, _)| id).collect();
+    let remaining = apply_replacements(lua, EFFECT_SEND_REPLACE, candidates, reason, reason_player, reason_effect)?;
+    // Every destination is settled before anything moves, as moving can switch redirects off
+    let mut destinations = Vec::new();
+    for &(card_id, before) in &previous {
+        if remaining.contains(&card_id) {
+            destinations.push((card_id, redirect(lua, card_id, location)?));
+        } else {
+            data.lock().unwrap().restore_move_reason(card_id, before);
+        }
+    }
+    let mut moved = Group::new();
+    let mut discarded = Group::new();
+    let mut overlays = Vec::new();
+    let mut equips = Vec::new();
+    for (card_id, to) in destinations {
+        let reason = if to != location { reason | REASON_REDIRECT } else { reason };
+        let player = player.unwrap_or(data.lock().unwrap().cards[card_id.0 as usize].owner);
+        let to = if to == location { placement } else { to };
+        // The Field Spell already in the zone makes way first
+        if to == Location::FZONE {
+            let displaced = data.lock().unwrap().displaced_field_spells(player, card_id);
+            if !displaced.is_empty() {
+                send_to(lua, &displaced, None, Location::GRAVE, None, REASON_RULE, reason_player, reason_effect)?;
+            }
+        }
+        let mut data_guard = data.lock().unwrap();
+        let card = &mut data_guard.cards[card_id.0 as usize];
+        let from_hand = card.location == Location::HAND;
+        let left_field = card.location.intersects(Location::ONFIELD) && !to.intersects(Location::ONFIELD);
+        card.destination = if to.contains(Location::DECK) { Location::DECK } else { to };
+        // A redirected card arrives the way its new destination takes it
+        card.to_position = if reason & REASON_REDIRECT == 0 { position.clone() } else { None };
+        let (units, equipped) = (card.xyz_materials.clone(), card.equip_cards.clone());
+        if data_guard.send_card_to(card_id, player, to, reason) {
+            data_guard.cards[card_id.0 as usize].reason_player = reason_player;
+            moved.0.insert(card_id);
+            if from_hand && reason & REASON_DISCARD != 0 {
+                discarded.0.insert(card_id);
+            }
+            if left_field && !data_guard.cards[card_id.0 as usize].location.intersects(Location::ONFIELD) {
+                overlays.extend(units);
+                equips.extend(equipped);
+            }
+        }
+    }
+    for (to, event) in [(Location::GRAVE, EVENT_TO_GRAVE), (Location::REMOVED, EVENT_REMOVE), (Location::HAND, EVENT_TO_HAND), (Location::DECK | Location::EXTRA, EVENT_TO_DECK)] {
+        let arrived = {
+            let data_guard = data.lock().unwrap();
+            Group(moved.0.iter().copied().filter(|&id| data_guard.cards[id.0 as usize].location.intersects(to)).collect())
+        };
+        if !arrived.0.is_empty() {
+            Duel::raise_event_static(lua, data.clone(), event, Some(arrived), reason_player, reason_effect);
+        }
+    }
+    if !discarded.0.is_empty() {
+        Duel::raise_event_static(lua, data.clone(), EVENT_DISCARD, Some(discarded), reason_player, reason_effect);
+    }
+    // Overlay units do not follow their Xyz monster off the field, and equip cards that lost their target are destroyed
+    let (overlays, equips): (Vec<CardId>, Vec<CardId>) = {
+        let data_guard = data.lock().unwrap();
+        let overlays = overlays.into_iter().filter(|id| data_guard.cards[id.0 as usize].location == Location::OVERLAY).collect();
+        let equips = equips.into_iter().filter(|id| {
+            let equip = &data_guard.cards[id.0 as usize];
+            equip.equip_target.is_none() && equip.location.intersects(Location::ONFIELD)
+        }).collect();
+        (overlays, equips)
+    };
+    if !overlays.is_empty() {
+        send_to(lua, &overlays, None, Location::GRAVE, None, REASON_LOST_OVERLAY | REASON_RULE, reason_player, reason_effect)?;
+    }
+    if !equips.is_empty() {
+        destroy(lua, &equips, REASON_RULE | REASON_LOST_TARGET, reason_player, reason_effect, Location::GRAVE)?;
+    }
+    Ok(moved)
+}
+
+/// Destroy cards, sending them to `location`. Cards already in the GY or banished, overlay units,
+/// indestructible cards and cards saved by a substitute or a destroy replacement stay where they are;
+/// a substitute's own card is destroyed in their place. Raises EVENT_DESTROYED and returns the destroyed cards.
+pub fn destroy(lua: &Lua, targets: &[CardId], reason: u32, reason_player: u8, reason_effect: Option<EffectId>, location: Location) -> mlua::Result<Group> {
+    let data = duel_data(lua);
+    let reason = reason | REASON_DESTROY;
+    let mut doomed = Vec::new();
+    for &card_id in targets {
+        let before = {
+            let mut data_guard = data.lock().unwrap();
+            let destructable = data_guard.get_card(card_id)
+                .is_some_and(|c| !c.location.is_empty() && !c.location.intersects(Location::GRAVE | Location::REMOVED | Location::OVERLAY));
+            if !destructable {
+                continue;
+            }
+            data_guard.set_move_reason(card_id, reason, reason_player, location)
+        };
+        if is_indestructible(lua, card_id, reason, reason_player, reason_effect)? {
+            data.lock().unwrap().restore_move_reason(card_id, before);
+            continue;
+        }
+        doomed.push((card_id, before));
+    }
+    let mut substitutes = Vec::new();
+    if reason & REASON_RULE == 0 {
+        for (card_id, before) in doomed.clone() {
+            for eid in card_affecting_effects(lua, card_id, EFFECT_DESTROY_SUBSTITUTE)? {
+                let handler = data.lock().unwrap().effects[eid.0 as usize].owner;
+                // A substitute that is being destroyed itself cannot take anyone's place
+                if doomed.iter().any(|&(c, _)| c == handler) || substitutes.contains(&handler) {
+                    continue;
+                }
+                if effect_value(lua, &data, eid, (eid, reason_effect, reason, reason_player))? != 0 {
+                    substitutes.push(handler);
+                    doomed.retain(|&(c, _)| c != card_id);
+                    data.lock().unwrap().restore_move_reason(card_id, before);
+                    break;
+                }
+            }
+        }
+    }
+    let candidates = doomed.iter().map(|&(c, _)| c).collect();
+    let remaining = apply_replacements(lua, EFFECT_DESTROY_REPLACE, candidates, reason, reason_player, reason_effect)?;
+    for &(card_id, before) in &doomed {
+        if !remaining.contains(&card_id) {
+            data.lock().unwrap().restore_move_reason(card_id, before);
+        }
+    }
+    let mut destroyed = send_to(lua, &remaining, None, location, None, reason, reason_player, reason_effect)?;
+    destroyed.0.extend(send_to(lua, &substitutes, None, Location::GRAVE, None, reason | REASON_REPLACE, reason_player, reason_effect)?.0);
+    if !destroyed.0.is_empty() {
+        Duel::raise_event_static(lua, data, EVENT_DESTROYED, Some(destroyed.clone()), reason_player, reason_effect);
+    }
+    Ok(destroyed)
+}
+
+/// Register the card movement functions on the global `Duel` table.
+pub fn register_replace_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.Destroy(targets, reason[, dest]) -> number destroyed
+    duel_table.set("Destroy", lua.create_function(|lua, (targets, reason, dest): (mlua::Value, u32, Option<u32>)| {
+        let (player, effect) = duel_data(lua).lock().unwrap().reason_context();
+        let location = dest.map(Location::from_bits_truncate).unwrap_or(Location::GRAVE);
+        Ok(destroy(lua, &cards_of(&targets), reason, player, effect, location)?.0.len() as u32)
+    })?)?;
+
+    // Duel.Remove(targets, pos, reason) -> number banished, in position `pos` (face-up or face-down)
+    duel_table.set("Remove", lua.create_function(|lua, (targets, pos, reason): (mlua::Value, u32, u32)| {
+        let (player, effect) = duel_data(lua).lock().unwrap().reason_context();
+        let position = Some(CardPosition::from_bits_truncate(pos)).filter(|p| !p.is_empty());
+        Ok(send_to(lua, &cards_of(&targets), None, Location::REMOVED, position, reason, player, effect)?.0.len() as u32)
+    })?)?;
+
+    // Duel.SendtoHand(targets, player|nil, reason) -> number sent
+    duel_table.set("SendtoHand", lua.create_function(|lua, (targets, player, reason): (mlua::Value, Option<u8>, u32)| {
+        let (reason_player, effect) = duel_data(lua).lock().unwrap().reason_context();
+        Ok(send_to(lua, &cards_of(&targets), player, Location::HAND, None, reason, reason_player, effect)?.0.len() as u32)
+    })?)?;
+
+    // Duel.SendtoDeck(targets, player|nil, seq, reason) -> number sent; seq is SEQ_DECKTOP, SEQ_DECKBOTTOM or SEQ_DECKSHUFFLE
+    duel_table.set("SendtoDeck", lua.create_function(|lua, (targets, player, seq, reason): (mlua::Value, Option<u8>, i32, u32)| {
+        let (reason_player, effect) = duel_data(lua).lock().unwrap().reason_context();
+        let placement = match seq {
+            0 => Location::DECK,
+            1 => Location::DECKBOT,
+            _ => Location::DECKSHF,
+        };
+        Ok(send_to(lua, &cards_of(&targets), player, placement, None, reason, reason_player, effect)?.0.len() as u32)
+    })?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::types::CardId;
+
+    #[test]
+    fn indestructible_substituted_and_replaced_cards_survive_destruction() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9991, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(9992, 0, 0, LOCATION_MZONE, 1, POS_FACEUP_ATTACK)
+            Debug.AddCard(9993, 0, 0, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)
+            Debug.AddCard(9994, 0, 0, LOCATION_MZONE, 3, POS_FACEUP_ATTACK)
+            Debug.AddCard(9995, 0, 0, LOCATION_SZONE, 0, POS_FACEUP)
+            local e1 = Effect.CreateEffect(Card(0))
+            e1:SetType(EFFECT_TYPE_SINGLE)
+            e1:SetCode(EFFECT_INDESTRUCTABLE_EFFECT)
+            e1:SetValue(1)
+            Card(0):RegisterEffect(e1)
+            Duel.Equip(0, Card(4), Card(1))
+            local e2 = Effect.CreateEffect(Card(4))
+            e2:SetType(EFFECT_TYPE_EQUIP)
+            e2:SetCode(EFFECT_DESTROY_SUBSTITUTE)
+            e2:SetValue(1)
+            Card(4):RegisterEffect(e2)
+            local e3 = Effect.CreateEffect(Card(2))
+            e3:SetType(EFFECT_TYPE_SINGLE + EFFECT_TYPE_CONTINUOUS)
+            e3:SetCode(EFFECT_DESTROY_REPLACE)
+            e3:SetTarget(function(e, tp, eg, ep, ev, re, r, rp, chk)
+                return e:GetHandler():IsReason(REASON_EFFECT)
+            end)
+            e3:SetOperation(function(e) e:SetLabel(1) end)
+            Card(2):RegisterEffect(e3)
+        "#).exec().expect("setup");
+        let (count, rule): (u32, u32) = duel.lua.load(r#"
+            local g = Group.CreateGroup()
+            for i = 0, 3 do g:AddCard(Card(i)) end
+            return Duel.Destroy(g, REASON_EFFECT), Duel.Destroy(Card(0), REASON_RULE)
+        "#).eval().unwrap();
+        assert_eq!((count, rule), (2, 1));
+        let data = duel.data.lock().unwrap();
+        let location = |id: u32| data.get_card(CardId::new(id)).unwrap().location;
+        assert_eq!(location(1), Location::MZONE, "the equip card was destroyed instead");
+        assert_eq!(location(2), Location::MZONE);
+        assert_eq!(location(3), Location::GRAVE);
+        assert_eq!(location(4), Location::GRAVE);
+        assert_eq!(location(0), Location::GRAVE, "rule destruction ignores indestructibility");
+        let equip = data.get_card(CardId::new(4)).unwrap();
+        assert_eq!(equip.reason & (REASON_DESTROY | REASON_REPLACE), REASON_DESTROY | REASON_REPLACE);
+        assert_eq!(data.get_card(CardId::new(2)).unwrap().reason, 0, "a replaced destruction leaves no reason behind");
+        assert_eq!(data.get_card(CardId::new(3)).unwrap().reason, REASON_EFFECT | REASON_DESTROY);
+    }
+
+    #[test]
+    fn leaving_cards_are_redirected_and_sends_can_be_replaced() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9996, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(9997, 1, 1, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(9998, 0, 0, LOCATION_SZONE, 0, POS_FACEUP)
+            Debug.AddCard(9999, 0, 0, LOCATION_HAND, 0, POS_FACEUP)
+            local e1 = Effect.CreateEffect(Card(0))
+            e1:SetType(EFFECT_TYPE_SINGLE)
+            e1:SetCode(EFFECT_LEAVE_FIELD_REDIRECT)
+            e1:SetValue(LOCATION_DECK)
+            Card(0):RegisterEffect(e1)
+            local e2 = Effect.CreateEffect(Card(2))
+            e2:SetType(EFFECT_TYPE_FIELD)
+            e2:SetCode(EFFECT_TO_GRAVE_REDIRECT)
+            e2:SetRange(LOCATION_SZONE)
+            e2:SetTargetRange(0, LOCATION_ONFIELD)
+            e2:SetValue(LOCATION_REMOVED)
+            Card(2):RegisterEffect(e2)
+            local e3 = Effect.CreateEffect(Card(3))
+            e3:SetType(EFFECT_TYPE_SINGLE + EFFECT_TYPE_CONTINUOUS)
+            e3:SetCode(EFFECT_SEND_REPLACE)
+            e3:SetTarget(function(e, tp, eg, ep, ev, re, r, rp, chk)
+                return e:GetHandler():GetDestination() == LOCATION_GRAVE
+            end)
+            Card(3):RegisterEffect(e3)
+        "#).exec().expect("setup");
+        let sent: u32 = duel.lua.load(r#"
+            local g = Group.CreateGroup()
+            for i = 0, 3 do g:AddCard(Card(i)) end
+            return Duel.SendtoGrave(g, REASON_EFFECT)
+        "#).eval().unwrap();
+        assert_eq!(sent, 3);
+        let data = duel.data.lock().unwrap();
+        let card = |id: u32| data.get_card(CardId::new(id)).unwrap();
+        assert_eq!(card(0).location, Location::DECK);
+        assert_eq!(card(0).reason, REASON_EFFECT | REASON_REDIRECT);
+        assert_eq!((card(1).location, card(1).controller), (Location::REMOVED, 1));
+        assert_eq!(card(2).location, Location::GRAVE, "its own side is out of the redirect's range");
+        assert_eq!(card(2).reason, REASON_EFFECT);
+        assert_eq!(card(3).location, Location::HAND);
+    }
+
+    #[test]
+    fn cards_are_banished_in_the_given_position() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9981, 0, 0, LOCATION_GRAVE, 0, POS_FACEUP)
+            Debug.AddCard(9982, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+        "#).exec().expect("setup");
+        let (removed, public): (u32, bool) = duel.lua.load(r#"
+            local removed = Duel.Remove(Card(0), POS_FACEDOWN, REASON_EFFECT) + Duel.Remove(Card(1), POS_FACEUP, REASON_EFFECT)
+            return removed, Card(0):IsPublic()
+        "#).eval().unwrap();
+        assert_eq!(removed, 2);
+        assert!(!public, "a card banished face-down is not public");
+        let data = duel.data.lock().unwrap();
+        let facedown = data.get_card(CardId::new(0)).unwrap();
+        assert_eq!(facedown.location, Location::REMOVED);
+        assert!(facedown.position.intersects(CardPosition::FACEDOWN));
+        assert!(data.get_card(CardId::new(1)).unwrap().position.intersects(CardPosition::FACEUP));
+        assert!(data.cards.iter().all(|c| c.to_position.is_none()));
+    }
+
+    #[test]
+    fn cards_moved_by_the_rules_are_redirected_too() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9961, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(9962, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9963, 0, 0, LOCATION_FZONE, 0, POS_FACEUP)
+            Debug.AddCard(9964, 0, 0, LOCATION_HAND, 1, POS_FACEDOWN)
+            Debug.AddCard(9965, 1, 1, LOCATION_SZONE, 0, POS_FACEUP)
+            Duel.Overlay(Card(0), Card(1))
+            local e = Effect.CreateEffect(Card(4))
+            e:SetType(EFFECT_TYPE_FIELD)
+            e:SetCode(EFFECT_TO_GRAVE_REDIRECT)
+            e:SetRange(LOCATION_SZONE)
+            e:SetTargetRange(0xff, 0xff)
+            e:SetValue(LOCATION_REMOVED)
+            Card(4):RegisterEffect(e)
+            Duel.SendtoGrave(Card(0), REASON_EFFECT)
+            Duel.MoveToField(Card(3), 0, 0, LOCATION_FZONE, POS_FACEUP, true)
+        "#).exec().expect("moves");
+        let data = duel.data.lock().unwrap();
+        let card = |id: u32| data.get_card(CardId::new(id)).unwrap();
+        assert_eq!((card(1).location, card(1).reason), (Location::REMOVED, REASON_LOST_OVERLAY | REASON_RULE | REASON_REDIRECT), "the overlay unit");
+        assert_eq!((card(2).location, card(2).reason), (Location::REMOVED, REASON_RULE | REASON_REDIRECT), "the old Field Spell");
+        assert_eq!(card(3).location, Location::SZONE);
+    }
+}
@@ -0,0 +1,465 @@
+//! Host responses (set_responsei / set_responseb) and their typed decoding.
+//! Byte layouts follow ocgcore: the response is a 64 byte buffer whose first four bytes double as `ivalue`.
+
+use crate::core::enums::Location;
+use crate::core::messages::MsgType;
+
+/// Size of the response buffer (returns.bvalue in C++)
+pub const RESPONSE_SIZE: usize = 64;
+
+/// A selection the engine is waiting on, carrying what is needed to validate the host's answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prompt {
+    SelectBattleCmd { chains: usize, attackers: usize, can_main2: bool, can_end: bool },
+    SelectIdleCmd {
+        summonable: usize,
+        spsummonable: usize,
+        repositionable: usize,
+        msetable: usize,
+        ssetable: usize,
+        activatable: usize,
+        can_battle: bool,
+        can_end: bool,
+        can_shuffle: bool,
+    },
+    SelectEffectYn,
+    SelectYesNo,
+    SelectOption { count: usize },
+    SelectCard { count: usize, min: usize, max: usize, cancelable: bool },
+    SelectUnselectCard { select: usize, unselect: usize, finishable: bool, cancelable: bool },
+    SelectChain { count: usize, forced: bool },
+    SelectPlace { player: u8, count: usize, flag: u32 },
+    SelectDisField { player: u8, count: usize, flag: u32 },
+    SelectPosition { positions: u32 },
+    SelectTribute { count: usize, min: usize, max: usize, cancelable: bool },
+    SortCard { count: usize },
+    SortChain { count: usize },
+    /// `available` holds the counters each candidate card can give up
+    SelectCounter { count: u16, available: Vec<u16> },
+    /// `values` are the sum parameters of the selectable cards, `must` those of the forced ones.
+    /// A sum parameter carries two alternatives: the low 16 bits and (if non-zero) the high 16 bits.
+    SelectSum { acc: u32, greater: bool, min: usize, max: usize, must: Vec<u32>, values: Vec<u32> },
+    AnnounceRace { count: usize, available: u32 },
+    AnnounceAttrib { count: usize, available: u32 },
+    AnnounceCard,
+    AnnounceNumber { count: usize },
+    RockPaperScissors,
+}
+
+impl Prompt {
+    /// Message type that asks the host for this response.
+    pub fn msg_type(&self) -> MsgType {
+        match self {
+            Prompt::SelectBattleCmd { .. } => MsgType::SelectBattleCmd,
+            Prompt::SelectIdleCmd { .. } => MsgType::SelectIdleCmd,
+            Prompt::SelectEffectYn => MsgType::SelectEffectYN,
+            Prompt::SelectYesNo => MsgType::SelectYesNo,
+            Prompt::SelectOption { .. } => MsgType::SelectOption,
+            Prompt::SelectCard { .. } => MsgType::SelectCard,
+            Prompt::SelectUnselectCard { .. } => MsgType::SelectUnselectCard,
+            Prompt::SelectChain { .. } => MsgType::SelectChain,
+            Prompt::SelectPlace { .. } => MsgType::SelectPlace,
+            Prompt::SelectDisField { .. } => MsgType::SelectDisField,
+            Prompt::SelectPosition { .. } => MsgType::SelectPosition,
+            Prompt::SelectTribute { .. } => MsgType::SelectTribute,
+            Prompt::SortCard { .. } => MsgType::SortCard,
+            Prompt::SortChain { .. } => MsgType::SortChain,
+            Prompt::SelectCounter { .. } => MsgType::SelectCounter,
+            Prompt::SelectSum { .. } => MsgType::SelectSum,
+            Prompt::AnnounceRace { .. } => MsgType::AnnounceRace,
+            Prompt::AnnounceAttrib { .. } => MsgType::AnnounceAttrib,
+            Prompt::AnnounceCard => MsgType::AnnounceCard,
+            Prompt::AnnounceNumber { .. } => MsgType::AnnounceNumber,
+            Prompt::RockPaperScissors => MsgType::RockPaperScissors,
+        }
+    }
+}
+
+/// A decoded, validated response.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    /// Battle / idle command: command type and index into that command's card list
+    Command { command: u16, index: usize },
+    YesNo(bool),
+    Option(usize),
+    /// Indices into the candidate list (for SelectSum these exclude the forced cards)
+    Cards(Vec<usize>),
+    Cancel,
+    /// SelectUnselectCard finished without picking another card
+    Finish,
+    /// SelectChain: index of the chosen effect, None to pass
+    Chain(Option<usize>),
+    /// (player, location, sequence) triples
+    Places(Vec<(u8, Location, u8)>),
+    Position(u32),
+    /// New order for SortCard/SortChain, None to keep the default order
+    Order(Option<Vec<u8>>),
+    Counters(Vec<u16>),
+    /// AnnounceRace / AnnounceAttrib bitmask or AnnounceCard code
+    Value(u32),
+    Number(usize),
+    /// Rock-paper-scissors hand (1..=3)
+    Hand(u8),
+}
+
+/// Read the i32 view of a response buffer.
+pub fn ivalue(bytes: &[u8]) -> i32 {
+    let mut raw = [0u8; 4];
+    for (dst, src) in raw.iter_mut().zip(bytes.iter()) {
+        *dst = *src;
+    }
+    i32::from_le_bytes(raw)
+}
+
+/// Decode and validate a response buffer against the prompt it answers.
+pub fn decode(prompt: &Prompt, bytes: &[u8]) -> Result<Response, String> {
+    let mut buf = [0u8; RESPONSE_SIZE];
+    let n = bytes.len().min(RESPONSE_SIZE);
+    buf[..n].copy_from_slice(&bytes[..n]);
+    let iv = ivalue(&buf);
+    match prompt {
+        Prompt::SelectBattleCmd { chains, attackers, can_main2, can_end } => {
+            let (command, index) = split_command(iv);
+            let ok = match command {
+                0 => index < *chains,
+                1 => index < *attackers,
+                2 => *can_main2,
+                3 => *can_end,
+                _ => false,
+            };
+            if !ok {
+                return Err(format!("invalid battle command {:#x}", iv));
+            }
+            Ok(Response::Command { command, index })
+        }
+        Prompt::SelectIdleCmd { summonable, spsummonable, repositionable, msetable, ssetable, activatable, can_battle, can_end, can_shuffle } => {
+            let (command, index) = split_command(iv);
+            let ok = match command {
+                0 => index < *summonable,
+                1 => index < *spsummonable,
+                2 => index < *repositionable,
+                3 => index < *msetable,
+                4 => index < *ssetable,
+                5 => index < *activatable,
+                6 => *can_battle,
+                7 => *can_end,
+                8 => *can_shuffle,
+                _ => false,
+            };
+            if !ok {
+                return Err(format!("invalid idle command {:#x}", iv));
+            }
+            Ok(Response::Command { command, index })
+        }
+        Prompt::SelectEffectYn | Prompt::SelectYesNo => match iv {
+            0 => Ok(Response::YesNo(false)),
+            1 => Ok(Response::YesNo(true)),
+            _ => Err(format!("invalid yes/no answer {}", iv)),
+        },
+        Prompt::SelectOption { count } | Prompt::AnnounceNumber { count } => {
+            if iv < 0 || iv as usize >= *count {
+                return Err(format!("option {} out of range", iv));
+            }
+            if matches!(prompt, Prompt::SelectOption { .. }) {
+                Ok(Response::Option(iv as usize))
+            } else {
+                Ok(Response::Number(iv as usize))
+            }
+        }
+        Prompt::SelectCard { count, min, max, cancelable } | Prompt::SelectTribute { count, min, max, cancelable } => {
+            if iv == -1 {
+                return if *cancelable { Ok(Response::Cancel) } else { Err("selection cannot be canceled".to_string()) };
+            }
+            let indices = read_indices(&buf, *count)?;
+            if indices.len() < *min || indices.len() > *max {
+                return Err(format!("selected {} cards, expected {}..={}", indices.len(), min, max));
+            }
+            Ok(Response::Cards(indices))
+        }
+        Prompt::SelectUnselectCard { select, unselect, finishable, cancelable } => {
+            if iv == -1 {
+                return if *finishable || *cancelable { Ok(Response::Finish) } else { Err("selection cannot be finished".to_string()) };
+            }
+            if buf[0] != 1 {
+                return Err("exactly one card must be chosen".to_string());
+            }
+            let index = buf[1] as usize;
+            if index >= select + unselect {
+                return Err(format!("card index {} out of range", index));
+            }
+            Ok(Response::Cards(vec![index]))
+        }
+        Prompt::SelectChain { count, forced } => {
+            if iv == -1 {
+                return if *forced { Err("a chain must be selected".to_string()) } else { Ok(Response::Chain(None)) };
+            }
+            if iv < 0 || iv as usize >= *count {
+                return Err(format!("chain index {} out of range", iv));
+            }
+            Ok(Response::Chain(Some(iv as usize)))
+        }
+        Prompt::SelectPlace { player, count, flag } | Prompt::SelectDisField { player, count, flag } => {
+            let mut places = Vec::with_capacity(*count);
+            for i in 0..*count {
+                let (p, l, s) = (buf[i * 3], buf[i * 3 + 1] as u32, buf[i * 3 + 2]);
+                let location = Location::from_bits_truncate(l);
+                if p > 1 || (location != Location::MZONE && location != Location::SZONE) || s > 7 {
+                    return Err(format!("invalid place ({}, {:#x}, {})", p, l, s));
+                }
+                let shift = if p == *player { 0 } else { 16 } + if location == Location::MZONE { 0 } else { 8 };
+                if (flag >> shift) & (1 << s) != 0 {
+                    return Err(format!("zone ({}, {:#x}, {}) is not selectable", p, l, s));
+                }
+                places.push((p, location, s));
+            }
+            Ok(Response::Places(places))
+        }
+        Prompt::SelectPosition { positions } => {
+            let pos = iv as u32;
+            if !matches!(pos, 0x1 | 0x2 | 0x4 | 0x8) || pos & positions == 0 {
+                return Err(format!("invalid position {:#x}", pos));
+            }
+            Ok(Response::Position(pos))
+        }
+        Prompt::SortCard { count } | Prompt::SortChain { count } => {
+            if buf[0] == 0xff {
+                return Ok(Response::Order(None));
+            }
+            let order = buf[..*count].to_vec();
+            let mut seen = vec![false; *count];
+            for &o in order.iter() {
+                if o as usize >= *count || seen[o as usize] {
+                    return Err(format!("invalid sort order {:?}", order));
+                }
+                seen[o as usize] = true;
+            }
+            Ok(Response::Order(Some(order)))
+        }
+        Prompt::SelectCounter { count, available } => {
+            let mut counters = Vec::with_capacity(available.len());
+            let mut total = 0u32;
+            for (i, &limit) in available.iter().enumerate() {
+                let v = u16::from_le_bytes([buf[i * 2], buf[i * 2 + 1]]);
+                if v > limit {
+                    return Err(format!("card {} only has {} counters", i, limit));
+                }
+                total += v as u32;
+                counters.push(v);
+            }
+            if total != *count as u32 {
+                return Err(format!("removed {} counters, expected {}", total, count));
+            }
+            Ok(Response::Counters(counters))
+        }
+        Prompt::SelectSum { acc, greater, min, max, must, values } => {
+            let total = buf[0] as usize;
+            let mcount = must.len();
+            if total < mcount || total - mcount < *min || total - mcount > *max || total + 1 > RESPONSE_SIZE {
+                return Err(format!("selected {} cards for sum", total));
+            }
+            let mut seen = vec![false; values.len()];
+            let mut indices = Vec::with_capacity(total - mcount);
+            let mut params: Vec<u32> = must.clone();
+            for &b in buf[1 + mcount..1 + total].iter() {
+                let idx = b as usize;
+                if idx >= values.len() || seen[idx] {
+                    return Err(format!("invalid sum card index {}", idx));
+                }
+                seen[idx] = true;
+                indices.push(idx);
+                params.push(values[idx]);
+            }
+            let ok = if *greater { check_sum_greater(&params, *acc) } else { check_sum_equal(&params, *acc) };
+            if !ok {
+                return Err(format!("selected cards do not sum to {}", acc));
+            }
+            Ok(Response::Cards(indices))
+        }
+        Prompt::AnnounceRace { count, available } | Prompt::AnnounceAttrib { count, available } => {
+            let value = iv as u32;
+            if value.count_ones() as usize != *count || value & !available != 0 {
+                return Err(format!("invalid announcement {:#x}", value));
+            }
+            Ok(Response::Value(value))
+        }
+        Prompt::AnnounceCard => {
+            if iv <= 0 {
+                return Err("no card announced".to_string());
+            }
+            Ok(Response::Value(iv as u32))
+        }
+        Prompt::RockPaperScissors => {
+            if !(1..=3).contains(&iv) {
+                return Err(format!("invalid hand {}", iv));
+            }
+            Ok(Response::Hand(iv as u8))
+        }
+    }
+}
+
+fn split_command(iv: i32) -> (u16, usize) {
+    ((iv as u32 & 0xffff) as u16, (iv as u32 >> 16) as usize)
+}
+
+/// Read `[count, idx...]` and check indices are in range and unique.
+fn read_indices(buf: &[u8; RESPONSE_SIZE], candidates: usize) -> Result<Vec<usize>, String> {
+    let n = buf[0] as usize;
+    if n + 1 > RESPONSE_SIZE {
+        return Err(format!("too many cards selected: {}", n));
+    }
+    let mut seen = vec![false; candidates];
+    let mut out = Vec::with_capacity(n);
+    for &b in buf[1..=n].iter() {
+        let idx = b as usize;
+        if idx >= candidates || seen[idx] {
+            return Err(format!("invalid card index {}", idx));
+        }
+        seen[idx] = true;
+        out.push(idx);
+    }
+    Ok(out)
+}
+
+/// The two alternatives of a sum parameter (the second one only when present).
+fn sum_options(param: u32) -> (u32, Option<u32>) {
+    let op1 = param & 0xffff;
+    let op2 = param >> 16;
+    (op1, if op2 > 0 { Some(op2) } else { None })
+}
+
+/// Whether some choice of alternatives makes the parameters add up to exactly `acc`.
+pub fn check_sum_equal(params: &[u32], acc: u32) -> bool {
+    fn rec(params: &[u32], acc: i64) -> bool {
+        match params.split_first() {
+            None => acc == 0,
+            Some((&p, rest)) => {
+                let (op1, op2) = sum_options(p);
+                rec(rest, acc - op1 as i64) || op2.is_some_and(|v| rec(rest, acc - v as i64))
+            }
+        }
+    }
+    rec(params, acc as i64)
+}
+
+/// Whether the parameters reach `acc` while every card is needed to do so (ritual style).
+pub fn check_sum_greater(params: &[u32], acc: u32) -> bool {
+    let max_sum: u32 = params.iter().map(|&p| { let (a, b) = sum_options(p); a.max(b.unwrap_or(0)) }).sum();
+    let min_single = params.iter().map(|&p| { let (a, b) = sum_options(p); a.min(b.unwrap_or(a)) }).min().unwrap_or(0);
+    max_sum >= acc && max_sum - min_single < acc
+}
+
+/// First choice of `min..=max` of `params` that, with the `must` ones, adds up to `acc`
+/// (exactly, or ritual style when `greater`). Returns the indices of the chosen parameters.
+pub fn find_sum(must: &[u32], params: &[u32], acc: u32, greater: bool, min: usize, max: usize) -> Option<Vec<usize>> {
+    fn rec(sum: &mut Vec<u32>, params: &[u32], start: usize, picked: &mut Vec<usize>, search: (u32, bool, usize, usize)) -> bool {
+        let (acc, greater, min, max) = search;
+        let done = if greater { check_sum_greater(sum, acc) } else { check_sum_equal(sum, acc) };
+        if picked.len() >= min && done {
+            return true;
+        }
+        if picked.len() == max {
+            return false;
+        }
+        for i in start..params.len() {
+            sum.push(params[i]);
+            picked.push(i);
+            if rec(sum, params, i + 1, picked, search) {
+                return true;
+            }
+            sum.pop();
+            picked.pop();
+        }
+        false
+    }
+    let mut sum = must.to_vec();
+    let mut picked = Vec::new();
+    rec(&mut sum, params, 0, &mut picked, (acc, greater, min, max)).then_some(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ivalue_reads_first_four_bytes() {
+        assert_eq!(ivalue(&(-1i32).to_le_bytes()), -1);
+        assert_eq!(ivalue(&[0x05, 0x00, 0x02, 0x00, 0xff]), 0x20005);
+        assert_eq!(ivalue(&[0x07]), 7);
+    }
+
+    #[test]
+    fn select_card_layout_and_validation() {
+        let prompt = Prompt::SelectCard { count: 4, min: 1, max: 2, cancelable: true };
+        assert_eq!(decode(&prompt, &[2, 3, 0]), Ok(Response::Cards(vec![3, 0])));
+        assert_eq!(decode(&prompt, &(-1i32).to_le_bytes()), Ok(Response::Cancel));
+        assert!(decode(&prompt, &[2, 1, 1]).is_err(), "duplicate index");
+        assert!(decode(&prompt, &[1, 4]).is_err(), "index out of range");
+        assert!(decode(&prompt, &[3, 0, 1, 2]).is_err(), "too many cards");
+        let forced = Prompt::SelectCard { count: 4, min: 1, max: 2, cancelable: false };
+        assert!(decode(&forced, &(-1i32).to_le_bytes()).is_err());
+    }
+
+    #[test]
+    fn chain_and_commands() {
+        let chain = Prompt::SelectChain { count: 2, forced: false };
+        assert_eq!(decode(&chain, &(-1i32).to_le_bytes()), Ok(Response::Chain(None)));
+        assert_eq!(decode(&chain, &1i32.to_le_bytes()), Ok(Response::Chain(Some(1))));
+        assert!(decode(&chain, &2i32.to_le_bytes()).is_err());
+        assert!(decode(&Prompt::SelectChain { count: 1, forced: true }, &(-1i32).to_le_bytes()).is_err());
+
+        let idle = Prompt::SelectIdleCmd {
+            summonable: 1, spsummonable: 0, repositionable: 0, msetable: 2, ssetable: 0,
+            activatable: 0, can_battle: true, can_end: true, can_shuffle: false,
+        };
+        assert_eq!(decode(&idle, &((1 << 16) | 3i32).to_le_bytes()), Ok(Response::Command { command: 3, index: 1 }));
+        assert_eq!(decode(&idle, &6i32.to_le_bytes()), Ok(Response::Command { command: 6, index: 0 }));
+        assert!(decode(&idle, &1i32.to_le_bytes()).is_err(), "nothing to special summon");
+        assert!(decode(&idle, &8i32.to_le_bytes()).is_err(), "shuffle not allowed");
+    }
+
+    #[test]
+    fn place_counter_and_sort_layouts() {
+        // Player 0 may only use mzone 2 and the opponent's szone 0
+        let flag = !((1u32 << 2) | (1 << (16 + 8)));
+        let place = Prompt::SelectPlace { player: 0, count: 1, flag };
+        assert_eq!(decode(&place, &[0, 0x04, 2]), Ok(Response::Places(vec![(0, Location::MZONE, 2)])));
+        assert_eq!(decode(&place, &[1, 0x08, 0]), Ok(Response::Places(vec![(1, Location::SZONE, 0)])));
+        assert!(decode(&place, &[0, 0x04, 1]).is_err());
+        assert!(decode(&place, &[0, 0x10, 2]).is_err());
+
+        let counter = Prompt::SelectCounter { count: 3, available: vec![2, 4] };
+        assert_eq!(decode(&counter, &[1, 0, 2, 0]), Ok(Response::Counters(vec![1, 2])));
+        assert!(decode(&counter, &[3, 0, 0, 0]).is_err(), "more than the card holds");
+        assert!(decode(&counter, &[1, 0, 1, 0]).is_err(), "wrong total");
+
+        let sort = Prompt::SortCard { count: 3 };
+        assert_eq!(decode(&sort, &[0xff]), Ok(Response::Order(None)));
+        assert_eq!(decode(&sort, &[2, 0, 1]), Ok(Response::Order(Some(vec![2, 0, 1]))));
+        assert!(decode(&sort, &[0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn select_sum_checks_levels() {
+        // Must-select tuner of level 3, candidates of level 4, 5 and one that counts as 2 or 4
+        let prompt = Prompt::SelectSum { acc: 7, greater: false, min: 1, max: 2, must: vec![3], values: vec![4, 5, 2 | (4 << 16)] };
+        assert_eq!(decode(&prompt, &[2, 0, 0]), Ok(Response::Cards(vec![0])));
+        assert_eq!(decode(&prompt, &[2, 0, 2]), Ok(Response::Cards(vec![2])));
+        assert!(decode(&prompt, &[2, 0, 1]).is_err(), "3 + 5 != 7");
+
+        let ritual = Prompt::SelectSum { acc: 8, greater: true, min: 1, max: 3, must: vec![], values: vec![4, 6, 1] };
+        assert_eq!(decode(&ritual, &[2, 0, 1]), Ok(Response::Cards(vec![0, 1])));
+        assert!(decode(&ritual, &[3, 0, 1, 2]).is_err(), "the level 1 card is redundant");
+        assert!(decode(&ritual, &[1, 1]).is_err(), "6 < 8");
+    }
+
+    #[test]
+    fn announcements() {
+        let race = Prompt::AnnounceRace { count: 1, available: 0x3 };
+        assert_eq!(decode(&race, &2i32.to_le_bytes()), Ok(Response::Value(2)));
+        assert!(decode(&race, &3i32.to_le_bytes()).is_err());
+        assert!(decode(&race, &4i32.to_le_bytes()).is_err());
+        assert!(decode(&Prompt::RockPaperScissors, &4i32.to_le_bytes()).is_err());
+        assert_eq!(decode(&Prompt::SelectPosition { positions: 0x5 }, &4i32.to_le_bytes()), Ok(Response::Position(4)));
+        assert!(decode(&Prompt::SelectPosition { positions: 0x5 }, &2i32.to_le_bytes()).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! Ritual summons: tributes by effect, ritual levels and the material pool aux.AddRitualProc* builds on.
+
+use crate::core::duel::{Duel, DuelData};
+use crate::core::enums::*;
+use crate::core::group::{cards_of, Group};
+use crate::core::prompt::{ready, select_cards, set_prompting};
+use crate::core::replace::send_to;
+use crate::core::scripting::{duel_data, effect_function, effect_value};
+use crate::core::types::CardId;
+use mlua::{Function, Lua, MultiValue, Value};
+
+impl DuelData {
+    /// Monsters a player could tribute by an effect: their own monsters on the field and, with `hand`, in the hand.
+    pub fn release_pool(&self, player: u8, hand: bool) -> Vec<CardId> {
+        let p = player as usize;
+        let in_hand = if hand { self.field.hand[p].as_slice() } else { &[] };
+        self.field.mzone[p].iter().flatten().chain(in_hand).copied()
+            .filter(|id| self.cards.get(id.0 as usize).is_some_and(|c| c.current_stats.type_.contains(CardType::MONSTER)))
+            .collect()
+    }
+
+    /// Default ritual material pool: the release pool with the hand, plus Graveyard cards under EFFECT_EXTRA_RITUAL_MATERIAL.
+    pub fn ritual_material_pool(&self, player: u8) -> Vec<CardId> {
+        let mut pool = self.release_pool(player, true);
+        pool.extend(self.field.grave[player as usize].iter().copied()
+            .filter(|&id| !self.card_effects(id, EFFECT_EXTRA_RITUAL_MATERIAL).is_empty()));
+        pool
+    }
+}
+
+/// Whether `player` may tribute a card by an effect (EFFECT_UNRELEASABLE_NONSUM, EFFECT_CANNOT_RELEASE).
+pub fn is_releasable_by_effect(lua: &Lua, card_id: CardId, player: u8) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let (unreleasable, blockers) = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        if !card.location.intersects(Location::MZONE | Location::HAND) {
+            return Ok(false);
+        }
+        (data_guard.card_effects(card_id, EFFECT_UNRELEASABLE_NONSUM), data_guard.player_effects(player, EFFECT_CANNOT_RELEASE))
+    };
+    for eid in unreleasable {
+        if effect_value(lua, &data, eid, (eid, card_id))? != 0 {
+            return Ok(false);
+        }
+    }
+    for eid in blockers {
+        let Some(target) = effect_function(lua, &data, eid, |e| &e.target) else {
+            return Ok(false);
+        };
+        if target.call::<_, bool>((eid, card_id, player))? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Level of a card as ritual material for `ritual`, packed as `level | alternate << 16` (EFFECT_RITUAL_LEVEL).
+pub fn ritual_level(lua: &Lua, card_id: CardId, ritual: CardId) -> mlua::Result<u32> {
+    let data = duel_data(lua);
+    let (level, modifier) = {
+        let data_guard = data.lock().unwrap();
+        let level = data_guard.cards.get(card_id.0 as usize)
+            .filter(|c| !c.current_stats.type_.intersects(CardType::XYZ | CardType::LINK))
+            .map(|c| c.current_stats.level)
+            .unwrap_or(0);
+        (level, data_guard.card_effects(card_id, EFFECT_RITUAL_LEVEL).first().copied())
+    };
+    match modifier {
+        Some(eid) => effect_value(lua, &data, eid, (eid, ritual)),
+        None => Ok(level),
+    }
+}
+
+/// Whether a card may be tributed for `ritual`: a releasable monster, or a Graveyard card under EFFECT_EXTRA_RITUAL_MATERIAL.
+pub fn is_can_be_ritual_material(lua: &Lua, card_id: CardId, ritual: Option<CardId>) -> mlua::Result<bool> {
+    let (player, extra) = {
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        if !card.current_stats.type_.contains(CardType::MONSTER) || Some(card_id) == ritual {
+            return Ok(false);
+        }
+        let extra = card.location == Location::GRAVE && !data_guard.card_effects(card_id, EFFECT_EXTRA_RITUAL_MATERIAL).is_empty();
+        (card.controller, extra)
+    };
+    Ok(extra || is_releasable_by_effect(lua, card_id, player)?)
+}
+
+/// Tribute the cards to their owners' Graveyards and raise EVENT_RELEASE. Returns the number released.
+pub fn release(lua: &Lua, cards: &[CardId], reason: u32) -> mlua::Result<u32> {
+    let data = duel_data(lua);
+    let (player, effect) = data.lock().unwrap().reason_context();
+    let released = send_to(lua, cards, None, Location::GRAVE, None, REASON_RELEASE | reason, player, effect)?;
+    let count = released.0.len() as u32;
+    if count > 0 {
+        Duel::raise_event_static(lua, data, EVENT_RELEASE, Some(released), player, effect);
+    }
+    Ok(count)
+}
+
+/// Cards of `player`'s release pool passing `f(c, ...)`, without the excluded ones.
+fn release_group<'lua>(lua: &'lua Lua, player: u8, hand: bool, f: &Option<Function<'lua>>, ex: &Value<'lua>, args: &MultiValue<'lua>) -> mlua::Result<Vec<CardId>> {
+    let pool = duel_data(lua).lock().unwrap().release_pool(player, hand);
+    let mut out = Vec::new();
+    for card_id in Group(pool.into_iter().collect()).filtered(lua, f, ex, args)? {
+        if is_releasable_by_effect(lua, card_id, player)? {
+            out.push(card_id);
+        }
+    }
+    Ok(out)
+}
+
+/// Register the release and ritual functions on the global `Duel` table.
+pub fn register_ritual_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.Release(targets, reason) -> count
+    duel_table.set("Release", lua.create_function(|lua, (targets, reason): (Value, u32)| {
+        release(lua, &cards_of(&targets), reason)
+    })?)?;
+
+    // Duel.CheckReleaseGroup(player, f, count, ex, ...) - monsters on the field only
+    duel_table.set("CheckReleaseGroup", lua.create_function(|lua, (player, f, count, ex, args): (u8, Option<Function>, usize, Value, MultiValue)| {
+        Ok(release_group(lua, player, false, &f, &ex, &args)?.len() >= count)
+    })?)?;
+
+    // Duel.SelectReleaseGroup(player, f, min, max, ex, ...) -> Group
+    set_prompting(lua, &duel_table, "SelectReleaseGroup", |lua, (player, f, min, max, ex, args): (u8, Option<Function>, usize, usize, Value, MultiValue)| {
+        let cards = release_group(lua, player, false, &f, &ex, &args)?;
+        select_cards(lua, player, cards, min, max, |lua, chosen| ready(lua, Group(chosen.into_iter().collect())))
+    })?;
+
+    // Duel.CheckReleaseGroupEx(player, f, count, ex, ...) - the hand counts as well
+    duel_table.set("CheckReleaseGroupEx", lua.create_function(|lua, (player, f, count, ex, args): (u8, Option<Function>, usize, Value, MultiValue)| {
+        Ok(release_group(lua, player, true, &f, &ex, &args)?.len() >= count)
+    })?)?;
+
+    // Duel.SelectReleaseGroupEx(player, f, min, max, ex, ...) -> Group
+    set_prompting(lua, &duel_table, "SelectReleaseGroupEx", |lua, (player, f, min, max, ex, args): (u8, Option<Function>, usize, usize, Value, MultiValue)| {
+        let cards = release_group(lua, player, true, &f, &ex, &args)?;
+        select_cards(lua, player, cards, min, max, |lua, chosen| ready(lua, Group(chosen.into_iter().collect())))
+    })?;
+
+    // Duel.GetRitualMaterial(player) -> Group of everything the player could tribute for a ritual summon
+    duel_table.set("GetRitualMaterial", lua.create_function(|lua, player: u8| {
+        let pool = duel_data(lua).lock().unwrap().ritual_material_pool(player);
+        let mut g = Group::new();
+        for card_id in pool {
+            if is_can_be_ritual_material(lua, card_id, None)? {
+                g.0.insert(card_id);
+            }
+        }
+        Ok(g)
+    })?)?;
+
+    // Duel.ReleaseRitualMaterial(g) - tributes, banishing the Graveyard ones -> count
+    duel_table.set("ReleaseRitualMaterial", lua.create_function(|lua, targets: Value| {
+        let reason = REASON_EFFECT | REASON_MATERIAL | REASON_RITUAL;
+        let (in_grave, tributes): (Vec<CardId>, Vec<CardId>) = {
+            let data = duel_data(lua);
+            let data_guard = data.lock().unwrap();
+            cards_of(&targets).into_iter()
+                .partition(|&id| data_guard.get_card(id).is_some_and(|c| c.location == Location::GRAVE))
+        };
+        let (player, effect) = duel_data(lua).lock().unwrap().reason_context();
+        let count = send_to(lua, &in_grave, None, Location::REMOVED, None, reason, player, effect)?.0.len() as u32;
+        Ok(count + release(lua, &tributes, reason)?)
+    })?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::database::CardData;
+    use crate::core::enums::*;
+    use crate::core::messages::MsgSelectPosition;
+    use crate::core::processor::ProcessResult;
+    use crate::core::scripting::effect_function;
+    use crate::core::testing::{card, duel_with};
+    use crate::core::types::{CardId, EffectId};
+
+    fn ritual(code: u32, level: u32) -> CardData {
+        card(code, CardType::MONSTER | CardType::RITUAL, level)
+    }
+
+    fn normal(code: u32, level: u32) -> CardData {
+        card(code, CardType::MONSTER | CardType::NORMAL, level)
+    }
+
+    #[test]
+    fn ritual_spell_tributes_by_level_and_summons() {
+        let cards = [ritual(9701, 6), normal(9702, 4), normal(9703, 3), normal(9704, 2), card(9705, CardType::SPELL | CardType::RITUAL, 0)];
+        let mut duel = duel_with(&cards, r#"
+            ritual = Debug.AddCard(9701, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9702, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(9703, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9704, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            spell = Debug.AddCard(9705, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            ritual:EnableReviveLimit()
+        "#);
+
+        // The ritual spell summons a ritual monster from the hand, through aux.AddRitualProcGreater
+        duel.lua.load("aux.AddRitualProcGreater(spell, nil)").exec().expect("ritual procedure");
+        let eid = {
+            let data = duel.data.lock().unwrap();
+            EffectId(data.effects.iter().rposition(|e| e.owner == CardId::new(4)).expect("the spell's effect") as u32)
+        };
+        {
+            let globals = duel.lua.globals();
+            globals.set("target", effect_function(&duel.lua, &duel.data, eid, |e| &e.target)).unwrap();
+            globals.set("operation", effect_function(&duel.lua, &duel.data, eid, |e| &e.operation)).unwrap();
+            globals.set("rpg", eid).unwrap();
+        }
+        let (ok, equal): (bool, bool) = duel.lua.load(r#"
+            local mg = Duel.GetRitualMaterial(0):Filter(Card.IsCanBeRitualMaterial, ritual, ritual)
+            return target(rpg, 0, nil, 0, 0, nil, 0, 0, 0), mg:CheckWithSumEqual(Card.GetRitualLevel, 6, 1, 3, ritual)
+        "#).eval().expect("checks");
+        assert!(ok, "the tributes on hand reach the ritual monster's level");
+        assert!(equal, "4 + 2 makes exactly 6");
+
+        let operation = duel.lua.load("operation(rpg, 0)").into_function().unwrap();
+        duel.execute_operation(operation).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the ritual monster is chosen by the player");
+        duel.set_responseb(&[1, 0]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "then the tributes");
+        // 4 + 3 reaches the level with both needed; 4 + 3 + 2 would not need the 2
+        duel.set_responseb(&[3, 0, 1, 2]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "a selection with a spare card is asked again");
+        duel.set_responseb(&[2, 0, 1]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "then the zone for the ritual monster");
+        duel.get_message();
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 1]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "and its position, as POS_FACEUP allows two");
+        assert_eq!(duel.get_message(), MsgSelectPosition { player: 0, code: 9701, positions: 0x5 }.encode());
+        duel.set_responsei(CardPosition::FACEDOWN_DEFENSE.bits() as i32);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "face-down Defense Position is not among them");
+        duel.set_responsei(CardPosition::FACEUP_ATTACK.bits() as i32);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+
+        let data = duel.data.lock().unwrap();
+        let card = data.get_card(CardId::new(0)).unwrap();
+        assert_eq!((card.location, card.sequence), (Location::MZONE, 1));
+        assert_eq!(card.summon_type(), SUMMON_TYPE_RITUAL);
+        assert_eq!(card.position.bits(), CardPosition::FACEUP_ATTACK.bits());
+        assert!(card.has_status(CardStatus::PROC_COMPLETE));
+        assert_eq!(card.materials.len(), 2);
+        let tributes = data.field.grave[0].clone();
+        assert_eq!(tributes, vec![CardId::new(1), CardId::new(2)]);
+        assert!(tributes.iter().all(|&id| data.cards[id.0 as usize].reason & REASON_RITUAL != 0));
+    }
+
+    #[test]
+    fn ritual_level_effects_and_unreleasable_monsters() {
+        let duel = duel_with(&[ritual(9801, 8), normal(9802, 2), normal(9803, 8)], r#"
+            ritual = Debug.AddCard(9801, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            double = Debug.AddCard(9802, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            locked = Debug.AddCard(9803, 0, 0, LOCATION_MZONE, 1, POS_FACEUP_ATTACK)
+            local e = Effect.CreateEffect(double)
+            e:SetType(EFFECT_TYPE_SINGLE)
+            e:SetCode(EFFECT_RITUAL_LEVEL)
+            e:SetValue(function(e, rc) return 2 + (8 << 16) end)
+            double:RegisterEffect(e)
+            local e2 = Effect.CreateEffect(locked)
+            e2:SetType(EFFECT_TYPE_SINGLE)
+            e2:SetCode(EFFECT_UNRELEASABLE_NONSUM)
+            e2:SetValue(1)
+            locked:RegisterEffect(e2)
+        "#);
+
+        let (level, count, can_release, enough): (u32, u32, bool, bool) = duel.lua.load(r#"
+            local mg = Duel.GetRitualMaterial(0)
+            return double:GetRitualLevel(ritual), mg:GetCount(), Duel.CheckReleaseGroup(0, nil, 2, nil),
+                mg:Filter(nil, ritual):CheckWithSumEqual(Card.GetRitualLevel, 8, 1, 1, ritual)
+        "#).eval().expect("checks");
+        assert_eq!(level, 2 | 8 << 16);
+        assert_eq!(count, 2, "the ritual monster itself and the double-level one; the locked one is unreleasable");
+        assert!(!can_release);
+        assert!(enough, "the alternate level 8 covers the ritual alone");
+    }
+
+    #[test]
+    fn tributes_by_effect_are_chosen_by_the_player() {
+        let mut duel = duel_with(&[normal(9901, 4), normal(9902, 4), normal(9903, 4)], r#"
+            Debug.AddCard(9901, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            Debug.AddCard(9902, 0, 0, LOCATION_MZONE, 1, POS_FACEUP_ATTACK)
+            Debug.AddCard(9903, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+        "#);
+
+        let operation = duel.lua.load(r#"
+            local g = Duel.SelectReleaseGroup(0, nil, 1, 1, nil)
+            released = Duel.Release(g, REASON_COST)
+        "#).into_function().unwrap();
+        duel.execute_operation(operation).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the tribute is chosen by the player");
+        duel.set_responseb(&[1, 1]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let released: u32 = duel.lua.load("return released").eval().unwrap();
+        assert_eq!(released, 1);
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.field.grave[0], vec![CardId::new(1)]);
+        assert_eq!(data.field.hand[0], vec![CardId::new(2)], "the hand only counts for SelectReleaseGroupEx");
+    }
+}
@@ -1,5 +1,10 @@
+use crate::core::duel::DuelData;
+use crate::core::effect::Effect;
+use crate::core::types::EffectId;
+use mlua::{Function, IntoLuaMulti, Lua, RegistryKey};
 use std::path::PathBuf;
 use std::fs;
+use std::sync::{Arc, Mutex};
 
 pub trait ScriptLoader {
     fn load_script(&self, name: &str) -> Option<String>;
@@ -28,6 +33,34 @@ impl ScriptLoader for FileSystemLoader {
     }
 }
 
+/// Shared DuelData handle stored in Lua app data.
+pub(crate) fn duel_data(lua: &Lua) -> Arc<Mutex<DuelData>> {
+    lua.app_data_ref::<Arc<Mutex<DuelData>>>()
+        .expect("DuelData not found in Lua app data")
+        .clone()
+}
+
+/// Fetch one of an effect's Lua functions without keeping the DuelData lock.
+pub(crate) fn effect_function<'lua>(lua: &'lua Lua, data: &Arc<Mutex<DuelData>>, eid: EffectId, pick: fn(&Effect) -> &Option<RegistryKey>) -> Option<Function<'lua>> {
+    let data_guard = data.lock().unwrap();
+    let effect = data_guard.effects.get(eid.0 as usize)?;
+    let key = pick(effect).as_ref()?;
+    lua.registry_value::<Function>(key).ok()
+}
+
+/// Evaluate an effect's value: its value function called with `args`, or the constant set by SetValue.
+pub(crate) fn effect_value<'lua>(lua: &'lua Lua, data: &Arc<Mutex<DuelData>>, eid: EffectId, args: impl IntoLuaMulti<'lua>) -> mlua::Result<u32> {
+    let Some(value) = effect_function(lua, data, eid, |e| &e.value_fn) else {
+        return Ok(data.lock().unwrap().effects.get(eid.0 as usize).map(|e| e.value).unwrap_or(0));
+    };
+    Ok(match value.call::<_, mlua::Value>(args)? {
+        mlua::Value::Boolean(b) => b as u32,
+        mlua::Value::Integer(i) => i as u32,
+        mlua::Value::Number(n) => n as u32,
+        _ => 0,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,637 @@
+//! Special summons: built-in procedures (EFFECT_SPSUMMON_PROC), legality checks and the summon itself.
+
+use crate::core::duel::{Duel, DuelData};
+use crate::core::effect::Effect;
+use crate::core::enums::*;
+use crate::core::group::{cards_of, Group};
+use crate::core::messages::{MsgSelectPosition, MsgSpSummoned, MsgSpSummoning};
+use crate::core::prompt::{ask, ready, set_prompting, unexpected, wrap};
+use crate::core::response::{Prompt, Response};
+use crate::core::scripting::{duel_data, effect_function, effect_value};
+use crate::core::types::{CardId, EffectId};
+use crate::core::zone::select_place;
+use mlua::{AnyUserData, Function, Lua, MultiValue};
+use std::sync::{Arc, Mutex};
+
+impl DuelData {
+    /// Whether an effect is in force: registered to a player, or its owner card sits in the effect's range.
+    pub fn is_effect_active(&self, effect: &Effect) -> bool {
+        if effect.player.is_some() {
+            return true;
+        }
+        match self.cards.get(effect.owner.0 as usize) {
+            Some(card) => effect.type_ & EFFECT_TYPE_SINGLE != 0 || card.location.bits() & effect.range != 0,
+            None => false,
+        }
+    }
+
+    /// Single effects with `code` registered on the card.
+    pub fn card_effects(&self, card_id: CardId, code: u32) -> Vec<EffectId> {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return Vec::new();
+        };
+        card.effects.iter().copied()
+            .filter(|eid| self.effects.get(eid.0 as usize)
+                .is_some_and(|e| e.code == code && e.type_ & EFFECT_TYPE_FIELD == 0))
+            .collect()
+    }
+
+    /// Active player-target effects with `code` that apply to `player`.
+    pub fn player_effects(&self, player: u8, code: u32) -> Vec<EffectId> {
+        self.effects.iter().enumerate()
+            .filter(|(_, e)| e.code == code && (e.player.is_some() || e.flag & EFFECT_FLAG_PLAYER_TARGET != 0))
+            .filter(|(_, e)| self.is_effect_active(e))
+            .filter(|(_, e)| {
+                let source = e.player.unwrap_or_else(|| self.cards.get(e.owner.0 as usize).map(|c| c.controller).unwrap_or(0));
+                (e.target_range.0 != 0 && source == player) || (e.target_range.1 != 0 && source != player)
+            })
+            .map(|(i, _)| EffectId::new(i as u32))
+            .collect()
+    }
+
+    /// Cards that must be used as material by `player`: the handlers of EFFECT_MUST_BE_*MATERIAL effects `code`.
+    pub fn must_materials(&self, player: u8, code: u32) -> Vec<CardId> {
+        let mut cards: Vec<CardId> = self.player_effects(player, code).into_iter()
+            .filter_map(|eid| self.effects.get(eid.0 as usize).map(|e| e.owner))
+            .collect();
+        cards.sort();
+        cards.dedup();
+        cards
+    }
+
+    /// Summon procedures of the card usable from its current location.
+    pub fn special_summon_procedures(&self, card_id: CardId) -> Vec<EffectId> {
+        let Some(card) = self.cards.get(card_id.0 as usize) else {
+            return Vec::new();
+        };
+        card.effects.iter().copied()
+            .filter(|eid| self.effects.get(eid.0 as usize)
+                .is_some_and(|e| e.code == EFFECT_SPSUMMON_PROC && card.location.bits() & e.range != 0))
+            .collect()
+    }
+
+    /// Number of free main monster zones of a player.
+    pub fn free_mzone_count(&self, player: u8) -> usize {
+        if player > 1 {
+            return 0;
+        }
+        (0..5u8).filter(|&s| self.is_location_useable(player, Location::MZONE, s)).count()
+    }
+
+    /// Monster zones of `target_player` a card could be special summoned into, as a mask of sequences.
+    pub fn summon_zones(&self, card_id: CardId, target_player: u8) -> u32 {
+        match self.get_card(card_id) {
+            Some(card) if card.location == Location::EXTRA => {
+                self.extra_summon_zones(target_player, Some(card_id), &[]).into_iter().fold(0, |mask, s| mask | 1 << s)
+            }
+            Some(_) => self.useable_zones(target_player, Location::MZONE, 0x1f),
+            None => 0,
+        }
+    }
+
+    /// Put a card into monster zone `seq` of `target_player` as a special summon: MSG_MOVE, then MSG_SPSUMMONING.
+    pub fn special_summon_place(&mut self, card_id: CardId, sumtype: u32, target_player: u8, position: CardPosition, seq: u8) -> bool {
+        let Some(card) = self.get_card(card_id) else {
+            return false;
+        };
+        if !self.remove_from_location(card_id) {
+            return false;
+        }
+        let from = self.info_location(card_id);
+        self.place_card(card_id, target_player, Location::MZONE, seq, position.clone());
+        if let Some(c) = self.cards.get_mut(card_id.0 as usize) {
+            c.reason = REASON_SPSUMMON;
+            c.summon_info = (SUMMON_TYPE_SPECIAL | sumtype) & 0xff00ffff | ((card.location.bits() & 0xff) << 16);
+            c.set_status(CardStatus::SPSUMMON_STEP | CardStatus::SUMMON_TURN);
+        }
+        let msg = MsgSpSummoning {
+            code: card.code,
+            player: target_player,
+            loc: Location::MZONE.bits() as u8,
+            seq,
+            pos: position.bits() as u8,
+            attack: None,
+            level: None,
+        };
+        self.write_move_message(card_id, from, REASON_SPSUMMON);
+        self.write_message(&msg.encode());
+        true
+    }
+}
+
+/// Whether `player` may special summon `card_id` (EFFECT_CANNOT_SPECIAL_SUMMON).
+pub fn is_player_can_special_summon(lua: &Lua, player: u8, card_id: Option<CardId>, sumtype: u32, position: u32, target_player: u8, reason_effect: Option<EffectId>) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let blockers = data.lock().unwrap().player_effects(player, EFFECT_CANNOT_SPECIAL_SUMMON);
+    for eid in blockers {
+        let Some(target) = effect_function(lua, &data, eid, |e| &e.target) else {
+            return Ok(false);
+        };
+        // Without a card only blanket restrictions apply
+        let Some(card_id) = card_id else { continue };
+        let blocked: bool = target.call((eid, card_id, player, SUMMON_TYPE_SPECIAL | sumtype, position, target_player, reason_effect))?;
+        if blocked {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Legality of special summoning a card by an effect (card::is_can_be_special_summoned).
+#[allow(clippy::too_many_arguments)]
+pub fn is_can_be_special_summoned(lua: &Lua, card_id: CardId, reason_effect: Option<EffectId>, sumtype: u32, sumplayer: u8, nocheck: bool, nolimit: bool, position: u32, target_player: u8) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let conditions = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        if card.location == Location::MZONE || !card.current_stats.type_.contains(CardType::MONSTER) {
+            return Ok(false);
+        }
+        if card.location == Location::REMOVED && card.position.intersects(CardPosition::FACEDOWN) {
+            return Ok(false);
+        }
+        // "Must first be properly summoned": until the procedure is completed the card may only be summoned
+        // ignoring the limit, or, from the hand or Deck, ignoring the summon conditions as well
+        if !card.has_status(CardStatus::PROC_COMPLETE) && !data_guard.card_effects(card_id, EFFECT_REVIVE_LIMIT).is_empty() {
+            let blocked = if card.location.intersects(Location::SZONE | Location::GRAVE | Location::REMOVED) {
+                !nolimit
+            } else if card.location.intersects(Location::HAND | Location::DECK) {
+                !nocheck && !nolimit
+            } else {
+                // Face-up Pendulum monsters in the Extra Deck were never properly summoned either
+                !nolimit && card.location == Location::EXTRA
+                    && card.current_stats.type_.contains(CardType::PENDULUM)
+                    && card.position.intersects(CardPosition::FACEUP)
+            };
+            if blocked {
+                return Ok(false);
+            }
+        }
+        if nocheck { Vec::new() } else { data_guard.card_effects(card_id, EFFECT_SPSUMMON_CONDITION) }
+    };
+    for eid in conditions {
+        if effect_value(lua, &data, eid, (eid, reason_effect, sumplayer, SUMMON_TYPE_SPECIAL | sumtype, position, target_player))? == 0 {
+            return Ok(false);
+        }
+    }
+    is_player_can_special_summon(lua, sumplayer, Some(card_id), sumtype, position, target_player, reason_effect)
+}
+
+/// First procedure whose condition currently holds, if the card may use one at all.
+pub fn special_summon_procedure(lua: &Lua, card_id: CardId, player: u8) -> mlua::Result<Option<EffectId>> {
+    let data = duel_data(lua);
+    let (procedures, free) = {
+        let data_guard = data.lock().unwrap();
+        (data_guard.special_summon_procedures(card_id), data_guard.free_mzone_count(player))
+    };
+    if free == 0 {
+        return Ok(None);
+    }
+    for eid in procedures {
+        let ok = match effect_function(lua, &data, eid, |e| &e.condition) {
+            Some(condition) => condition.call::<_, bool>((eid, card_id))?,
+            None => true,
+        };
+        if ok && is_player_can_special_summon(lua, player, Some(card_id), 0, CardPosition::FACEUP_ATTACK.bits(), player, Some(eid))? {
+            return Ok(Some(eid));
+        }
+    }
+    Ok(None)
+}
+
+/// Registry name of the function running a summon procedure (Duel.SpecialSummonRule).
+pub(crate) const SPECIAL_SUMMON_RULE: &str = "osiris.summon.rule";
+
+/// A summon procedure runs in Lua, so its target and operation can wait on the host's selections.
+/// The chunk gets the Rust helpers that look up the procedure and place the card once it is paid for.
+const RULE: &str = r#"
+    local procedure, place = ...
+    return function(player, c)
+        local e, target, operation = procedure(player, c)
+        if not e then
+            return false
+        end
+        if target and not target(e, player, nil, 0, 0, nil, 0, 0, true, c) then
+            return false
+        end
+        if operation then
+            operation(e, player, nil, 0, 0, nil, 0, 0, c)
+        end
+        return place(player, c, e)
+    end
+"#;
+
+/// Place a card summoned by procedure `eid`, which has been paid for, in a zone its player picks,
+/// and raise EVENT_SPSUMMON_SUCCESS. Gives whether the card was placed.
+fn place_by_procedure(lua: &Lua, player: u8, card_id: CardId, eid: EffectId) -> mlua::Result<MultiValue<'_>> {
+    let data = duel_data(lua);
+    let (value, position, target_player, zones) = {
+        let data_guard = data.lock().unwrap();
+        let (value, flag, range) = {
+            let e = &data_guard.effects[eid.0 as usize];
+            (e.value, e.flag, e.target_range)
+        };
+        // With SPSUM_PARAM the target range carries the position and whether it goes to the opponent's field
+        let (position, target_player) = if flag & EFFECT_FLAG_SPSUM_PARAM != 0 {
+            let pos = if range.0 != 0 { CardPosition::from_bits_truncate(range.0) } else { CardPosition::FACEUP_ATTACK };
+            (pos, if range.1 != 0 { 1 - player } else { player })
+        } else {
+            (CardPosition::FACEUP_ATTACK, player)
+        };
+        (value, position, target_player, data_guard.summon_zones(card_id, target_player))
+    };
+    select_place(lua, target_player, Location::MZONE, zones, move |lua, seq| {
+        let data = duel_data(lua);
+        let placed = seq.is_some_and(|seq| {
+            let mut data_guard = data.lock().unwrap();
+            let placed = data_guard.special_summon_place(card_id, value, target_player, position, seq);
+            if placed {
+                data_guard.cards[card_id.0 as usize].set_status(CardStatus::PROC_COMPLETE);
+                data_guard.write_message(&MsgSpSummoned.encode());
+            }
+            placed
+        });
+        if placed {
+            finish_special_summon(lua, data, vec![card_id], player);
+        }
+        ready(lua, placed)
+    })
+}
+
+/// Clear the in-progress status and raise EVENT_SPSUMMON_SUCCESS for the summoned cards.
+pub(crate) fn finish_special_summon(lua: &Lua, data: Arc<Mutex<DuelData>>, cards: Vec<CardId>, player: u8) {
+    let mut g = Group::new();
+    {
+        let mut data_guard = data.lock().unwrap();
+        for card_id in cards {
+            if let Some(c) = data_guard.cards.get_mut(card_id.0 as usize) {
+                c.clear_status(CardStatus::SPSUMMON_STEP);
+            }
+            g.0.insert(card_id);
+        }
+    }
+    Duel::raise_event_static(lua, data, EVENT_SPSUMMON_SUCCESS, Some(g), player, None);
+}
+
+/// `player` picks one of `positions` for a card (MSG_SELECT_POSITION); a single position is taken as is.
+pub fn select_position<'lua, F>(lua: &'lua Lua, player: u8, card_id: CardId, positions: u32, then: F) -> mlua::Result<MultiValue<'lua>>
+where
+    F: for<'a> FnOnce(&'a Lua, CardPosition) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+{
+    if positions.count_ones() <= 1 {
+        return then(lua, CardPosition::from_bits_truncate(positions));
+    }
+    let code = duel_data(lua).lock().unwrap().cards.get(card_id.0 as usize).map(|c| c.code).unwrap_or(0);
+    let msg = MsgSelectPosition { player, code, positions: positions as u8 };
+    ask(lua, Prompt::SelectPosition { positions }, &msg.encode(), move |lua, answer| match answer {
+        Response::Position(pos) => then(lua, CardPosition::from_bits_truncate(pos)),
+        other => Err(unexpected(other)),
+    })
+}
+
+/// The arguments of a special summon by effect, shared by the step of each card.
+#[derive(Clone, Copy)]
+struct SpecialSummonStep {
+    sumtype: u32,
+    sumplayer: u8,
+    target_player: u8,
+    nocheck: bool,
+    nolimit: bool,
+    positions: u32,
+}
+
+impl SpecialSummonStep {
+    /// Summon `cards` one after the other: the target player picks the zone, then the summoning player the
+    /// position when several are allowed (e.g. POS_FACEUP). `then` gets the cards that were placed.
+    fn each<'lua, F>(self, lua: &'lua Lua, mut cards: Vec<CardId>, mut placed: Vec<CardId>, then: F) -> mlua::Result<MultiValue<'lua>>
+    where
+        F: for<'a> FnOnce(&'a Lua, Vec<CardId>) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+    {
+        loop {
+            if cards.is_empty() {
+                return then(lua, placed);
+            }
+            let card_id = cards.remove(0);
+            if !is_can_be_special_summoned(lua, card_id, None, self.sumtype, self.sumplayer, self.nocheck, self.nolimit, self.positions, self.target_player)? {
+                continue;
+            }
+            let zones = duel_data(lua).lock().unwrap().summon_zones(card_id, self.target_player);
+            return select_place(lua, self.target_player, Location::MZONE, zones, move |lua, seq| {
+                let Some(seq) = seq else {
+                    return self.each(lua, cards, placed, then);
+                };
+                select_position(lua, self.sumplayer, card_id, self.positions, move |lua, position| {
+                    let data = duel_data(lua);
+                    let mut data_guard = data.lock().unwrap();
+                    if data_guard.special_summon_place(card_id, self.sumtype, self.target_player, position, seq) {
+                        data_guard.spsummon_step_cards.push(card_id);
+                        placed.push(card_id);
+                    }
+                    drop(data_guard);
+                    self.each(lua, cards, placed, then)
+                })
+            });
+        }
+    }
+}
+
+/// Special summon by effect, one card at a time into zones the target player picks; the summon is
+/// announced by complete_special_summon. `then` gets the cards that were placed.
+#[allow(clippy::too_many_arguments)]
+fn special_summon_step<'lua, F>(lua: &'lua Lua, cards: Vec<CardId>, sumtype: u32, sumplayer: u8, target_player: u8, nocheck: bool, nolimit: bool, position: u32, then: F) -> mlua::Result<MultiValue<'lua>>
+where
+    F: for<'a> FnOnce(&'a Lua, Vec<CardId>) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+{
+    let positions = if position & 0xf == 0 { CardPosition::FACEUP_ATTACK.bits() } else { position & 0xf };
+    SpecialSummonStep { sumtype, sumplayer, target_player, nocheck, nolimit, positions }.each(lua, cards, Vec::new(), then)
+}
+
+/// Register the special summon functions on the global `Duel` table.
+pub fn register_summon_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.SpecialSummon(targets, sumtype, sumplayer, target_player, nocheck, nolimit, pos) -> count
+    set_prompting(lua, &duel_table, "SpecialSummon", |lua, (targets, sumtype, sumplayer, target_player, nocheck, nolimit, pos): (mlua::Value, u32, u8, u8, bool, bool, u32)| {
+        special_summon_step(lua, cards_of(&targets), sumtype, sumplayer, target_player, nocheck, nolimit, pos, move |lua, placed| {
+            complete_special_summon(lua, sumplayer);
+            ready(lua, placed.len() as u32)
+        })
+    })?;
+
+    // Duel.SpecialSummonStep(c, sumtype, sumplayer, target_player, nocheck, nolimit, pos) -> bool
+    set_prompting(lua, &duel_table, "SpecialSummonStep", |lua, (card, sumtype, sumplayer, target_player, nocheck, nolimit, pos): (AnyUserData, u32, u8, u8, bool, bool, u32)| {
+        let card_id = *card.borrow::<CardId>()?;
+        special_summon_step(lua, vec![card_id], sumtype, sumplayer, target_player, nocheck, nolimit, pos, |lua, placed| ready(lua, !placed.is_empty()))
+    })?;
+
+    // Duel.SpecialSummonComplete() -> count
+    duel_table.set("SpecialSummonComplete", lua.create_function(|lua, ()| {
+        let data = duel_data(lua);
+        let player = data.lock().unwrap().turn_player;
+        Ok(complete_special_summon(lua, player))
+    })?)?;
+
+    // Duel.SpecialSummonRule(player, c) summons through the card's procedure
+    let procedure = lua.create_function(|lua, (player, card_id): (u8, CardId)| {
+        let Some(eid) = special_summon_procedure(lua, card_id, player)? else {
+            return Ok((None, None, None));
+        };
+        let data = duel_data(lua);
+        Ok((Some(eid), effect_function(lua, &data, eid, |e| &e.target), effect_function(lua, &data, eid, |e| &e.operation)))
+    })?;
+    let place = wrap(lua, lua.create_function(|lua, (player, card_id, e): (u8, CardId, AnyUserData)| {
+        let eid = *e.borrow::<EffectId>()?;
+        place_by_procedure(lua, player, card_id, eid)
+    })?)?;
+    let rule: Function = lua.load(RULE).set_name("SpecialSummonRule").call((procedure, place))?;
+    lua.set_named_registry_value(SPECIAL_SUMMON_RULE, rule.clone())?;
+    duel_table.set("SpecialSummonRule", rule)?;
+
+    // Duel.IsPlayerCanSpecialSummon(player[, sumtype, pos, target_player, c]) -> bool
+    duel_table.set("IsPlayerCanSpecialSummon", lua.create_function(|lua, (player, sumtype, pos, target_player, card): (u8, Option<u32>, Option<u32>, Option<u8>, Option<AnyUserData>)| {
+        let card_id = match card {
+            Some(ud) => Some(*ud.borrow::<CardId>()?),
+            None => None,
+        };
+        is_player_can_special_summon(lua, player, card_id, sumtype.unwrap_or(0), pos.unwrap_or(CardPosition::FACEUP.bits()), target_player.unwrap_or(player), None)
+    })?)?;
+
+    // Duel.GetLocationCount(player, location[, use_player, reason, zone]) -> free zones among `zone`
+    duel_table.set("GetLocationCount", lua.create_function(|lua, (player, location, _use_player, _reason, zone): (u8, u32, Option<u8>, Option<u32>, Option<u32>)| {
+        crate::core::zone::refresh_disabled_field(lua)?;
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        Ok(data_guard.location_count(player, Location::from_bits_truncate(location), zone.unwrap_or(0xff)) as u32)
+    })?)?;
+
+    // Duel.GetLocationCountFromEx(player[, rp, sg, c]) -> zones an Extra Deck monster could use once `sg` has left
+    duel_table.set("GetLocationCountFromEx", lua.create_function(|lua, (player, _rp, sg, card): (u8, Option<u8>, mlua::Value, mlua::Value)| {
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        let leaving = cards_of(&sg);
+        let card = cards_of(&card).first().copied();
+        Ok(data_guard.extra_summon_zones(player, card, &leaving).len() as u32)
+    })?)?;
+
+    // Duel.IsPlayerAffectedByEffect(player, code) -> the first effect with `code` that applies to player, or nil
+    duel_table.set("IsPlayerAffectedByEffect", lua.create_function(|lua, (player, code): (u8, u32)| {
+        Ok(duel_data(lua).lock().unwrap().player_effects(player, code).first().copied())
+    })?)?;
+
+    // Duel.GetMustMaterial(player, code) -> Group of the cards player must use as material
+    duel_table.set("GetMustMaterial", lua.create_function(|lua, (player, code): (u8, u32)| {
+        Ok(Group(duel_data(lua).lock().unwrap().must_materials(player, code).into_iter().collect()))
+    })?)?;
+
+    // Duel.CheckMustMaterial(player, cards, code) - cards (a Card, Group or nil) include every must material
+    duel_table.set("CheckMustMaterial", lua.create_function(|lua, (player, cards, code): (u8, mlua::Value, u32)| {
+        let cards = cards_of(&cards);
+        Ok(duel_data(lua).lock().unwrap().must_materials(player, code).iter().all(|c| cards.contains(c)))
+    })?)?;
+
+    Ok(())
+}
+
+/// Announce the cards placed by SpecialSummonStep and raise their success event.
+fn complete_special_summon(lua: &Lua, player: u8) -> u32 {
+    let data = duel_data(lua);
+    let cards = {
+        let mut data_guard = data.lock().unwrap();
+        let cards = std::mem::take(&mut data_guard.spsummon_step_cards);
+        if !cards.is_empty() {
+            data_guard.write_message(&MsgSpSummoned.encode());
+        }
+        cards
+    };
+    let count = cards.len() as u32;
+    if count > 0 {
+        finish_special_summon(lua, data, cards, player);
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::database::CardData;
+    use crate::core::enums::{CardStatus, CardType, Location};
+    use crate::core::messages::MsgType;
+    use crate::core::processor::ProcessResult;
+    use crate::core::testing::{card, duel_with};
+    use crate::core::types::CardId;
+
+    fn monster(code: u32, level: u32) -> CardData {
+        card(code, CardType::MONSTER | CardType::EFFECT, level)
+    }
+
+    #[test]
+    fn procedure_summons_from_hand() {
+        let mut duel = duel_with(&[monster(7001, 0)], r#"
+            proc_card = Debug.AddCard(7001, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            fodder = Debug.AddCard(7002, 0, 0, LOCATION_HAND, 1, POS_FACEDOWN)
+            local e = Effect.CreateEffect(proc_card)
+            e:SetType(EFFECT_TYPE_FIELD)
+            e:SetCode(EFFECT_SPSUMMON_PROC)
+            e:SetProperty(EFFECT_FLAG_UNCOPYABLE)
+            e:SetRange(LOCATION_HAND)
+            e:SetCondition(function(e, c) return Duel.GetLocationCount(c:GetControler(), LOCATION_MZONE) > 0 end)
+            e:SetOperation(function(e, tp, eg, ep, ev, re, r, rp, c) Duel.SendtoGrave(fodder, REASON_COST) end)
+            proc_card:RegisterEffect(e)
+        "#);
+        duel.get_message();
+
+        duel.special_summon_rule(0, CardId::new(0));
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the zone is chosen by the player");
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 2]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+
+        let data = duel.data.lock().unwrap();
+        let card = data.get_card(CardId::new(0)).unwrap();
+        assert_eq!((card.location, card.sequence), (Location::MZONE, 2));
+        assert!(card.has_status(CardStatus::PROC_COMPLETE));
+        assert!(!card.has_status(CardStatus::SPSUMMON_STEP));
+        assert_eq!(card.summon_type(), 0x40000000);
+        assert_eq!(card.summon_location(), Location::HAND.bits());
+        assert_eq!(data.get_card(CardId::new(1)).unwrap().location, Location::GRAVE, "cost was paid");
+        // The card moves first, then MSG_SPSUMMONING (9 bytes) and MSG_SPSUMMONED announce it
+        let tail = &data.message_buffer[data.message_buffer.len() - 27..];
+        assert_eq!((tail[0], tail[17], tail[26]), (MsgType::Move.id(), MsgType::SPSummoning.id(), MsgType::SPSummoned.id()));
+    }
+
+    #[test]
+    fn procedure_errors_reach_the_host() {
+        let mut duel = duel_with(&[monster(7001, 0)], r#"
+            local c = Debug.AddCard(7001, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            local e = Effect.CreateEffect(c)
+            e:SetType(EFFECT_TYPE_FIELD)
+            e:SetCode(EFFECT_SPSUMMON_PROC)
+            e:SetRange(LOCATION_HAND)
+            e:SetOperation(function() error("broken procedure") end)
+            c:RegisterEffect(e)
+        "#);
+        duel.special_summon_rule(0, CardId::new(0));
+        assert_eq!(duel.process(), ProcessResult::Error);
+        assert!(duel.take_error().is_some_and(|e| e.to_string().contains("broken procedure")));
+        assert!(duel.take_error().is_none());
+        assert_eq!(duel.data.lock().unwrap().get_card(CardId::new(0)).unwrap().location, Location::HAND);
+    }
+
+    #[test]
+    fn revive_limit_and_summon_restrictions() {
+        let mut duel = duel_with(&[monster(7101, 0), monster(7102, 0), monster(7103, 0)], r#"
+            nomi = Debug.AddCard(7101, 0, 0, LOCATION_GRAVE, 0, POS_FACEUP)
+            normal = Debug.AddCard(7102, 0, 0, LOCATION_GRAVE, 1, POS_FACEUP)
+            picky = Debug.AddCard(7103, 0, 0, LOCATION_GRAVE, 2, POS_FACEUP)
+            nomi:EnableReviveLimit()
+            local c = Effect.CreateEffect(picky)
+            c:SetType(EFFECT_TYPE_SINGLE)
+            c:SetCode(EFFECT_SPSUMMON_CONDITION)
+            c:SetValue(function(e, se, sp, st) return sp == 1 end)
+            picky:RegisterEffect(c)
+        "#);
+        let (a, b, c): (bool, bool, bool) = duel.lua.load(r#"
+            return nomi:IsCanBeSpecialSummoned(nil, 0, 0, false, false),
+                normal:IsCanBeSpecialSummoned(nil, 0, 0, false, false),
+                picky:IsCanBeSpecialSummoned(nil, 0, 0, false, false)
+        "#).eval().unwrap();
+        assert!(!a, "revive limited card was never properly summoned");
+        assert!(b);
+        assert!(!c, "summon condition rejects player 0");
+
+        duel.execute_operation(duel.lua.load(r#"
+            nomi:CompleteProcedure()
+            local lock = Effect.CreateEffect(normal)
+            lock:SetType(EFFECT_TYPE_FIELD)
+            lock:SetCode(EFFECT_CANNOT_SPECIAL_SUMMON)
+            lock:SetProperty(EFFECT_FLAG_PLAYER_TARGET)
+            lock:SetTargetRange(1, 0)
+            lock:SetTarget(function(e, c) return c:GetCode() == 7102 end)
+            Duel.RegisterEffect(lock, 0)
+            local g = Group.CreateGroup()
+            g:AddCard(nomi)
+            g:AddCard(normal)
+            count = Duel.SpecialSummon(g, 0, 0, 0, false, false, POS_FACEUP_DEFENSE)
+        "#).into_function().unwrap()).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting, "a zone for the one that may be summoned");
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 0]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let count: u32 = duel.lua.globals().get("count").unwrap();
+        assert_eq!(count, 1, "only the completed revive-limit card may be summoned");
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.get_card(CardId::new(0)).unwrap().location, Location::MZONE);
+        assert_eq!(data.get_card(CardId::new(1)).unwrap().location, Location::GRAVE);
+    }
+
+    #[test]
+    fn revive_limit_depends_on_location_and_flags() {
+        let duel = duel_with(&[monster(7201, 0), CardData { type_: (CardType::MONSTER | CardType::EFFECT | CardType::PENDULUM).bits(), ..monster(7202, 0) }, monster(7203, 0)], r#"
+            hand = Debug.AddCard(7201, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            pendulum = Debug.AddCard(7202, 0, 0, LOCATION_EXTRA, 0, POS_FACEUP_DEFENSE)
+            fusion = Debug.AddCard(7203, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+            for _, c in ipairs({hand, pendulum, fusion}) do c:EnableReviveLimit() end
+        "#);
+        let checks: Vec<bool> = duel.lua.load(r#"
+            return {
+                hand:IsCanBeSpecialSummoned(nil, 0, 0, false, false),
+                hand:IsCanBeSpecialSummoned(nil, 0, 0, true, false),
+                pendulum:IsCanBeSpecialSummoned(nil, 0, 0, true, false),
+                pendulum:IsCanBeSpecialSummoned(nil, 0, 0, false, true),
+                fusion:IsCanBeSpecialSummoned(nil, 0, 0, false, false),
+            }
+        "#).eval().unwrap();
+        assert_eq!(checks, vec![
+            false, true, // from the hand, ignoring the summon conditions is enough
+            false, true, // a face-up Pendulum in the Extra Deck only ignoring the limit
+            true,        // a face-down Extra Deck monster is summoned by its own procedure
+        ]);
+    }
+
+    #[test]
+    fn must_materials_and_card_place_checks() {
+        let duel = duel_with(&[monster(7301, 3), monster(7302, 4)], r#"
+            forced = Debug.AddCard(7301, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            spare = Debug.AddCard(7302, 0, 0, LOCATION_MZONE, 1, POS_FACEUP_ATTACK)
+            theirs = Debug.AddCard(7303, 1, 1, LOCATION_HAND, 0, POS_FACEDOWN)
+            local e = Effect.CreateEffect(forced)
+            e:SetType(EFFECT_TYPE_FIELD)
+            e:SetCode(EFFECT_MUST_BE_SMATERIAL)
+            e:SetProperty(EFFECT_FLAG_PLAYER_TARGET)
+            e:SetRange(LOCATION_MZONE)
+            e:SetTargetRange(1, 0)
+            forced:RegisterEffect(e)
+        "#);
+        let (affected, unaffected, must, with, without): (bool, bool, u32, bool, bool) = duel.lua.load(r#"
+            local g = Group.CreateGroup()
+            g:AddCard(spare)
+            local without = Duel.CheckMustMaterial(0, g, EFFECT_MUST_BE_SMATERIAL)
+            g:AddCard(forced)
+            return Duel.IsPlayerAffectedByEffect(0, EFFECT_MUST_BE_SMATERIAL) ~= nil,
+                Duel.IsPlayerAffectedByEffect(1, EFFECT_MUST_BE_SMATERIAL) == nil,
+                Duel.GetMustMaterial(0, EFFECT_MUST_BE_SMATERIAL):GetFirst():GetCode(),
+                Duel.CheckMustMaterial(0, g, EFFECT_MUST_BE_SMATERIAL) and Duel.CheckMustMaterial(1, nil, EFFECT_MUST_BE_SMATERIAL),
+                without
+        "#).eval().unwrap();
+        assert!(affected && unaffected, "the effect only targets its controller");
+        assert_eq!(must, 7301);
+        assert!(with);
+        assert!(!without);
+
+        duel.data.lock().unwrap().cards[1].set_status(CardStatus::SPSUMMON_STEP);
+        let checks: Vec<bool> = duel.lua.load(r#"
+            return {forced:IsLocation(LOCATION_MZONE), forced:IsLocation(LOCATION_ONFIELD), theirs:IsLocation(LOCATION_MZONE),
+                spare:IsLocation(LOCATION_MZONE), forced:IsControler(0), theirs:IsControler(0)}
+        "#).eval().unwrap();
+        assert_eq!(checks, vec![true, true, false, false, true, false], "a monster still being summoned is not there yet");
+
+        // Duel.SetSelectedCard forces a card into the next sum check only
+        let (forced_in, used_up): (bool, bool) = duel.lua.load(r#"
+            local g = Group.CreateGroup()
+            g:AddCard(spare)
+            Duel.SetSelectedCard(forced)
+            return g:CheckWithSumEqual(Card.GetLevel, 7, 1, 1), g:CheckWithSumEqual(Card.GetLevel, 7, 1, 1)
+        "#).eval().unwrap();
+        assert!(forced_in, "3 + 4 with the forced card");
+        assert!(!used_up);
+    }
+}
@@ -0,0 +1,354 @@
+//! Synchro summons: synchro levels, tuner / non-tuner material checks and the level-sum selection aux.AddSynchroProcedure builds on.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::group::Group;
+use crate::core::prompt::{ready, select_cards, select_sum, set_prompting};
+use crate::core::response::find_sum;
+use crate::core::scripting::{duel_data, effect_value};
+use crate::core::types::CardId;
+use mlua::{AnyUserData, Function, Lua, MultiValue, RegistryKey};
+
+impl DuelData {
+    /// Default synchro material pool of a player: face-up monsters in the monster zones.
+    pub fn synchro_material_pool(&self, player: u8) -> Vec<CardId> {
+        self.field.mzone[player as usize].iter().flatten().copied()
+            .filter(|id| self.cards.get(id.0 as usize).is_some_and(|c| {
+                c.current_stats.type_.contains(CardType::MONSTER) && c.position.intersects(CardPosition::FACEUP)
+            }))
+            .collect()
+    }
+
+    /// Whether a card can be used as the tuner of a synchro summon.
+    pub fn is_tuner(&self, card_id: CardId) -> bool {
+        self.cards.get(card_id.0 as usize).is_some_and(|c| c.current_stats.type_.contains(CardType::TUNER))
+    }
+
+    /// Whether a card can be used as a non-tuner: not a tuner, or a tuner under EFFECT_NONTUNER.
+    pub fn is_not_tuner(&self, card_id: CardId) -> bool {
+        !self.is_tuner(card_id) || !self.card_effects(card_id, EFFECT_NONTUNER).is_empty()
+    }
+}
+
+/// Level of a card as synchro material for `synchro`, packed as `level | alternate << 16` (EFFECT_SYNCHRO_LEVEL).
+pub fn synchro_level(lua: &Lua, card_id: CardId, synchro: CardId) -> mlua::Result<u32> {
+    let data = duel_data(lua);
+    let (level, modifier) = {
+        let data_guard = data.lock().unwrap();
+        let level = data_guard.cards.get(card_id.0 as usize).map(|c| c.current_stats.level).unwrap_or(0);
+        (level, data_guard.card_effects(card_id, EFFECT_SYNCHRO_LEVEL).first().copied())
+    };
+    match modifier {
+        Some(eid) => effect_value(lua, &data, eid, (eid, synchro)),
+        None => Ok(level),
+    }
+}
+
+/// Whether a card may be used as synchro material for `synchro` alongside `tuner` (EFFECT_CANNOT_BE_SYNCHRO_MATERIAL).
+pub fn is_can_be_synchro_material(lua: &Lua, card_id: CardId, synchro: Option<CardId>, tuner: Option<CardId>) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let blockers = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        let type_ = &card.current_stats.type_;
+        if !type_.contains(CardType::MONSTER) || type_.intersects(CardType::XYZ | CardType::LINK) {
+            return Ok(false);
+        }
+        if card.location == Location::MZONE && !card.position.intersects(CardPosition::FACEUP) {
+            return Ok(false);
+        }
+        data_guard.card_effects(card_id, EFFECT_CANNOT_BE_SYNCHRO_MATERIAL)
+    };
+    for eid in blockers {
+        if effect_value(lua, &data, eid, (eid, synchro, tuner))? != 0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Material pool for `synchro`: `mg` when given, otherwise the pool of the synchro monster's controller.
+fn material_pool(lua: &Lua, synchro: CardId, mg: Option<AnyUserData>) -> mlua::Result<Vec<CardId>> {
+    if let Some(mg) = mg {
+        return Ok(mg.borrow::<Group>()?.0.iter().copied().collect());
+    }
+    let data = duel_data(lua);
+    let data_guard = data.lock().unwrap();
+    let player = data_guard.get_card(synchro).map(|c| c.controller).unwrap_or(0);
+    Ok(data_guard.synchro_material_pool(player))
+}
+
+fn passes(filter: &Option<Function>, card_id: CardId, synchro: CardId) -> mlua::Result<bool> {
+    match filter {
+        Some(f) => f.call((card_id, synchro)),
+        None => Ok(true),
+    }
+}
+
+/// Tuners in `pool` that pass `f1`.
+fn tuner_candidates(lua: &Lua, synchro: CardId, f1: &Option<Function>, pool: &[CardId]) -> mlua::Result<Vec<CardId>> {
+    let mut tuners = Vec::new();
+    for &id in pool {
+        let is_tuner = duel_data(lua).lock().unwrap().is_tuner(id);
+        if id != synchro && is_tuner
+            && is_can_be_synchro_material(lua, id, Some(synchro), None)?
+            && passes(f1, id, synchro)?
+        {
+            tuners.push(id);
+        }
+    }
+    Ok(tuners)
+}
+
+/// Non-tuners in `pool` that pass `f2` and may go with `tuner`, paired with their synchro levels.
+fn nontuner_candidates(lua: &Lua, synchro: CardId, tuner: CardId, f2: &Option<Function>, pool: &[CardId]) -> mlua::Result<Vec<(CardId, u32)>> {
+    let mut cards = Vec::new();
+    for &id in pool {
+        let is_not_tuner = duel_data(lua).lock().unwrap().is_not_tuner(id);
+        if id != synchro && id != tuner && is_not_tuner
+            && is_can_be_synchro_material(lua, id, Some(synchro), Some(tuner))?
+            && passes(f2, id, synchro)?
+        {
+            cards.push((id, synchro_level(lua, id, synchro)?));
+        }
+    }
+    Ok(cards)
+}
+
+/// Non-tuner candidates with their synchro levels, and the indices of a default choice among them.
+type SumChoice = (Vec<(CardId, u32)>, Vec<usize>);
+
+/// The search a synchro summon runs: the synchro monster, its material filters and the non-tuner count.
+struct SynchroSearch<'lua> {
+    synchro: CardId,
+    f1: Option<Function<'lua>>,
+    f2: Option<Function<'lua>>,
+    min: usize,
+    max: usize,
+}
+
+impl SynchroSearch<'_> {
+    fn level(&self, lua: &Lua) -> u32 {
+        duel_data(lua).lock().unwrap().get_card(self.synchro).map(|c| c.current_stats.level).unwrap_or(0)
+    }
+
+    /// Non-tuner candidates for `tuner` and a default choice among them, if any choice reaches the level.
+    fn solve(&self, lua: &Lua, tuner: CardId, pool: &[CardId], smat: Option<CardId>) -> mlua::Result<Option<SumChoice>> {
+        let mut must = vec![synchro_level(lua, tuner, self.synchro)?];
+        let mut cards = nontuner_candidates(lua, self.synchro, tuner, &self.f2, pool)?;
+        let (mut min, mut max) = (self.min, self.max);
+        // A forced non-tuner counts toward the material count but is not offered for selection
+        if let Some(forced) = smat.filter(|&s| s != tuner) {
+            let Some(pos) = cards.iter().position(|&(id, _)| id == forced) else {
+                return Ok(None);
+            };
+            must.push(cards.remove(pos).1);
+            min = min.saturating_sub(1);
+            max = max.saturating_sub(1);
+        }
+        let params: Vec<u32> = cards.iter().map(|c| c.1).collect();
+        Ok(find_sum(&must, &params, self.level(lua), false, min, max).map(|picked| (cards, picked)))
+    }
+
+    fn check(&self, lua: &Lua, pool: &[CardId], smat: Option<CardId>) -> mlua::Result<bool> {
+        for tuner in tuner_candidates(lua, self.synchro, &self.f1, pool)? {
+            if self.solve(lua, tuner, pool, smat)?.is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Let `player` pick the non-tuners for `tuner` with MSG_SELECT_SUM; gives the full material group.
+    fn select_with_tuner<'a>(&self, lua: &'a Lua, player: u8, tuner: CardId, pool: &[CardId], smat: Option<CardId>) -> mlua::Result<MultiValue<'a>> {
+        let Some((cards, _)) = self.solve(lua, tuner, pool, smat)? else {
+            return ready(lua, Group::new());
+        };
+        let mut must = vec![(tuner, synchro_level(lua, tuner, self.synchro)?)];
+        if let Some(forced) = smat.filter(|&s| s != tuner) {
+            must.push((forced, synchro_level(lua, forced, self.synchro)?));
+        }
+        let forced = must.len() - 1;
+        let level = self.level(lua);
+        let materials: Vec<CardId> = must.iter().map(|m| m.0).collect();
+        select_sum(lua, player, level, false, self.min.saturating_sub(forced), self.max.saturating_sub(forced), &must, cards, move |lua, chosen| {
+            ready(lua, Group(materials.into_iter().chain(chosen).collect()))
+        })
+    }
+
+    /// Let `player` pick the tuner, then the non-tuners that go with it.
+    fn select<'a>(&self, lua: &'a Lua, player: u8, pool: Vec<CardId>, smat: Option<CardId>) -> mlua::Result<MultiValue<'a>> {
+        let mut tuners = Vec::new();
+        for tuner in tuner_candidates(lua, self.synchro, &self.f1, &pool)? {
+            if self.solve(lua, tuner, &pool, smat)?.is_some() {
+                tuners.push(tuner);
+            }
+        }
+        // A forced tuner is the only choice
+        if let Some(s) = smat.filter(|s| tuners.contains(s)) {
+            tuners = vec![s];
+        }
+        // The non-tuners are searched once the tuner is chosen, so the filters are kept until then
+        let keep = |f: &Option<Function>| f.clone().map(|f| lua.create_registry_value(f)).transpose();
+        let (synchro, f1, f2, min, max) = (self.synchro, keep(&self.f1)?, keep(&self.f2)?, self.min, self.max);
+        select_cards(lua, player, tuners, 1, 1, move |lua, chosen| {
+            let Some(&tuner) = chosen.first() else {
+                return ready(lua, Group::new());
+            };
+            let restore = |key: Option<RegistryKey>| key.map(|k| lua.registry_value::<Function>(&k)).transpose();
+            let search = SynchroSearch { synchro, f1: restore(f1)?, f2: restore(f2)?, min, max };
+            search.select_with_tuner(lua, player, tuner, &pool, smat)
+        })
+    }
+}
+
+/// Register the synchro functions on the global `Duel` table.
+#[allow(clippy::type_complexity)]
+pub fn register_synchro_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.CheckSynchroMaterial(c, f1, f2, minc, maxc[, smat, mg])
+    duel_table.set("CheckSynchroMaterial", lua.create_function(|lua, (synchro, f1, f2, min, max, smat, mg): (CardId, Option<Function>, Option<Function>, usize, usize, Option<CardId>, Option<AnyUserData>)| {
+        let pool = material_pool(lua, synchro, mg)?;
+        SynchroSearch { synchro, f1, f2, min, max }.check(lua, &pool, smat)
+    })?)?;
+
+    // Duel.SelectSynchroMaterial(player, c, f1, f2, minc, maxc[, smat, mg]) -> Group
+    set_prompting(lua, &duel_table, "SelectSynchroMaterial", |lua, (player, synchro, f1, f2, min, max, smat, mg): (u8, CardId, Option<Function>, Option<Function>, usize, usize, Option<CardId>, Option<AnyUserData>)| {
+        let pool = material_pool(lua, synchro, mg)?;
+        SynchroSearch { synchro, f1, f2, min, max }.select(lua, player, pool, smat)
+    })?;
+
+    // Duel.CheckTunerMaterial(c, tuner, f1, f2, minc, maxc[, mg])
+    duel_table.set("CheckTunerMaterial", lua.create_function(|lua, (synchro, tuner, f1, f2, min, max, mg): (CardId, CardId, Option<Function>, Option<Function>, usize, usize, Option<AnyUserData>)| {
+        let pool = material_pool(lua, synchro, mg)?;
+        let search = SynchroSearch { synchro, f1, f2, min, max };
+        if !tuner_candidates(lua, synchro, &search.f1, &[tuner])?.contains(&tuner) {
+            return Ok(false);
+        }
+        Ok(search.solve(lua, tuner, &pool, None)?.is_some())
+    })?)?;
+
+    // Duel.SelectTunerMaterial(player, c, tuner, f1, f2, minc, maxc[, mg]) -> Group
+    set_prompting(lua, &duel_table, "SelectTunerMaterial", |lua, (player, synchro, tuner, f1, f2, min, max, mg): (u8, CardId, CardId, Option<Function>, Option<Function>, usize, usize, Option<AnyUserData>)| {
+        let pool = material_pool(lua, synchro, mg)?;
+        SynchroSearch { synchro, f1, f2, min, max }.select_with_tuner(lua, player, tuner, &pool, None)
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::enums::{CardStatus, CardType, Location, REASON_MATERIAL, REASON_SYNCHRO, SUMMON_TYPE_SYNCHRO};
+    use crate::core::messages::MsgType;
+    use crate::core::processor::ProcessResult;
+    use crate::core::testing::{card, duel_with};
+    use crate::core::types::CardId;
+
+    /// Synchro monster 9001 (level 7): 1 tuner + 1 or more non-tuners, through aux.AddSynchroProcedure.
+    const SETUP: &str = r#"
+        synchro = Debug.AddCard(9001, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+        tuner = Debug.AddCard(9002, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+        four = Debug.AddCard(9003, 0, 0, LOCATION_MZONE, 1, POS_FACEUP_ATTACK)
+        other = Debug.AddCard(9004, 0, 0, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)
+        synchro:EnableReviveLimit()
+        aux.AddSynchroProcedure(synchro, aux.Tuner(nil), aux.NonTuner(nil), 1)
+    "#;
+
+    #[test]
+    fn synchro_procedure_sends_tuner_and_non_tuner() {
+        let mut duel = duel_with(&[
+            card(9001, CardType::MONSTER | CardType::SYNCHRO, 7),
+            card(9002, CardType::MONSTER | CardType::TUNER, 3),
+            card(9003, CardType::MONSTER | CardType::NORMAL, 4),
+            card(9004, CardType::MONSTER | CardType::NORMAL, 2),
+        ], SETUP);
+        duel.get_message();
+
+        duel.special_summon_rule(0, CardId::new(0));
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the tuner is chosen first");
+        assert!(duel.get_message().contains(&MsgType::SelectCard.id()));
+        duel.set_responseb(&[1, 0]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "then the non-tuners that make up the level");
+        assert!(duel.get_message().contains(&MsgType::SelectSum.id()));
+        // 3 + 2 misses level 7
+        duel.set_responseb(&[2, 0, 1]);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[2, 0, 0]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "then the zone");
+        assert!(duel.get_message().contains(&MsgType::SelectPlace.id()));
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 6]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+
+        let data = duel.data.lock().unwrap();
+        let synchro = data.get_card(CardId::new(0)).unwrap();
+        assert_eq!((synchro.location, synchro.sequence), (Location::MZONE, 6));
+        assert_eq!(synchro.summon_type(), SUMMON_TYPE_SYNCHRO);
+        assert!(synchro.has_status(CardStatus::PROC_COMPLETE));
+        assert_eq!(synchro.materials, vec![CardId::new(1), CardId::new(2)]);
+        for id in 1..3 {
+            let mat = data.get_card(CardId::new(id)).unwrap();
+            assert_eq!(mat.location, Location::GRAVE);
+            assert_eq!(mat.reason & (REASON_MATERIAL | REASON_SYNCHRO), REASON_MATERIAL | REASON_SYNCHRO);
+        }
+        assert_eq!(data.get_card(CardId::new(3)).unwrap().location, Location::MZONE);
+    }
+
+    #[test]
+    fn level_modifiers_and_sum_selection() {
+        let mut duel = duel_with(&[
+            card(9001, CardType::MONSTER | CardType::SYNCHRO, 7),
+            card(9002, CardType::MONSTER | CardType::TUNER, 2),
+            card(9003, CardType::MONSTER | CardType::NORMAL, 4),
+            card(9004, CardType::MONSTER | CardType::NORMAL, 3),
+        ], SETUP);
+
+        let (before, after, level): (bool, bool, u32) = duel.lua.load(r#"
+            local before = Duel.CheckSynchroMaterial(synchro, nil, nil, 1, 99)
+            local lv = Effect.CreateEffect(other)
+            lv:SetType(EFFECT_TYPE_SINGLE)
+            lv:SetCode(EFFECT_SYNCHRO_LEVEL)
+            lv:SetValue(function(e, sc) return 5 * 65536 + e:GetHandler():GetLevel() end)
+            other:RegisterEffect(lv)
+            return before, Duel.CheckSynchroMaterial(synchro, nil, nil, 1, 1), other:GetSynchroLevel(synchro)
+        "#).eval().unwrap();
+        assert!(!before, "2 + 4 and 2 + 3 both miss level 7");
+        assert!(after, "2 + 5 reaches level 7 through the alternate level");
+        assert_eq!(level, 5 << 16 | 3);
+
+        // With a level 5 non-tuner both cards fit; the answer picks the second one
+        duel.data.lock().unwrap().cards[2].current_stats.level = 5;
+        duel.get_message();
+        let operation = duel.lua.load(r#"
+            local g = Duel.SelectTunerMaterial(0, synchro, tuner, nil, nil, 1, 1)
+            picked = {}
+            for _, c in ipairs({tuner, four, other}) do
+                if g:IsContains(c) then table.insert(picked, c:GetCode()) end
+            end
+        "#).into_function().unwrap();
+        duel.execute_operation(operation).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        assert!(duel.get_message().contains(&MsgType::SelectSum.id()));
+        duel.set_responseb(&[2, 0, 1]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let picked: Vec<u32> = duel.lua.globals().get("picked").unwrap();
+        assert_eq!(picked, vec![9002, 9004]);
+
+        let blocked: bool = duel.lua.load(r#"
+            local lock = Effect.CreateEffect(other)
+            lock:SetType(EFFECT_TYPE_SINGLE)
+            lock:SetCode(EFFECT_CANNOT_BE_SYNCHRO_MATERIAL)
+            lock:SetValue(1)
+            other:RegisterEffect(lock)
+            local nt = Effect.CreateEffect(tuner)
+            nt:SetType(EFFECT_TYPE_SINGLE)
+            nt:SetCode(EFFECT_NONTUNER)
+            tuner:RegisterEffect(nt)
+            return other:IsCanBeSynchroMaterial(synchro) or not tuner:IsNotTuner()
+        "#).eval().unwrap();
+        assert!(!blocked);
+    }
+}
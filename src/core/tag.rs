@@ -0,0 +1,159 @@
+//! Tag duels: two duelists per side take turns, sharing their side's LP and field while the
+//! waiting teammate's Deck, hand and Extra Deck are kept aside until the swap.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::{CardPosition, Location};
+use crate::core::messages::MsgTagSwap;
+use crate::core::types::CardId;
+
+impl DuelData {
+    /// Create a card in the Deck or Extra Deck of `player`'s waiting teammate (ocgcore new_tag_card).
+    pub fn new_tag_card(&mut self, code: u32, player: u8, location: Location) -> CardId {
+        let p = player as usize;
+        let card_id = self.new_card(code, player);
+        // Like load_deck, main Deck cards go under the ones already loaded
+        let sequence = if location == Location::EXTRA {
+            self.field.tag_extra[p].push(card_id);
+            self.field.tag_extra[p].len() - 1
+        } else {
+            self.field.tag_deck[p].insert(0, card_id);
+            0
+        };
+        let card = &mut self.cards[card_id.0 as usize];
+        card.controller = player;
+        card.location = if location == Location::EXTRA { Location::EXTRA } else { Location::DECK };
+        card.sequence = sequence as u8;
+        card_id
+    }
+
+    /// Deal `player`'s waiting teammate their opening hand from the top of their Deck.
+    pub fn draw_tag_hand(&mut self, player: u8, count: u32) {
+        let p = player as usize;
+        for _ in 0..count {
+            let Some(card_id) = self.field.tag_deck[p].pop() else { break };
+            self.field.tag_hand[p].push(card_id);
+            let card = &mut self.cards[card_id.0 as usize];
+            card.location = Location::HAND;
+            card.sequence = (self.field.tag_hand[p].len() - 1) as u8;
+        }
+    }
+
+    /// Bring in `player`'s waiting teammate (MSG_TAG_SWAP): their Deck, hand and Extra Deck trade
+    /// places with the active ones. The opponent's copy does not carry the codes of hidden cards.
+    pub fn tag_swap(&mut self, player: u8) {
+        let p = player as usize;
+        std::mem::swap(&mut self.field.deck[p], &mut self.field.tag_deck[p]);
+        std::mem::swap(&mut self.field.hand[p], &mut self.field.tag_hand[p]);
+        std::mem::swap(&mut self.field.extra[p], &mut self.field.tag_extra[p]);
+        self.reset_deck_sequence(player);
+        for list in [self.field.hand[p].clone(), self.field.extra[p].clone()] {
+            for (sequence, card_id) in list.into_iter().enumerate() {
+                self.cards[card_id.0 as usize].sequence = sequence as u8;
+            }
+        }
+
+        let faceup_code = |data: &DuelData, card_id: CardId| {
+            let card = &data.cards[card_id.0 as usize];
+            if card.position.intersects(CardPosition::FACEUP) { card.code | 0x80000000 } else { card.code }
+        };
+        let top_code = self.field.deck[p].last()
+            .map(|&top| faceup_code(self, top))
+            .filter(|code| code & 0x80000000 != 0)
+            .unwrap_or(0);
+        let shown: Vec<CardId> = self.field.hand[p].iter().chain(self.field.extra[p].iter()).copied().collect();
+        let msg = MsgTagSwap {
+            player,
+            main_count: self.field.deck[p].len() as u8,
+            extra_p_count: self.field.extra[p].iter().filter(|c| self.cards[c.0 as usize].position.intersects(CardPosition::FACEUP)).count() as u8,
+            top_code,
+            hand: self.field.hand[p].iter().map(|&c| faceup_code(self, c)).collect(),
+            extra: self.field.extra[p].iter().map(|&c| faceup_code(self, c)).collect(),
+        };
+        let offsets: Vec<(usize, CardId)> = shown.into_iter().enumerate().map(|(i, c)| (10 + 4 * i, c)).collect();
+        self.write_message_hiding(&msg.encode(), &offsets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::messages::{parse_packet, MsgTagSwap, MsgType};
+    use crate::core::options::DuelOptions;
+    use crate::core::processor::{ProcessResult, ProcessorUnit};
+    use crate::core::types::CardId;
+    use std::collections::VecDeque;
+
+    fn tag_duel() -> Duel {
+        let options = DuelOptions { flags: DuelFlag::TAG_MODE | DuelFlag::PSEUDO_SHUFFLE, duel_rule: 5 };
+        let mut duel = Duel::with_options(0, options);
+        duel.load_deck(0, &[1001, 1002, 1003], &[1101]);
+        duel.load_tag_deck(0, &[2001, 2002, 2003], &[]);
+        duel.load_deck(1, &[3001, 3002, 3003], &[]);
+        duel.load_tag_deck(1, &[4001, 4002, 4003], &[4101]);
+        duel.data.lock().unwrap().start_count = [2, 2];
+        duel.start_duel();
+        duel.get_message();
+        duel
+    }
+
+    #[test]
+    fn teammates_are_dealt_their_own_opening_hands() {
+        let duel = tag_duel();
+        let data = duel.data.lock().unwrap();
+        let codes = |list: &[CardId]| list.iter().map(|c| data.cards[c.0 as usize].code).collect::<Vec<_>>();
+        assert_eq!(codes(&data.field.hand[0]), vec![1001, 1002]);
+        assert_eq!(codes(&data.field.tag_hand[0]), vec![2001, 2002]);
+        assert_eq!(codes(&data.field.tag_deck[1]), vec![4003]);
+        assert_eq!(codes(&data.field.tag_extra[1]), vec![4101]);
+        assert!(data.field.tag_hand[1].iter().all(|c| data.cards[c.0 as usize].location == Location::HAND));
+    }
+
+    #[test]
+    fn each_side_swaps_in_its_teammate_from_its_second_turn() {
+        let mut duel = tag_duel();
+        let take_turn = |duel: &mut Duel, player: u8| {
+            {
+                let mut data = duel.data.lock().unwrap();
+                data.turn_player = player;
+                data.processor_units = VecDeque::from([ProcessorUnit::turn(0)]);
+            }
+            assert_eq!(duel.process(), ProcessResult::Continue);
+            duel.get_player_messages()
+        };
+        let hand_codes = |duel: &Duel, player: usize| {
+            let data = duel.data.lock().unwrap();
+            data.field.hand[player].iter().map(|c| data.cards[c.0 as usize].code).collect::<Vec<_>>()
+        };
+
+        // A and C open for their sides without a swap
+        let [own, _] = take_turn(&mut duel, 0);
+        assert!(!own.contains(&MsgType::TagSwap.id()), "the first duelist starts");
+        let [_, own] = take_turn(&mut duel, 1);
+        assert!(!own.contains(&MsgType::TagSwap.id()), "the opponent's first duelist starts too");
+        assert_eq!(hand_codes(&duel, 1), vec![3001, 3002]);
+
+        // B takes the side's second turn
+        let [own, opponent] = take_turn(&mut duel, 0);
+        let (ty, payload) = parse_packet(&own[2..]);
+        assert_eq!(ty, MsgType::TagSwap);
+        let swap = MsgTagSwap::parse(payload).unwrap();
+        assert_eq!((swap.player, swap.main_count, swap.top_code), (0, 1, 0));
+        assert_eq!(swap.hand, vec![2001, 2002]);
+        assert!(swap.extra.is_empty());
+        let hidden = MsgTagSwap::parse(parse_packet(&opponent[2..]).1).unwrap();
+        assert_eq!(hidden.hand, vec![0, 0], "the opponent does not see the new hand");
+
+        // Then D
+        let [_, own] = take_turn(&mut duel, 1);
+        let swap = MsgTagSwap::parse(parse_packet(&own[2..]).1).unwrap();
+        assert_eq!(swap.player, 1);
+        assert_eq!(hand_codes(&duel, 1), vec![4001, 4002]);
+
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.turn_by_player, [2, 2]);
+        assert_eq!(data.field.tag_hand[0].len(), 2);
+        assert_eq!(data.field.tag_extra[0].len(), 1, "the Extra Deck goes aside with its duelist");
+        assert_eq!(data.lp, [8000, 8000]);
+    }
+}
@@ -0,0 +1,24 @@
+//! Test support: cards the tests put on the field get their stats from the card database, as real cards do.
+
+use crate::core::database::CardData;
+use crate::core::duel::Duel;
+use crate::core::enums::CardType;
+
+/// Database record of a card with a type and level (the rank or link rating of Xyz and Link monsters).
+pub fn card(code: u32, type_: CardType, level: u32) -> CardData {
+    CardData { code, alias: 0, setcode: 0, type_: type_.bits(), level, attribute: 0, race: 0, attack: 0, defense: 0, lscale: 0, rscale: 0, link_marker: 0 }
+}
+
+/// A duel whose card database knows `cards`, set up by the Lua chunk `setup`.
+pub fn duel_with(cards: &[CardData], setup: &str) -> Duel {
+    let duel = Duel::new(0);
+    {
+        let data = duel.data.lock().unwrap();
+        let mut db = data.database.lock().unwrap();
+        for card in cards {
+            db.cache.insert(card.code, card.clone());
+        }
+    }
+    duel.lua.load(setup).exec().expect("setup");
+    duel
+}
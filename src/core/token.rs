@@ -0,0 +1,155 @@
+//! Tokens: cards created outside any location by an effect, and the stat-based special summon check made before creating them.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::scripting::duel_data;
+use crate::core::summon::is_player_can_special_summon;
+use crate::core::types::CardId;
+use mlua::Lua;
+
+/// Stats a monster would have, for checking summons of cards that do not exist yet.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MonsterStats {
+    pub code: u32,
+    pub setcode: u64,
+    pub type_: u32,
+    pub attack: i32,
+    pub defense: i32,
+    pub level: u32,
+    pub race: u32,
+    pub attribute: u32,
+}
+
+impl DuelData {
+    /// Create a token for `player` from its database record. It is in no location until it is summoned,
+    /// and once it leaves the field it is gone again.
+    pub fn create_token(&mut self, player: u8, code: u32) -> CardId {
+        let id = self.new_card(code, player);
+        let card = &mut self.cards[id.0 as usize];
+        for stats in [&mut card.original_stats, &mut card.current_stats] {
+            stats.type_ |= CardType::MONSTER | CardType::TOKEN;
+        }
+        id
+    }
+
+    /// The scratch card, carrying `stats` and controlled by `player`.
+    pub fn temp_card_with(&mut self, player: u8, stats: &MonsterStats) -> CardId {
+        let id = match self.temp_card {
+            Some(id) => id,
+            None => {
+                let id = self.new_card(0, player);
+                self.temp_card = Some(id);
+                id
+            }
+        };
+        let card = &mut self.cards[id.0 as usize];
+        card.code = stats.code;
+        card.setcode = stats.setcode;
+        card.owner = player;
+        card.controller = player;
+        let block = &mut card.current_stats;
+        block.type_ = CardType::from_bits_truncate(stats.type_);
+        block.attack = stats.attack;
+        block.defense = stats.defense;
+        block.base_attack = stats.attack;
+        block.base_defense = stats.defense;
+        block.level = stats.level;
+        block.race = CardRace::from_bits_truncate(stats.race);
+        block.attribute = CardAttribute::from_bits_truncate(stats.attribute);
+        card.original_stats = card.current_stats.clone();
+        id
+    }
+}
+
+/// Whether `player` could special summon a monster with the given stats (IsPlayerCanSpecialSummonMonster).
+/// The stats are put on a scratch card so restrictions can look at them like at a real card.
+pub fn is_player_can_special_summon_monster(lua: &Lua, player: u8, stats: &MonsterStats, sumtype: u32, position: u32, target_player: u8) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let temp = data.lock().unwrap().temp_card_with(player, stats);
+    is_player_can_special_summon(lua, player, Some(temp), sumtype, position, target_player, None)
+}
+
+/// Register the token functions on the global `Duel` table.
+pub fn register_token_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.CreateToken(player, code) -> Card
+    duel_table.set("CreateToken", lua.create_function(|lua, (player, code): (u8, u32)| {
+        let data = duel_data(lua);
+        let token = data.lock().unwrap().create_token(player, code);
+        Ok(token)
+    })?)?;
+
+    // Duel.IsPlayerCanSpecialSummonMonster(player, code, setcode, type, atk, def, level, race, attribute[, pos, target_player, sumtype])
+    #[allow(clippy::type_complexity)]
+    let can_summon = lua.create_function(|lua, (player, code, setcode, type_, attack, defense, level, race, attribute, pos, target_player, sumtype):
+        (u8, u32, Option<u64>, u32, i32, i32, u32, u32, u32, Option<u32>, Option<u8>, Option<u32>)| {
+        let stats = MonsterStats { code, setcode: setcode.unwrap_or(0), type_, attack, defense, level, race, attribute };
+        let position = pos.unwrap_or(CardPosition::FACEUP.bits());
+        is_player_can_special_summon_monster(lua, player, &stats, sumtype.unwrap_or(0), position, target_player.unwrap_or(player))
+    })?;
+    duel_table.set("IsPlayerCanSpecialSummonMonster", can_summon)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::processor::ProcessResult;
+    use crate::core::types::CardId;
+
+    #[test]
+    fn tokens_are_summoned_from_nowhere_and_vanish_when_they_leave() {
+        let mut duel = Duel::new(0);
+        duel.execute_operation(duel.lua.load(r#"
+            token = Duel.CreateToken(0, 9951)
+            summoned = Duel.SpecialSummon(token, 0, 0, 0, false, false, POS_FACEUP_DEFENSE)
+        "#).into_function().unwrap()).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 0]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let (token, summoned): (CardId, u32) = duel.lua.load("return token, summoned").eval().unwrap();
+        assert_eq!(summoned, 1);
+        let mut data = duel.data.lock().unwrap();
+        let card = data.get_card(token).unwrap();
+        assert!(card.current_stats.type_.contains(CardType::TOKEN));
+        assert_eq!((card.location, card.sequence), (Location::MZONE, 0));
+        assert_eq!(card.summon_location(), 0);
+
+        data.message_buffer.clear();
+        assert!(data.send_card_to(token, 0, Location::GRAVE, REASON_DESTROY));
+        let card = data.get_card(token).unwrap();
+        assert!(card.location.is_empty());
+        assert!(data.field.grave[0].is_empty() && data.field.mzone[0][0].is_none());
+        assert_eq!(data.message_buffer[1 + 4 + 4 + 1], 0, "MSG_MOVE reports no destination location");
+        data.send_card_to(token, 0, Location::DECK, REASON_EFFECT);
+        assert!(data.field.deck[0].is_empty(), "not even a token that is already gone");
+    }
+
+    #[test]
+    fn special_summon_restrictions_see_the_token_stats() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            local c = Debug.AddCard(9961, 0, 0, LOCATION_SZONE, 0, POS_FACEUP)
+            local e = Effect.CreateEffect(c)
+            e:SetType(EFFECT_TYPE_FIELD)
+            e:SetCode(EFFECT_CANNOT_SPECIAL_SUMMON)
+            e:SetProperty(EFFECT_FLAG_PLAYER_TARGET)
+            e:SetRange(LOCATION_SZONE)
+            e:SetTargetRange(1, 0)
+            e:SetTarget(function(e, c) return c:GetLevel() >= 4 end)
+            c:RegisterEffect(e)
+        "#).exec().expect("setup");
+        let (high, low): (bool, bool) = duel.lua.load(r#"
+            return Duel.IsPlayerCanSpecialSummonMonster(0, 9962, 0, TYPE_MONSTER + TYPE_NORMAL + TYPE_TOKEN, 0, 0, 4, RACE_FIEND, ATTRIBUTE_DARK),
+                Duel.IsPlayerCanSpecialSummonMonster(0, 9963, 0, TYPE_MONSTER + TYPE_NORMAL + TYPE_TOKEN, 0, 0, 1, RACE_FAIRY, ATTRIBUTE_LIGHT, POS_FACEUP_DEFENSE, 1)
+        "#).eval().unwrap();
+        assert!(!high);
+        assert!(low);
+        let data = duel.data.lock().unwrap();
+        let temp = data.temp_card.and_then(|id| data.get_card(id)).unwrap();
+        assert!(temp.location.is_empty(), "the scratch card is never placed");
+    }
+}
@@ -31,6 +31,7 @@ impl<'lua> FromLua<'lua> for CardId {
         match value {
             Value::Integer(i) => Ok(CardId(i as u32)),
             Value::Number(n) => Ok(CardId(n as u32)),
+            Value::UserData(ref ud) if ud.is::<CardId>() => Ok(*ud.borrow::<CardId>()?),
             _ => Err(mlua::Error::FromLuaConversionError {
                 from: value.type_name(),
                 to: "CardId",
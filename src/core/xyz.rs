@@ -0,0 +1,321 @@
+//! Xyz summons: overlay units attached to cards, material checks by level and the detach helpers aux.AddXyzProcedure builds on.
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::group::{cards_of, Group};
+use crate::core::prompt::{ready, select_cards, set_prompting};
+use crate::core::replace::send_to;
+use crate::core::scripting::{duel_data, effect_value};
+use crate::core::types::CardId;
+use mlua::{AnyUserData, Function, Lua, MultiValue};
+
+impl DuelData {
+    /// Default Xyz material pool of a player: face-up monsters in the monster zones.
+    pub fn xyz_material_pool(&self, player: u8) -> Vec<CardId> {
+        self.field.mzone[player as usize].iter().flatten().copied()
+            .filter(|id| self.cards.get(id.0 as usize).is_some_and(|c| {
+                c.current_stats.type_.contains(CardType::MONSTER) && c.position.intersects(CardPosition::FACEUP)
+            }))
+            .collect()
+    }
+
+    /// Attach `materials` to `target` as overlay units. Returns the overlay units of materials that
+    /// were Xyz monsters themselves, which have to go to the GY.
+    pub fn overlay(&mut self, target: CardId, materials: &[CardId]) -> Vec<CardId> {
+        let Some(controller) = self.cards.get(target.0 as usize).map(|c| c.controller) else {
+            return Vec::new();
+        };
+        let mut lost = Vec::new();
+        for &material in materials {
+            let attached = self.cards.get(material.0 as usize).map(|c| c.overlay_target == Some(target));
+            if material == target || attached != Some(false) {
+                continue;
+            }
+            let from = self.info_location(material);
+            if !self.remove_from_location(material) {
+                continue;
+            }
+            lost.extend(self.cards[material.0 as usize].xyz_materials.iter().copied());
+            let index = self.cards[target.0 as usize].xyz_materials.len();
+            self.cards[target.0 as usize].xyz_materials.push(material);
+            let card = &mut self.cards[material.0 as usize];
+            card.controller = controller;
+            card.location = Location::OVERLAY;
+            card.sequence = index as u8;
+            card.overlay_target = Some(target);
+            card.reason = REASON_XYZ | REASON_MATERIAL;
+            self.write_move_message(material, from, REASON_XYZ | REASON_MATERIAL);
+        }
+        lost
+    }
+
+    /// Overlay units on the monsters in the given monster zones (`s` for `player`, `o` for the opponent).
+    pub fn field_overlays(&self, player: u8, s: bool, o: bool) -> Vec<CardId> {
+        [(player, s), (1 - player, o)].into_iter()
+            .filter(|&(_, include)| include)
+            .flat_map(|(p, _)| self.field.mzone[p as usize].iter().flatten())
+            .filter_map(|id| self.cards.get(id.0 as usize))
+            .flat_map(|c| c.xyz_materials.iter().copied())
+            .collect()
+    }
+}
+
+/// Let `player` detach between `min` and `max` of `overlays` to their owners' GYs; gives how many were detached.
+pub fn remove_overlay_cards(lua: &Lua, player: u8, overlays: Vec<CardId>, min: usize, max: usize, reason: u32) -> mlua::Result<MultiValue<'_>> {
+    if overlays.len() < min {
+        return ready(lua, 0);
+    }
+    select_cards(lua, player, overlays, min, max, move |lua, chosen| {
+        let (reason_player, effect) = duel_data(lua).lock().unwrap().reason_context();
+        let detached = send_to(lua, &chosen, None, Location::GRAVE, None, reason, reason_player, effect)?.0.len() as u32;
+        ready(lua, detached)
+    })
+}
+
+/// Whether a card can be treated as level `level` for the Xyz summon of `xyz` (EFFECT_XYZ_LEVEL).
+pub fn is_xyz_level(lua: &Lua, card_id: CardId, xyz: CardId, level: u32) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let (own, modifier) = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        if card.current_stats.type_.intersects(CardType::XYZ | CardType::LINK) {
+            return Ok(false);
+        }
+        (card.current_stats.level, data_guard.card_effects(card_id, EFFECT_XYZ_LEVEL).first().copied())
+    };
+    let packed = match modifier {
+        Some(eid) => effect_value(lua, &data, eid, (eid, xyz))?,
+        None => own,
+    };
+    let (lo, hi) = (packed & 0xfff, (packed >> 16) & 0xfff);
+    Ok(level != 0 && (lo == level || hi == level))
+}
+
+/// Whether a card may be used as Xyz material for `xyz` (EFFECT_CANNOT_BE_XYZ_MATERIAL).
+pub fn is_can_be_xyz_material(lua: &Lua, card_id: CardId, xyz: Option<CardId>) -> mlua::Result<bool> {
+    let data = duel_data(lua);
+    let blockers = {
+        let data_guard = data.lock().unwrap();
+        let Some(card) = data_guard.cards.get(card_id.0 as usize) else {
+            return Ok(false);
+        };
+        let type_ = &card.current_stats.type_;
+        if !type_.contains(CardType::MONSTER) || type_.contains(CardType::LINK) {
+            return Ok(false);
+        }
+        if card.location == Location::MZONE && !card.position.intersects(CardPosition::FACEUP) {
+            return Ok(false);
+        }
+        data_guard.card_effects(card_id, EFFECT_CANNOT_BE_XYZ_MATERIAL)
+    };
+    for eid in blockers {
+        if effect_value(lua, &data, eid, (eid, xyz))? != 0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Cards of `mg` (or the Xyz monster controller's pool) that pass `f` and can be level `level` material for `xyz`.
+fn xyz_candidates(lua: &Lua, xyz: CardId, f: &Option<Function>, level: u32, mg: Option<AnyUserData>) -> mlua::Result<Vec<CardId>> {
+    let pool = match mg {
+        Some(mg) => mg.borrow::<Group>()?.0.iter().copied().collect(),
+        None => {
+            let data = duel_data(lua);
+            let data_guard = data.lock().unwrap();
+            let player = data_guard.get_card(xyz).map(|c| c.controller).unwrap_or(0);
+            data_guard.xyz_material_pool(player)
+        }
+    };
+    let mut candidates = Vec::new();
+    for id in pool {
+        if id == xyz || !is_xyz_level(lua, id, xyz, level)? || !is_can_be_xyz_material(lua, id, Some(xyz))? {
+            continue;
+        }
+        let passes = match f {
+            Some(f) => f.call((id, xyz))?,
+            None => true,
+        };
+        if passes {
+            candidates.push(id);
+        }
+    }
+    Ok(candidates)
+}
+
+/// Register the Xyz and overlay functions on the global `Duel` table.
+#[allow(clippy::type_complexity)]
+pub fn register_xyz_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.Overlay(c, ocard | og) - attach cards to c as overlay units
+    duel_table.set("Overlay", lua.create_function(|lua, (target, materials): (CardId, mlua::Value)| {
+        let materials = cards_of(&materials);
+        let data = duel_data(lua);
+        let (lost, (reason_player, effect)) = {
+            let mut data_guard = data.lock().unwrap();
+            (data_guard.overlay(target, &materials), data_guard.reason_context())
+        };
+        send_to(lua, &lost, None, Location::GRAVE, None, REASON_RULE, reason_player, effect)?;
+        Ok(())
+    })?)?;
+
+    // Duel.CheckXyzMaterial(c, f, lv, minc, maxc, mg)
+    duel_table.set("CheckXyzMaterial", lua.create_function(|lua, (xyz, f, level, min, _max, mg): (CardId, Option<Function>, u32, usize, usize, Option<AnyUserData>)| {
+        Ok(xyz_candidates(lua, xyz, &f, level, mg)?.len() >= min)
+    })?)?;
+
+    // Duel.SelectXyzMaterial(player, c, f, lv, minc, maxc[, mg]) -> Group
+    set_prompting(lua, &duel_table, "SelectXyzMaterial", |lua, (player, xyz, f, level, min, max, mg): (u8, CardId, Option<Function>, u32, usize, usize, Option<AnyUserData>)| {
+        let candidates = xyz_candidates(lua, xyz, &f, level, mg)?;
+        if candidates.len() < min {
+            return ready(lua, Group::new());
+        }
+        select_cards(lua, player, candidates, min, max, |lua, chosen| ready(lua, Group(chosen.into_iter().collect())))
+    })?;
+
+    // Duel.GetOverlayGroup(player, s, o) -> Group
+    duel_table.set("GetOverlayGroup", lua.create_function(|lua, (player, s, o): (u8, u32, u32)| {
+        let overlays = duel_data(lua).lock().unwrap().field_overlays(player, s != 0, o != 0);
+        Ok(Group(overlays.into_iter().collect()))
+    })?)?;
+
+    // Duel.GetOverlayCount(player, s, o)
+    duel_table.set("GetOverlayCount", lua.create_function(|lua, (player, s, o): (u8, u32, u32)| {
+        Ok(duel_data(lua).lock().unwrap().field_overlays(player, s != 0, o != 0).len() as u32)
+    })?)?;
+
+    // Duel.CheckRemoveOverlayCard(player, s, o, count, reason)
+    duel_table.set("CheckRemoveOverlayCard", lua.create_function(|lua, (player, s, o, count, _reason): (u8, u32, u32, usize, u32)| {
+        Ok(duel_data(lua).lock().unwrap().field_overlays(player, s != 0, o != 0).len() >= count)
+    })?)?;
+
+    // Duel.RemoveOverlayCard(player, s, o, min, max, reason) -> number detached
+    set_prompting(lua, &duel_table, "RemoveOverlayCard", |lua, (player, s, o, min, max, reason): (u8, u32, u32, usize, usize, u32)| {
+        let overlays = duel_data(lua).lock().unwrap().field_overlays(player, s != 0, o != 0);
+        remove_overlay_cards(lua, player, overlays, min, max, reason)
+    })?;
+
+    // Method: c:RemoveOverlayCard(player, min, max, reason) -> number of detached units
+    let card_table: mlua::Table = lua.globals().get("Card")?;
+    set_prompting(lua, &card_table, "RemoveOverlayCard", |lua, (card, player, min, max, reason): (CardId, u8, usize, usize, u32)| {
+        let overlays = duel_data(lua).lock().unwrap().get_card(card).map(|c| c.xyz_materials).unwrap_or_default();
+        remove_overlay_cards(lua, player, overlays, min, max, reason)
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::enums::{CardType, Location, QueryFlag, REASON_COST, REASON_LOST_OVERLAY, REASON_RULE, SUMMON_TYPE_XYZ};
+    use crate::core::processor::ProcessResult;
+    use crate::core::testing::{card, duel_with};
+    use crate::core::types::CardId;
+
+    /// Rank 4 Xyz monster 9101 needing 2 level 4 monsters, through aux.AddXyzProcedure.
+    const SETUP: &str = r#"
+        xyz = Debug.AddCard(9101, 0, 0, LOCATION_EXTRA, 0, POS_FACEDOWN)
+        mat_a = Debug.AddCard(9102, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+        mat_b = Debug.AddCard(9103, 0, 0, LOCATION_MZONE, 1, POS_FACEUP_ATTACK)
+        low = Debug.AddCard(9104, 0, 0, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)
+        xyz:EnableReviveLimit()
+        aux.AddXyzProcedure(xyz, nil, 4, 2)
+    "#;
+
+    #[test]
+    fn xyz_procedure_attaches_materials_and_detaches_as_cost() {
+        let mut duel = duel_with(&[
+            card(9101, CardType::MONSTER | CardType::XYZ, 4),
+            card(9102, CardType::MONSTER | CardType::NORMAL, 4),
+            card(9103, CardType::MONSTER | CardType::NORMAL, 4),
+            card(9104, CardType::MONSTER | CardType::NORMAL, 3),
+        ], SETUP);
+
+        duel.special_summon_rule(0, CardId::new(0));
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the materials are chosen by the player");
+        duel.set_responseb(&[1, 0]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "two materials are needed");
+        duel.set_responseb(&[2, 0, 1]);
+        assert_eq!(duel.process(), ProcessResult::Waiting, "then the zone");
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 0]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        {
+            let data = duel.data.lock().unwrap();
+            let xyz = data.get_card(CardId::new(0)).unwrap();
+            assert_eq!(xyz.location, Location::MZONE);
+            assert_eq!(xyz.summon_type(), SUMMON_TYPE_XYZ);
+            assert_eq!(xyz.xyz_materials, vec![CardId::new(1), CardId::new(2)]);
+            for (index, id) in [1u32, 2].into_iter().enumerate() {
+                let mat = data.get_card(CardId::new(id)).unwrap();
+                assert_eq!(mat.location, Location::OVERLAY);
+                assert_eq!(mat.overlay_target, Some(CardId::new(0)));
+                let loc = data.info_location(CardId::new(id));
+                assert_eq!(loc.location, (Location::OVERLAY | Location::MZONE).bits() as u8);
+                assert_eq!(loc.sequence, xyz.sequence);
+                assert_eq!(loc.position, index as u8);
+            }
+            assert_eq!(data.field.mzone[0][0], Some(CardId::new(0)), "the Xyz monster takes a freed zone");
+            assert!(data.field.mzone[0][1].is_none());
+            assert_eq!(data.get_card(CardId::new(3)).unwrap().location, Location::MZONE);
+        }
+
+        let operation = duel.lua.load(r#"
+            can = xyz:CheckRemoveOverlayCard(0, 2, REASON_COST)
+                and not xyz:CheckRemoveOverlayCard(0, 3, REASON_COST)
+            removed = xyz:RemoveOverlayCard(0, 1, 1, REASON_COST)
+        "#).into_function().unwrap();
+        duel.execute_operation(operation).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the unit to detach is chosen by the player");
+        duel.set_responseb(&[1, 0]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let (can, removed, left, field_count): (bool, u32, u32, u32) = duel.lua.load(r#"
+            return can, removed, xyz:GetOverlayCount(), Duel.GetOverlayCount(0, 1, 0)
+        "#).eval().unwrap();
+        assert!(can);
+        assert_eq!(removed, 1);
+        assert_eq!(left, 1);
+        assert_eq!(field_count, 1);
+        let data = duel.data.lock().unwrap();
+        let detached = data.get_card(CardId::new(1)).unwrap();
+        assert_eq!(detached.location, Location::GRAVE);
+        assert_eq!(detached.reason, REASON_COST);
+        assert_eq!(detached.overlay_target, None);
+        assert_eq!(data.get_card(CardId::new(2)).unwrap().sequence, 0, "remaining unit moves down");
+    }
+
+    #[test]
+    fn overlays_follow_rules_when_xyz_moves() {
+        let duel = duel_with(&[card(9201, CardType::MONSTER | CardType::XYZ, 4), card(9202, CardType::MONSTER | CardType::XYZ, 4)], r#"
+            first = Debug.AddCard(9201, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+            second = Debug.AddCard(9202, 0, 0, LOCATION_MZONE, 1, POS_FACEUP_ATTACK)
+            unit_a = Debug.AddCard(9203, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            unit_b = Debug.AddCard(9204, 1, 1, LOCATION_GRAVE, 0, POS_FACEUP)
+        "#);
+
+        duel.lua.load("Duel.Overlay(first, unit_a) Duel.Overlay(first, unit_b)").exec().unwrap();
+        let query = duel.query_card(0, Location::MZONE, 0, QueryFlag::OVERLAY_CARD);
+        let expected: Vec<u8> = [2u32, 9203, 9204].iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert!(query.windows(expected.len()).any(|w| w == expected.as_slice()), "count then codes");
+
+        // An Xyz monster becoming material loses its own units to the GY
+        duel.lua.load("Duel.Overlay(second, first)").exec().unwrap();
+        {
+            let data = duel.data.lock().unwrap();
+            assert_eq!(data.get_card(CardId::new(1)).unwrap().xyz_materials, vec![CardId::new(0)]);
+            let unit_b = data.get_card(CardId::new(3)).unwrap();
+            assert_eq!(unit_b.location, Location::GRAVE);
+            assert_eq!(unit_b.controller, 1, "units go to their owner's GY");
+            assert_eq!(unit_b.reason, REASON_RULE);
+        }
+
+        // Leaving the field sends the remaining units to the GY as well
+        duel.lua.load("Duel.SendtoGrave(second, REASON_EFFECT)").exec().unwrap();
+        let data = duel.data.lock().unwrap();
+        let unit = data.get_card(CardId::new(0)).unwrap();
+        assert_eq!((unit.location, unit.reason), (Location::GRAVE, REASON_LOST_OVERLAY | REASON_RULE));
+        assert!(data.get_card(CardId::new(1)).unwrap().xyz_materials.is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+//! Zones: availability masks, zones disabled by EFFECT_DISABLE_FIELD and letting a player pick a zone (MSG_SELECT_PLACE).
+
+use crate::core::duel::DuelData;
+use crate::core::enums::*;
+use crate::core::messages::{MsgFieldDisabled, MsgSelectPlace, MsgType};
+use crate::core::prompt::{ask, ready, set_prompting, unexpected};
+use crate::core::response::{Prompt, Response};
+use crate::core::scripting::{duel_data, effect_value};
+use crate::core::types::{CardId, EffectId};
+use mlua::{Lua, MultiValue};
+
+impl DuelData {
+    /// Useable sequences of a player's monster or spell & trap zones as a bit mask, limited to `zone`.
+    pub fn useable_zones(&self, player: u8, location: Location, zone: u32) -> u32 {
+        let count = if location == Location::MZONE { 7 } else if location == Location::SZONE { 5 } else { 0 };
+        (0..count)
+            .filter(|&s| zone & (1 << s) != 0 && self.is_location_useable(player, location, s))
+            .fold(0, |mask, s| mask | 1 << s)
+    }
+
+    /// Free zones a card could be put into (GetLocationCount): the main monster zones, or the spell & trap zones.
+    pub fn location_count(&self, player: u8, location: Location, zone: u32) -> usize {
+        if player > 1 {
+            return 0;
+        }
+        self.useable_zones(player, location, zone & 0x1f).count_ones() as usize
+    }
+
+    /// Zone of the `zones` (a mask of sequences) taken without asking `player`: the only one,
+    /// or the lowest one for player 1 under DUEL_SIMPLE_AI.
+    pub fn auto_place(&self, player: u8, zones: u32) -> Option<u8> {
+        let unasked = zones.count_ones() == 1 || (player == 1 && self.duel_options.contains(DuelFlag::SIMPLE_AI));
+        (zones != 0 && unasked).then(|| zones.trailing_zeros() as u8)
+    }
+
+    /// Zones `player` may pick with SelectDisableField: the free zones of the `s` (own) and `o` (opponent)
+    /// locations, as a mask relative to `player` with the opponent's zones 16 bits higher.
+    pub fn free_field_zones(&self, player: u8, s: u32, o: u32) -> u32 {
+        let mut mask = 0;
+        for (side, locations, shift) in [(player, s, 0), (1 - player, o, 16)] {
+            if locations & Location::MZONE.bits() != 0 {
+                mask |= self.useable_zones(side, Location::MZONE, 0x1f) << shift;
+            }
+            if locations & Location::SZONE.bits() != 0 {
+                mask |= self.useable_zones(side, Location::SZONE, 0x1f) << (shift + 8);
+            }
+        }
+        mask
+    }
+}
+
+/// Let `player` choose one of the `zones` of `location` with MSG_SELECT_PLACE, unless DuelData::auto_place
+/// settles it. `then` gets the zone, or None when there is none to choose from.
+pub fn select_place<'lua, F>(lua: &'lua Lua, player: u8, location: Location, zones: u32, then: F) -> mlua::Result<MultiValue<'lua>>
+where
+    F: for<'a> FnOnce(&'a Lua, Option<u8>) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+{
+    if zones == 0 {
+        return then(lua, None);
+    }
+    let auto = duel_data(lua).lock().unwrap().auto_place(player, zones);
+    if auto.is_some() {
+        return then(lua, auto);
+    }
+    let shift = if location == Location::MZONE { 0 } else { 8 };
+    let flag = !(zones << shift);
+    let msg = MsgSelectPlace { player, count: 1, flag };
+    ask(lua, Prompt::SelectPlace { player, count: 1, flag }, &msg.encode(), move |lua, answer| match answer {
+        Response::Places(places) => then(lua, places.first().map(|&(_, _, seq)| seq)),
+        other => Err(unexpected(other)),
+    })
+}
+
+/// Put `cards` into zones of `location` one after the other. `zones` gives the player choosing for a card and
+/// the zones left to it once the earlier ones are placed, or None to pass it over; `place` puts the card into
+/// the chosen zone (None when there was none) and tells whether it got there. `then` gets the placed cards.
+pub fn place_each<'lua, Z, P, F>(lua: &'lua Lua, location: Location, mut cards: Vec<CardId>, mut placed: Vec<CardId>, zones: Z, place: P, then: F) -> mlua::Result<MultiValue<'lua>>
+where
+    Z: Fn(&Lua, CardId) -> mlua::Result<Option<(u8, u32)>> + Clone + Send + 'static,
+    P: Fn(&Lua, CardId, Option<u8>) -> mlua::Result<bool> + Clone + Send + 'static,
+    F: for<'a> FnOnce(&'a Lua, Vec<CardId>) -> mlua::Result<MultiValue<'a>> + Send + 'static,
+{
+    loop {
+        if cards.is_empty() {
+            return then(lua, placed);
+        }
+        let card_id = cards.remove(0);
+        if let Some((player, mask)) = zones(lua, card_id)? {
+            return select_place(lua, player, location, mask, move |lua, seq| {
+                if place(lua, card_id, seq)? {
+                    placed.push(card_id);
+                }
+                place_each(lua, location, cards, placed, zones, place, then)
+            });
+        }
+    }
+}
+
+/// Recompute the zones disabled by EFFECT_DISABLE_FIELD and announce a change with MSG_FIELD_DISABLED.
+/// The effect value is a zone mask relative to the effect's controller.
+pub fn refresh_disabled_field(lua: &Lua) -> mlua::Result<()> {
+    let data = duel_data(lua);
+    let effects: Vec<(EffectId, u8)> = {
+        let data_guard = data.lock().unwrap();
+        data_guard.effects.iter().enumerate()
+            .filter(|(_, e)| e.code == EFFECT_DISABLE_FIELD && data_guard.is_effect_active(e))
+            .map(|(i, e)| {
+                let controller = e.player.unwrap_or_else(|| data_guard.cards.get(e.owner.0 as usize).map(|c| c.controller).unwrap_or(0));
+                (EffectId::new(i as u32), controller)
+            })
+            .collect()
+    };
+    let mut disabled = [0u32; 2];
+    for (eid, controller) in effects {
+        let value = effect_value(lua, &data, eid, eid)?;
+        disabled[controller as usize & 1] |= value & 0xffff;
+        disabled[1 - (controller as usize & 1)] |= value >> 16;
+    }
+    let mut data_guard = data.lock().unwrap();
+    if data_guard.field.disabled != disabled {
+        data_guard.field.disabled = disabled;
+        data_guard.write_message(&MsgFieldDisabled { flag: disabled[0] | disabled[1] << 16 }.encode());
+    }
+    Ok(())
+}
+
+/// Register the zone functions on the global `Duel` table.
+pub fn register_zone_functions(lua: &Lua) -> mlua::Result<()> {
+    let duel_table: mlua::Table = lua.globals().get("Duel")?;
+
+    // Duel.CheckLocation(player, location, seq) - whether the zone is free and not disabled
+    duel_table.set("CheckLocation", lua.create_function(|lua, (player, location, seq): (u8, u32, u8)| {
+        refresh_disabled_field(lua)?;
+        let data = duel_data(lua);
+        let data_guard = data.lock().unwrap();
+        Ok(player <= 1 && data_guard.is_location_useable(player, Location::from_bits_truncate(location), seq))
+    })?)?;
+
+    // Duel.SelectDisableField(player, count, s, o[, filter]) -> mask of the chosen zones, relative to `player`
+    set_prompting(lua, &duel_table, "SelectDisableField", |lua, (player, count, s, o, filter): (u8, usize, u32, u32, Option<u32>)| {
+        refresh_disabled_field(lua)?;
+        let selectable = duel_data(lua).lock().unwrap().free_field_zones(player, s, o) & !filter.unwrap_or(0);
+        if (selectable.count_ones() as usize) < count {
+            return ready(lua, 0);
+        }
+        let flag = !selectable;
+        let msg = MsgSelectPlace { player, count: count as u8, flag };
+        let prompt = Prompt::SelectDisField { player, count, flag };
+        ask(lua, prompt, &msg.encode_as(MsgType::SelectDisField), move |lua, answer| match answer {
+            Response::Places(places) => ready(lua, places.into_iter().fold(0u32, |mask, (p, location, seq)| {
+                let shift = if p == player { 0 } else { 16 } + if location == Location::MZONE { 0 } else { 8 };
+                mask | 1 << (shift + seq as u32)
+            })),
+            other => Err(unexpected(other)),
+        })
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::duel::Duel;
+    use crate::core::enums::*;
+    use crate::core::messages::{MsgFieldDisabled, MsgSelectPlace, MsgType};
+    use crate::core::processor::ProcessResult;
+    use crate::core::types::CardId;
+
+    #[test]
+    fn disable_field_effects_block_zones_and_counts() {
+        let duel = Duel::new(0);
+        duel.lua.load(r#"
+            local c = Debug.AddCard(9901, 0, 0, LOCATION_SZONE, 2, POS_FACEUP)
+            local e = Effect.CreateEffect(c)
+            e:SetType(EFFECT_TYPE_FIELD)
+            e:SetCode(EFFECT_DISABLE_FIELD)
+            e:SetRange(LOCATION_SZONE)
+            e:SetValue(function(e) return 0x1 + 0x100 + (0x4 << 16) end)
+            c:RegisterEffect(e)
+        "#).exec().expect("setup");
+        duel.get_message();
+        let (mzone, szone, opp, zone_limited, free): (u32, u32, u32, u32, bool) = duel.lua.load(r#"
+            return Duel.GetLocationCount(0, LOCATION_MZONE), Duel.GetLocationCount(0, LOCATION_SZONE),
+                Duel.GetLocationCount(1, LOCATION_MZONE), Duel.GetLocationCount(0, LOCATION_MZONE, 0, 0, 0x3),
+                Duel.CheckLocation(0, LOCATION_MZONE, 0)
+        "#).eval().unwrap();
+        assert_eq!((mzone, szone, opp), (4, 3, 4), "zone 0 of each own row and the opponent's monster zone 2");
+        assert_eq!(zone_limited, 1, "only zone 1 of the two asked for");
+        assert!(!free);
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.field.disabled, [0x101, 0x4]);
+        assert_eq!(data.message_buffer, MsgFieldDisabled { flag: 0x101 | 0x4 << 16 }.encode());
+        assert_eq!(data.field.find_empty_mzone_slot(1), Some(0));
+    }
+
+    #[test]
+    fn select_place_lets_the_player_choose_a_zone() {
+        let mut duel = Duel::new(0);
+        duel.lua.load(r#"
+            Debug.AddCard(9911, 0, 0, LOCATION_HAND, 0, POS_FACEDOWN)
+            Debug.AddCard(9912, 0, 0, LOCATION_MZONE, 0, POS_FACEUP_ATTACK)
+        "#).exec().expect("setup");
+        duel.get_message();
+        duel.execute_operation(duel.lua.load("count = Duel.Summon(0, Card(0), true, nil)").into_function().unwrap()).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting, "the zone is chosen by the player");
+        let asked = MsgSelectPlace { player: 0, count: 1, flag: !0b11110 }.encode();
+        assert_eq!(duel.get_message(), asked);
+        // Zone 0 is taken, so it is asked again
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 0]);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        assert_eq!(duel.get_message()[0], MsgType::Retry.id());
+        duel.set_responseb(&[0, Location::MZONE.bits() as u8, 3]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let count: u32 = duel.lua.globals().get("count").unwrap();
+        assert_eq!(count, 1);
+        let data = duel.data.lock().unwrap();
+        assert_eq!(data.get_card(CardId::new(0)).map(|c| (c.location, c.sequence)), Some((Location::MZONE, 3)));
+
+        // A single free zone is not asked for
+        assert_eq!(data.auto_place(0, 0b1000), Some(3));
+        assert_eq!(data.auto_place(0, 0b10100), None);
+        assert_eq!(data.auto_place(0, 0), None);
+    }
+
+    #[test]
+    fn select_disable_field_asks_for_the_zones() {
+        let mut duel = Duel::new(0);
+        duel.lua.load("Debug.AddCard(9921, 1, 1, LOCATION_MZONE, 2, POS_FACEUP_ATTACK)").exec().expect("setup");
+        duel.get_message();
+        duel.execute_operation(duel.lua.load("zones = Duel.SelectDisableField(0, 2, 0, LOCATION_MZONE)").into_function().unwrap()).unwrap();
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        let asked = MsgSelectPlace { player: 0, count: 2, flag: !(0b11011 << 16) }.encode_as(MsgType::SelectDisField);
+        assert_eq!(duel.get_message(), asked);
+        // The opponent's occupied zone 2 cannot be chosen
+        duel.set_responseb(&[1, Location::MZONE.bits() as u8, 2, 1, Location::MZONE.bits() as u8, 4]);
+        assert_eq!(duel.process(), ProcessResult::Waiting);
+        duel.set_responseb(&[1, Location::MZONE.bits() as u8, 0, 1, Location::MZONE.bits() as u8, 4]);
+        assert_eq!(duel.process(), ProcessResult::Continue);
+        let zones: u32 = duel.lua.globals().get("zones").unwrap();
+        assert_eq!(zones, (0b10001) << 16);
+    }
+}